@@ -0,0 +1,348 @@
+//! Stable-memory layout shared by the Dooor Rust canisters.
+//!
+//! Every canister declares its [`MemoryManager`] regions once, as a [`Layout`]
+//! of named [`Region`]s with fixed ids and an owning subsystem. The layout is
+//! recorded in a manifest in the reserved region [`MANIFEST_ID`], and
+//! [`Layout::validate`] checks it against that manifest on `init` and
+//! `post_upgrade`, so an upgrade that renumbers, renames or silently drops a
+//! region is refused before any data is read through the wrong structure.
+//!
+//! Rules enforced:
+//! - ids and names are unique within a layout, and [`MANIFEST_ID`] is reserved
+//! - an id recorded on disk keeps its name forever
+//! - a region recorded on disk stays declared, either active or in `retired`
+//! - no undeclared region may hold data
+//!
+//! ```ignore
+//! pub const DB: u8 = 0;
+//! pub const LAYOUT: Layout = Layout {
+//!     regions: &[Region::new(DB, "db.records", "lib")],
+//!     retired: &[],
+//! };
+//! ```
+
+use candid::{CandidType, Deserialize};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::Memory;
+use std::fmt;
+
+/// Region holding the layout manifest; never available to a canister.
+pub const MANIFEST_ID: u8 = 254;
+
+/// Name reported for the manifest region by [`Layout::stats`].
+pub const MANIFEST_NAME: &str = "layout.manifest";
+
+const MAGIC: &[u8; 4] = b"DMLY";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: u64 = 9; // magic (4) || version (1) || body length u32 LE (4)
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// One named region of stable memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub id: u8,
+    /// Stable name recorded on disk, e.g. `"db.records"`.
+    pub name: &'static str,
+    /// Module or subsystem that owns the region, for reporting only.
+    pub owner: &'static str,
+}
+
+impl Region {
+    pub const fn new(id: u8, name: &'static str, owner: &'static str) -> Self {
+        Region { id, name, owner }
+    }
+}
+
+/// All regions of one canister.
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    /// Regions in use.
+    pub regions: &'static [Region],
+    /// Regions that are no longer used. Their ids stay reserved and their data
+    /// is left in place.
+    pub retired: &'static [Region],
+}
+
+/// Why a layout does not match itself or the manifest on disk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The id is declared more than once.
+    DuplicateId(u8),
+    /// The name is declared more than once.
+    DuplicateName(&'static str),
+    /// The id is reserved by this crate.
+    ReservedId(u8),
+    /// The manifest records `id` under another name.
+    Renamed { id: u8, stored: String, declared: String },
+    /// The manifest records a region the layout no longer declares.
+    Dropped { id: u8, name: String },
+    /// An undeclared region holds data.
+    Undeclared { id: u8, pages: u64 },
+    /// The manifest region does not hold a readable manifest.
+    CorruptManifest,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::DuplicateId(id) => write!(f, "memory id {id} is declared twice"),
+            LayoutError::DuplicateName(name) => write!(f, "region {name:?} is declared twice"),
+            LayoutError::ReservedId(id) => write!(f, "memory id {id} is reserved"),
+            LayoutError::Renamed { id, stored, declared } => write!(
+                f,
+                "memory id {id} holds region {stored:?} but is declared as {declared:?}"
+            ),
+            LayoutError::Dropped { id, name } => write!(
+                f,
+                "region {name:?} (memory id {id}) is on disk but not declared; \
+                 keep it or move it to `retired`"
+            ),
+            LayoutError::Undeclared { id, pages } => {
+                write!(f, "undeclared memory id {id} holds {pages} pages")
+            }
+            LayoutError::CorruptManifest => write!(f, "stable memory layout manifest is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Size of one region, as returned by `memory_stats`.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq, Eq)]
+pub struct RegionStats {
+    pub id: u8,
+    pub name: String,
+    pub owner: String,
+    pub retired: bool,
+    /// Wasm pages (64 KiB) allocated to the region.
+    pub pages: u64,
+    pub bytes: u64,
+}
+
+impl Layout {
+    /// The active region with id `id`, if any.
+    pub fn region(&self, id: u8) -> Option<&Region> {
+        self.regions.iter().find(|r| r.id == id)
+    }
+
+    fn declared(&self, id: u8) -> Option<&Region> {
+        self.regions.iter().chain(self.retired).find(|r| r.id == id)
+    }
+
+    /// Checks the layout on its own: unique ids and names, no reserved id.
+    pub fn check(&self) -> Result<(), LayoutError> {
+        let all: Vec<&Region> = self.regions.iter().chain(self.retired).collect();
+        for (i, r) in all.iter().enumerate() {
+            if r.id == MANIFEST_ID {
+                return Err(LayoutError::ReservedId(r.id));
+            }
+            if all[..i].iter().any(|o| o.id == r.id) {
+                return Err(LayoutError::DuplicateId(r.id));
+            }
+            if all[..i].iter().any(|o| o.name == r.name) {
+                return Err(LayoutError::DuplicateName(r.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the layout against the manifest in `mm` and records any new
+    /// regions. Call it first thing in `init` and `post_upgrade`.
+    pub fn validate<M: Memory>(&self, mm: &MemoryManager<M>) -> Result<(), LayoutError> {
+        self.check()?;
+        let manifest = mm.get(MemoryId::new(MANIFEST_ID));
+        for (id, name) in read_manifest(&manifest)? {
+            match self.declared(id) {
+                None => return Err(LayoutError::Dropped { id, name }),
+                Some(r) if r.name != name => {
+                    return Err(LayoutError::Renamed { id, stored: name, declared: r.name.into() })
+                }
+                Some(_) => {}
+            }
+        }
+        for id in (0..MANIFEST_ID).filter(|&id| self.declared(id).is_none()) {
+            let pages = mm.get(MemoryId::new(id)).size();
+            if pages > 0 {
+                return Err(LayoutError::Undeclared { id, pages });
+            }
+        }
+        let mut entries: Vec<(u8, &str)> =
+            self.regions.iter().chain(self.retired).map(|r| (r.id, r.name)).collect();
+        entries.sort_unstable();
+        write_manifest(&manifest, &entries);
+        Ok(())
+    }
+
+    /// Per-region sizes, in id order, including retired regions and the manifest.
+    pub fn stats<M: Memory>(&self, mm: &MemoryManager<M>) -> Vec<RegionStats> {
+        let manifest = Region::new(MANIFEST_ID, MANIFEST_NAME, "memory_layout");
+        let mut regions: Vec<(&Region, bool)> = self
+            .regions
+            .iter()
+            .map(|r| (r, false))
+            .chain(self.retired.iter().map(|r| (r, true)))
+            .chain(std::iter::once((&manifest, false)))
+            .collect();
+        regions.sort_unstable_by_key(|(r, _)| r.id);
+        regions
+            .into_iter()
+            .map(|(r, retired)| {
+                let pages = mm.get(MemoryId::new(r.id)).size();
+                RegionStats {
+                    id: r.id,
+                    name: r.name.into(),
+                    owner: r.owner.into(),
+                    retired,
+                    pages,
+                    bytes: pages * WASM_PAGE_SIZE,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads `(id, name)` entries; an empty region is an empty manifest.
+fn read_manifest<M: Memory>(m: &M) -> Result<Vec<(u8, String)>, LayoutError> {
+    if m.size() == 0 {
+        return Ok(Vec::new());
+    }
+    let mut header = [0u8; HEADER_LEN as usize];
+    m.read(0, &mut header);
+    if &header[..4] != MAGIC || header[4] != FORMAT_VERSION {
+        return Err(LayoutError::CorruptManifest);
+    }
+    let len = u32::from_le_bytes(header[5..9].try_into().unwrap()) as u64;
+    if HEADER_LEN + len > m.size() * WASM_PAGE_SIZE {
+        return Err(LayoutError::CorruptManifest);
+    }
+    let mut body = vec![0u8; len as usize];
+    m.read(HEADER_LEN, &mut body);
+
+    let mut entries = Vec::new();
+    let mut rest = body.as_slice();
+    while let [id, n, tail @ ..] = rest {
+        let n = *n as usize;
+        let name = tail.get(..n).ok_or(LayoutError::CorruptManifest)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| LayoutError::CorruptManifest)?;
+        entries.push((*id, name));
+        rest = &tail[n..];
+    }
+    if !rest.is_empty() {
+        return Err(LayoutError::CorruptManifest);
+    }
+    Ok(entries)
+}
+
+fn write_manifest<M: Memory>(m: &M, entries: &[(u8, &str)]) {
+    let mut body = Vec::new();
+    for (id, name) in entries {
+        assert!(name.len() <= u8::MAX as usize, "region name {name:?} is too long");
+        body.push(*id);
+        body.push(name.len() as u8);
+        body.extend_from_slice(name.as_bytes());
+    }
+    let mut out = MAGIC.to_vec();
+    out.push(FORMAT_VERSION);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+
+    let pages = (out.len() as u64).div_ceil(WASM_PAGE_SIZE);
+    if m.size() < pages && m.grow(pages - m.size()) < 0 {
+        panic!("out of stable memory while writing the layout manifest");
+    }
+    m.write(0, &out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    const A: Region = Region::new(0, "a", "mod_a");
+    const B: Region = Region::new(1, "b", "mod_b");
+
+    fn layout(regions: &'static [Region], retired: &'static [Region]) -> Layout {
+        Layout { regions, retired }
+    }
+
+    fn mm() -> MemoryManager<DefaultMemoryImpl> {
+        MemoryManager::init(DefaultMemoryImpl::default())
+    }
+
+    fn touch(mm: &MemoryManager<DefaultMemoryImpl>, id: u8) {
+        mm.get(MemoryId::new(id)).grow(1);
+    }
+
+    #[test]
+    fn rejects_inconsistent_layouts() {
+        const DUP_NAME: Region = Region::new(2, "a", "mod_c");
+        const MANIFEST: Region = Region::new(MANIFEST_ID, "m", "mod_m");
+        assert_eq!(layout(&[A, A], &[]).check(), Err(LayoutError::DuplicateId(0)));
+        assert_eq!(layout(&[A], &[A]).check(), Err(LayoutError::DuplicateId(0)));
+        assert_eq!(layout(&[A, DUP_NAME], &[]).check(), Err(LayoutError::DuplicateName("a")));
+        assert_eq!(layout(&[MANIFEST], &[]).check(), Err(LayoutError::ReservedId(MANIFEST_ID)));
+        assert_eq!(layout(&[A, B], &[]).check(), Ok(()));
+    }
+
+    #[test]
+    fn records_and_revalidates() {
+        let mm = mm();
+        layout(&[A], &[]).validate(&mm).unwrap();
+        touch(&mm, 0);
+        layout(&[A], &[]).validate(&mm).unwrap();
+        // Adding a region is always allowed.
+        layout(&[A, B], &[]).validate(&mm).unwrap();
+        assert_eq!(
+            read_manifest(&mm.get(MemoryId::new(MANIFEST_ID))).unwrap(),
+            vec![(0, "a".to_string()), (1, "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_renamed_and_dropped_regions() {
+        const RENAMED: Region = Region::new(1, "b2", "mod_b");
+        let mm = mm();
+        layout(&[A, B], &[]).validate(&mm).unwrap();
+        assert_eq!(
+            layout(&[A, RENAMED], &[]).validate(&mm),
+            Err(LayoutError::Renamed { id: 1, stored: "b".into(), declared: "b2".into() })
+        );
+        assert_eq!(
+            layout(&[A], &[]).validate(&mm),
+            Err(LayoutError::Dropped { id: 1, name: "b".into() })
+        );
+        layout(&[A], &[B]).validate(&mm).unwrap();
+    }
+
+    #[test]
+    fn rejects_undeclared_data() {
+        let mm = mm();
+        touch(&mm, 7);
+        assert_eq!(
+            layout(&[A], &[]).validate(&mm),
+            Err(LayoutError::Undeclared { id: 7, pages: 1 })
+        );
+    }
+
+    #[test]
+    fn adopts_existing_canisters() {
+        // Regions written before the manifest existed validate as long as
+        // they are declared.
+        let mm = mm();
+        touch(&mm, 0);
+        touch(&mm, 1);
+        layout(&[A, B], &[]).validate(&mm).unwrap();
+    }
+
+    #[test]
+    fn reports_stats() {
+        let mm = mm();
+        layout(&[A], &[B]).validate(&mm).unwrap();
+        touch(&mm, 0);
+        let stats = layout(&[A], &[B]).stats(&mm);
+        let summary: Vec<_> = stats.iter().map(|s| (s.id, s.retired, s.pages)).collect();
+        assert_eq!(summary, vec![(0, false, 1), (1, true, 0), (MANIFEST_ID, false, 1)]);
+        assert_eq!(stats[0].bytes, WASM_PAGE_SIZE);
+        assert_eq!(stats[0].owner, "mod_a");
+    }
+}
@@ -0,0 +1,267 @@
+// Settles JS promises and runs JS callbacks from outside JS: from the
+// callbacks of inter-canister calls and from timers.
+//
+// An async native takes the id of a promise whose resolve and reject functions
+// JS stored as _azleResolveIds._resolve_{id} and _azleRejectIds._reject_{id},
+// and hands its future to `spawn`. When the future completes, the promise is
+// settled with its output and both entries are deleted, so natives without a
+// JS wrapper that deletes them do not leak them.
+//
+// A native runs inside the JS that called it, which holds RUNTIME borrowed, so
+// nothing a native calls may go through with_context: that is only for code
+// that runs outside JS, like call callbacks and timers. A native settles a
+// promise it can settle at once with its own context (see reject_now), and
+// spawn does the same for a future that is ready on its first poll, such as a
+// call that could not be sent. The event loop of the JS that called the native
+// then runs the settlement.
+//
+// A pending promise can be cancelled with the cancelPromise native (e.g. from
+// the abort handler of an AbortController's signal). It is rejected right away
+// with an AbortError, and the output of its future, which keeps running since
+// a call that was made cannot be recalled, is dropped without entering JS.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    future::Future,
+    pin::Pin,
+    task::{self, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use wasmedge_quickjs::{AsObject, Context, JsValue};
+
+use crate::{js_error, run_event_loop, RUNTIME};
+
+const RESOLVE_IDS: &str = "_azleResolveIds";
+const REJECT_IDS: &str = "_azleRejectIds";
+
+thread_local! {
+    static PENDING_PROMISES: RefCell<BTreeSet<String>> = RefCell::new(BTreeSet::new());
+}
+
+/// Runs `body` with the JS context, for code that is not called from JS.
+pub fn with_context<F>(body: F)
+where
+    F: FnOnce(&mut Context),
+{
+    RUNTIME.with(|runtime| {
+        let mut runtime = runtime.borrow_mut();
+        let runtime = runtime.as_mut().unwrap();
+
+        runtime.run_with_context(body);
+    });
+}
+
+/// Calls `globalThis[registry][name]()` and runs the event loop, trapping if
+/// it throws. Returns false if there is no such function. Timers call their
+/// callbacks this way.
+pub fn run_callback(registry: &str, name: &str) -> bool {
+    let mut found = false;
+
+    with_context(|context| {
+        let callback = match context
+            .get_global()
+            .get(registry)
+            .to_obj()
+            .unwrap()
+            .get(name)
+            .to_function()
+        {
+            Some(callback) => callback,
+            None => return,
+        };
+
+        found = true;
+
+        match js_error::call(context, &callback, &[]) {
+            Ok(_) => run_event_loop(context),
+            Err(error) => js_error::trap(&error),
+        };
+    });
+
+    found
+}
+
+/// Runs `future` and settles the promise `promise_id` with its output, as
+/// `resolve(value)` or `reject(error)`, unless the promise was cancelled first.
+/// `context` is the context of the native that calls this.
+pub fn spawn<F, T, E, R, J>(
+    context: &mut Context,
+    promise_id: String,
+    future: F,
+    resolve: R,
+    reject: J,
+) where
+    F: Future<Output = Result<T, E>> + 'static,
+    R: FnOnce(&mut Context, T) -> JsValue + 'static,
+    J: FnOnce(&mut Context, E) -> JsValue + 'static,
+{
+    let mut future = Box::pin(future);
+
+    // ic_cdk::spawn would poll the future right away too, and settling it from
+    // there through with_context would borrow RUNTIME a second time
+    if let Some(result) = poll_once(future.as_mut()) {
+        settle_result(context, &promise_id, result, resolve, reject);
+
+        return;
+    }
+
+    PENDING_PROMISES.with(|pending| pending.borrow_mut().insert(promise_id.clone()));
+
+    ic_cdk::spawn(async move {
+        let result = future.await;
+
+        if !take_pending(&promise_id) {
+            return;
+        }
+
+        with_context(|context| {
+            settle_result(context, &promise_id, result, resolve, reject);
+
+            run_event_loop(context);
+        });
+    });
+}
+
+/// Rejects the promise `promise_id` from within a native, before anything was
+/// spawned for it. The event loop of the JS that called the native runs it.
+pub fn reject_now(context: &mut Context, promise_id: &str, error: JsValue) {
+    settle(context, promise_id, false, error);
+}
+
+/// Rejects the pending promise `promise_id` with an AbortError and drops the
+/// output of its future. Returns false if the promise was already settled.
+pub fn cancel(context: &mut Context, promise_id: &str) -> bool {
+    if !take_pending(promise_id) {
+        return false;
+    }
+
+    let mut abort_error = context
+        .new_error("The operation was aborted")
+        .to_obj()
+        .unwrap();
+
+    abort_error.set("name", context.new_string("AbortError").into());
+
+    settle(context, promise_id, false, abort_error.into());
+
+    true
+}
+
+/// An Error with `message`, for futures that fail with a String.
+pub fn error(context: &mut Context, message: String) -> JsValue {
+    context.new_error(&message).into()
+}
+
+/// An Error with the rejection message of a failed call.
+pub fn rejection_error(
+    context: &mut Context,
+    rejection: (ic_cdk::api::call::RejectionCode, String),
+) -> JsValue {
+    error(context, rejection_message(&rejection))
+}
+
+pub fn rejection_message(rejection: &(ic_cdk::api::call::RejectionCode, String)) -> String {
+    format!(
+        "Rejection code {rejection_code}, {error_message}",
+        rejection_code = (rejection.0 as i32).to_string(),
+        error_message = rejection.1
+    )
+}
+
+// Polls `future` once with a waker that does nothing. A future that is still
+// pending registers the waker of its next poll instead.
+fn poll_once<F: Future + ?Sized>(future: Pin<&mut F>) -> Option<F::Output> {
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+
+    match future.poll(&mut task::Context::from_waker(&waker)) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
+
+static NOOP_WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_noop_waker, do_nothing, do_nothing, do_nothing);
+
+fn noop_raw_waker() -> RawWaker {
+    RawWaker::new(std::ptr::null(), &NOOP_WAKER_VTABLE)
+}
+
+fn clone_noop_waker(_: *const ()) -> RawWaker {
+    noop_raw_waker()
+}
+
+fn do_nothing(_: *const ()) {}
+
+fn settle_result<T, E, R, J>(
+    context: &mut Context,
+    promise_id: &str,
+    result: Result<T, E>,
+    resolve: R,
+    reject: J,
+) where
+    R: FnOnce(&mut Context, T) -> JsValue,
+    J: FnOnce(&mut Context, E) -> JsValue,
+{
+    match result {
+        Ok(value) => {
+            let js_value = resolve(context, value);
+
+            settle(context, promise_id, true, js_value);
+        }
+        Err(error) => {
+            let js_value = reject(context, error);
+
+            settle(context, promise_id, false, js_value);
+        }
+    };
+}
+
+fn take_pending(promise_id: &str) -> bool {
+    PENDING_PROMISES.with(|pending| pending.borrow_mut().remove(promise_id))
+}
+
+fn settle(context: &mut Context, promise_id: &str, resolved: bool, js_value: JsValue) {
+    let resolve_name = format!("_resolve_{promise_id}");
+    let reject_name = format!("_reject_{promise_id}");
+
+    let global = context.get_global();
+
+    let mut resolve_ids = global.get(RESOLVE_IDS).to_obj().unwrap();
+    let mut reject_ids = global.get(REJECT_IDS).to_obj().unwrap();
+
+    let callback = if resolved {
+        resolve_ids.get(resolve_name.as_str())
+    } else {
+        reject_ids.get(reject_name.as_str())
+    }
+    .to_function()
+    .unwrap();
+
+    resolve_ids.delete(&resolve_name);
+    reject_ids.delete(&reject_name);
+
+    if let Err(error) = js_error::call(context, &callback, &[js_value]) {
+        js_error::trap(&error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_once_returns_the_output_of_a_ready_future() {
+        let mut ready = Box::pin(std::future::ready(7));
+
+        assert_eq!(poll_once(ready.as_mut()), Some(7));
+    }
+
+    #[test]
+    fn poll_once_leaves_a_pending_future_pending() {
+        let mut pending = Box::pin(std::future::pending::<u8>());
+
+        assert_eq!(poll_once(pending.as_mut()), None);
+        assert_eq!(poll_once(pending.as_mut()), None);
+    }
+}
@@ -0,0 +1,264 @@
+// Bounded-wait (best-effort response) inter-canister calls. ic-cdk 0.12 only
+// makes unbounded-wait calls, which keep a call context open for as long as the
+// callee takes to respond, so a hung callee keeps the canister from stopping
+// and thus from being upgraded. A bounded-wait call is rejected with
+// SYS_UNKNOWN once its timeout passes, whether or not the callee responded.
+//
+// The call is made with the ic0 system API directly, the way ic-cdk makes its
+// own calls, adding call_with_best_effort_response before call_perform.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use candid::Principal;
+use wasmedge_quickjs::{AsObject, JsValue};
+
+// The system caps the timeout of a bounded-wait call at 300 seconds
+const MAX_TIMEOUT_SECONDS: u32 = 300;
+
+const SYS_FATAL: u32 = 1;
+const SYS_TRANSIENT: u32 = 2;
+const DESTINATION_INVALID: u32 = 3;
+const CANISTER_REJECT: u32 = 4;
+const CANISTER_ERROR: u32 = 5;
+const SYS_UNKNOWN: u32 = 6;
+
+mod ic0 {
+    #[link(wasm_import_module = "ic0")]
+    extern "C" {
+        pub fn call_new(
+            callee_src: usize,
+            callee_size: usize,
+            name_src: usize,
+            name_size: usize,
+            reply_fun: usize,
+            reply_env: usize,
+            reject_fun: usize,
+            reject_env: usize,
+        );
+        pub fn call_on_cleanup(fun: usize, env: usize);
+        pub fn call_data_append(src: usize, size: usize);
+        pub fn call_cycles_add128(amount_high: u64, amount_low: u64);
+        pub fn call_with_best_effort_response(timeout_seconds: u32);
+        pub fn call_perform() -> u32;
+        pub fn msg_reject_code() -> u32;
+    }
+}
+
+// Why a bounded-wait call failed. code is what JS matches on:
+// - TIMEOUT: the call was rejected with SYS_UNKNOWN after its timeout
+// - SYS_UNKNOWN: the response was lost before the timeout, e.g. under load
+// - SYS_FATAL, SYS_TRANSIENT, DESTINATION_INVALID, CANISTER_REJECT and
+//   CANISTER_ERROR: the reject code of the same name
+// After TIMEOUT and SYS_UNKNOWN the call may or may not have taken effect.
+pub(super) struct CallError {
+    pub code: &'static str,
+    pub reject_code: u32,
+    pub reject_message: String,
+    pub retryable: bool,
+}
+
+impl CallError {
+    fn new(reject_code: u32, reject_message: String, timed_out: bool) -> Self {
+        let code = match reject_code {
+            SYS_UNKNOWN if timed_out => "TIMEOUT",
+            SYS_FATAL => "SYS_FATAL",
+            SYS_TRANSIENT => "SYS_TRANSIENT",
+            DESTINATION_INVALID => "DESTINATION_INVALID",
+            CANISTER_REJECT => "CANISTER_REJECT",
+            CANISTER_ERROR => "CANISTER_ERROR",
+            SYS_UNKNOWN => "SYS_UNKNOWN",
+            _ => "UNKNOWN",
+        };
+
+        CallError {
+            code,
+            reject_code,
+            reject_message,
+            // Retrying a call of unknown outcome is only safe if it is idempotent
+            retryable: matches!(reject_code, SYS_TRANSIENT | SYS_UNKNOWN),
+        }
+    }
+
+    // A call that call_perform refused with error_code, e.g. SYS_TRANSIENT
+    // when the output queue is full. It was never sent.
+    fn not_sent(error_code: u32) -> Self {
+        CallError::new(error_code, "Couldn't send message".to_string(), false)
+    }
+
+    // An Error with code, rejectCode, rejectMessage and retryable fields
+    pub(super) fn to_js(&self, context: &mut wasmedge_quickjs::Context) -> JsValue {
+        let message = format!(
+            "{code}: rejection code {rejection_code}, {error_message}",
+            code = self.code,
+            rejection_code = self.reject_code,
+            error_message = self.reject_message
+        );
+
+        let mut error_js_object = context.new_error(&message).to_obj().unwrap();
+
+        error_js_object.set("code", context.new_string(self.code).into());
+        error_js_object.set("rejectCode", JsValue::Int(self.reject_code as i32));
+        error_js_object.set(
+            "rejectMessage",
+            context.new_string(&self.reject_message).into(),
+        );
+        error_js_object.set("retryable", JsValue::Bool(self.retryable));
+
+        error_js_object.into()
+    }
+}
+
+struct CallState {
+    result: Option<Result<Vec<u8>, CallError>>,
+    waker: Option<Waker>,
+    deadline: u64,
+}
+
+pub(super) struct CallFuture {
+    state: Rc<RefCell<CallState>>,
+}
+
+impl Future for CallFuture {
+    type Output = Result<Vec<u8>, CallError>;
+
+    fn poll(self: Pin<&mut Self>, task_context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(task_context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Makes the call right away; the future resolves to the reply's Candid bytes.
+// timeout_seconds above MAX_TIMEOUT_SECONDS is capped by the system. A call
+// that could not be sent is returned as an error at once, so that the native
+// can reject its promise without going through the async bridge.
+pub(super) fn call_raw(
+    canister_id: Principal,
+    method: &str,
+    args_raw: &[u8],
+    payment: u128,
+    timeout_seconds: u32,
+) -> Result<CallFuture, CallError> {
+    let timeout_nanos = timeout_seconds.min(MAX_TIMEOUT_SECONDS) as u64 * 1_000_000_000;
+
+    let state = Rc::new(RefCell::new(CallState {
+        result: None,
+        waker: None,
+        deadline: ic_cdk::api::time() + timeout_nanos,
+    }));
+
+    // The reply or reject callback, whichever runs, takes this reference back
+    let state_ptr = Rc::into_raw(state.clone()) as usize;
+
+    let callee = canister_id.as_slice();
+
+    let error_code = unsafe {
+        ic0::call_new(
+            callee.as_ptr() as usize,
+            callee.len(),
+            method.as_ptr() as usize,
+            method.len(),
+            callback as usize,
+            state_ptr,
+            callback as usize,
+            state_ptr,
+        );
+        ic0::call_on_cleanup(cleanup as usize, state_ptr);
+        ic0::call_data_append(args_raw.as_ptr() as usize, args_raw.len());
+
+        if payment > 0 {
+            ic0::call_cycles_add128((payment >> 64) as u64, payment as u64);
+        }
+
+        ic0::call_with_best_effort_response(timeout_seconds);
+        ic0::call_perform()
+    };
+
+    if error_code != 0 {
+        // The callbacks will never run
+        unsafe { drop(Rc::from_raw(state_ptr as *const RefCell<CallState>)) };
+
+        return Err(CallError::not_sent(error_code));
+    }
+
+    Ok(CallFuture { state })
+}
+
+extern "C" fn callback(state_ptr: usize) {
+    let state = unsafe { Rc::from_raw(state_ptr as *const RefCell<CallState>) };
+
+    let reject_code = unsafe { ic0::msg_reject_code() };
+
+    let result = if reject_code == 0 {
+        Ok(ic_cdk::api::call::arg_data_raw())
+    } else {
+        let timed_out = ic_cdk::api::time() >= state.borrow().deadline;
+
+        Err(CallError::new(
+            reject_code,
+            ic_cdk::api::call::reject_message(),
+            timed_out,
+        ))
+    };
+
+    state.borrow_mut().result = Some(result);
+
+    let waker = state.borrow_mut().waker.take();
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+// Runs instead if the callback trapped, whose changes, including taking back
+// the reference, were rolled back
+extern "C" fn cleanup(state_ptr: usize) {
+    unsafe { drop(Rc::from_raw(state_ptr as *const RefCell<CallState>)) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsent_calls_report_the_call_perform_error() {
+        let error = CallError::not_sent(SYS_TRANSIENT);
+
+        assert_eq!(error.code, "SYS_TRANSIENT");
+        assert_eq!(error.reject_code, SYS_TRANSIENT);
+        assert_eq!(error.reject_message, "Couldn't send message");
+        assert!(error.retryable);
+
+        let error = CallError::not_sent(SYS_FATAL);
+
+        assert_eq!(error.code, "SYS_FATAL");
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn sys_unknown_is_a_timeout_only_after_the_deadline() {
+        let timed_out = CallError::new(SYS_UNKNOWN, "deadline".to_string(), true);
+        let lost = CallError::new(SYS_UNKNOWN, "lost".to_string(), false);
+
+        assert_eq!(timed_out.code, "TIMEOUT");
+        assert_eq!(lost.code, "SYS_UNKNOWN");
+        assert!(timed_out.retryable && lost.retryable);
+
+        let rejected = CallError::new(CANISTER_REJECT, "no".to_string(), true);
+
+        assert_eq!(rejected.code, "CANISTER_REJECT");
+        assert!(!rejected.retryable);
+    }
+}
@@ -0,0 +1,54 @@
+// callRawBoundedWait(promiseId, canisterId, method, argsRaw, payment,
+// timeoutSeconds) is callRaw128 as a bounded-wait call: it is rejected once
+// timeoutSeconds (at most 300) pass without a response, so a hung callee can
+// no longer block this canister's upgrades. It rejects with an Error carrying
+// code, rejectCode, rejectMessage and retryable fields (see bounded_wait).
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::bounded_wait;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "callRawBoundedWait", argv, call_raw_bounded_wait)
+    }
+}
+
+fn call_raw_bounded_wait(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let canister_id_bytes = args.array_buffer(1)?;
+    let canister_id = candid::Principal::from_slice(&canister_id_bytes);
+
+    let method = args.string(2)?;
+
+    let args_raw = args.array_buffer(3)?;
+
+    let payment: u128 = args.parse(4)?;
+
+    let timeout_seconds: u32 = args.parse(5)?;
+
+    match bounded_wait::call_raw(canister_id, &method, &args_raw, payment, timeout_seconds) {
+        Ok(call_future) => async_bridge::spawn(
+            context,
+            promise_id,
+            call_future,
+            |context, candid_bytes| context.new_array_buffer(&candid_bytes).into(),
+            |context, error| error.to_js(context),
+        ),
+        Err(error) => {
+            let error = error.to_js(context);
+
+            // The caller's event loop runs the rejection
+            async_bridge::reject_now(context, &promise_id, error);
+        }
+    }
+
+    Ok(JsValue::UnDefined)
+}
@@ -0,0 +1,22 @@
+// cancelJob(jobId) removes a job, including one kept as failed. Returns false
+// if there is no such job, e.g. because it already ran.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    scheduler,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "cancelJob", argv, cancel_job)
+    }
+}
+
+fn cancel_job(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let job_id: u64 = args.parse(0)?;
+
+    Ok(JsValue::Bool(scheduler::cancel(job_id)))
+}
@@ -0,0 +1,24 @@
+// cancelPromise(promiseId) drops interest in the result of a pending async
+// native, e.g. when an AbortController's signal aborts: the promise is
+// rejected with an AbortError now and the result is discarded when it comes.
+// Returns false if the promise was already settled.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "cancelPromise", argv, cancel_promise)
+    }
+}
+
+fn cancel_promise(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    Ok(JsValue::Bool(async_bridge::cancel(context, &promise_id)))
+}
@@ -1,19 +1,21 @@
 use wasmedge_quickjs::{Context, JsArrayBuffer, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let candid_path = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "candidCompiler", argv, candid_compiler)
+    }
+}
 
-        let (env, actor) =
-            candid_parser::pretty_check_file(std::path::Path::new(&candid_path)).unwrap();
+fn candid_compiler(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let candid_path = args.string(0)?;
 
-        let result = candid_parser::bindings::javascript::compile(&env, &actor);
+    let (env, actor) =
+        candid_parser::pretty_check_file(std::path::Path::new(&candid_path)).unwrap();
 
-        context.new_string(&result).into()
-    }
+    let result = candid_parser::bindings::javascript::compile(&env, &actor);
+
+    Ok(context.new_string(&result).into())
 }
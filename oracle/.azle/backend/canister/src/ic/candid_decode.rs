@@ -1,17 +1,19 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let candid_encoded = if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(0).unwrap() {
-            js_array_buffer.to_vec()
-        } else {
-            panic!("conversion from JsValue to JsArrayBuffer failed")
-        };
+        native_args::call(context, "candidDecode", argv, candid_decode)
+    }
+}
 
-        let candid_args: candid::IDLArgs = candid::IDLArgs::from_bytes(&candid_encoded).unwrap();
-        let candid_string = candid_args.to_string();
+fn candid_decode(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let candid_encoded = args.array_buffer(0)?;
 
-        context.new_string(&candid_string).into()
-    }
+    let candid_args: candid::IDLArgs = candid::IDLArgs::from_bytes(&candid_encoded).unwrap();
+    let candid_string = candid_args.to_string();
+
+    Ok(context.new_string(&candid_string).into())
 }
@@ -1,17 +1,19 @@
 use wasmedge_quickjs::{Context, JsArrayBuffer, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let candid_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "candidEncode", argv, candid_encode)
+    }
+}
 
-        let candid_args = candid_parser::parse_idl_args(&candid_string).unwrap();
-        let candid_encoded = candid_args.to_bytes().unwrap();
+fn candid_encode(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let candid_string = args.string(0)?;
 
-        context.new_array_buffer(&candid_encoded).into()
-    }
+    let candid_args = candid_parser::parse_idl_args(&candid_string).unwrap();
+    let candid_encoded = candid_args.to_bytes().unwrap();
+
+    Ok(context.new_array_buffer(&candid_encoded).into())
 }
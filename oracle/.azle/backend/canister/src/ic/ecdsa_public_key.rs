@@ -0,0 +1,44 @@
+// ecdsaPublicKey(promiseId, keyIdJson, derivationPathJson) resolves to
+// { publicKey: ArrayBuffer, chainCode: ArrayBuffer } for this canister, with
+// publicKey as a 33-byte compressed SEC1 point.
+
+use ic_cdk::api::management_canister::ecdsa::{EcdsaKeyId, EcdsaPublicKeyArgument};
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::threshold_signatures::{parse_derivation_path, public_key_to_js};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "ecdsaPublicKey", argv, ecdsa_public_key)
+    }
+}
+
+fn ecdsa_public_key(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id: EcdsaKeyId = args.json(1, "an ECDSA key id as JSON")?;
+
+    let derivation_path = parse_derivation_path(args, 2)?;
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::api::management_canister::ecdsa::ecdsa_public_key(EcdsaPublicKeyArgument {
+            canister_id: None,
+            derivation_path,
+            key_id,
+        }),
+        |context, (response,)| {
+            public_key_to_js(context, &response.public_key, &response.chain_code)
+        },
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
@@ -0,0 +1,405 @@
+// httpRequest(promiseId, requestJson, body, cyclesBudget) performs an HTTPS
+// outcall through the management canister from Rust, so JS pays neither the
+// Candid encoding of http_request nor a QuickJS transform.
+//
+// requestJson is
+//
+//     {
+//         "url": "https://...",
+//         "method": "get" | "post" | "head",
+//         "headers": [{ "name": "...", "value": "..." }],
+//         "maxResponseBytes": 500000,
+//         "transform": {
+//             "stripHeaders": ["*"],
+//             "keepJsonFields": ["price", "timestamp"],
+//             "jsonPointers": ["/data/0/price"]
+//         }
+//     }
+//
+// where only url is required. The transform rules travel as the transform
+// context and are applied by the _azle_http_transform query below; without
+// transform, the default rules strip all headers:
+// - stripHeaders removes response headers by case-insensitive name, "*" removes
+//   all of them (the default, since headers like Date break consensus)
+// - keepJsonFields keeps only these top-level fields of a JSON object body
+// - jsonPointers then replaces a JSON body with an object mapping each RFC 6901
+//   pointer to the value it selects, omitting pointers that select nothing
+// A rewritten JSON body is serialized with sorted keys and no whitespace. A
+// body that is not JSON is left as it is.
+//
+// cyclesBudget (a decimal string) is attached to the call; unused cycles are
+// refunded. If the estimated cost of the outcall exceeds it, the promise is
+// rejected without calling. The promise resolves to
+// { status: string, headers: [{ name, value }], body: ArrayBuffer }.
+
+use std::collections::BTreeMap;
+
+use ic_cdk::api::management_canister::http_request::{
+    CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext, TransformFunc,
+};
+use serde::{Deserialize, Serialize};
+use wasmedge_quickjs::{AsObject, Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+const TRANSFORM_METHOD: &str = "_azle_http_transform";
+
+// The management canister's limit and default for max_response_bytes
+const MAX_RESPONSE_BYTES: u64 = 2_000_000;
+
+// Outcall prices on a 13-node application subnet
+const SUBNET_SIZE: u128 = 13;
+const BASE_FEE: u128 = (3_000_000 + 60_000 * SUBNET_SIZE) * SUBNET_SIZE;
+const REQUEST_BYTE_FEE: u128 = 400 * SUBNET_SIZE;
+const RESPONSE_BYTE_FEE: u128 = 800 * SUBNET_SIZE;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    url: String,
+    #[serde(default)]
+    method: Method,
+    #[serde(default)]
+    headers: Vec<Header>,
+    max_response_bytes: Option<u64>,
+    transform: Option<TransformRules>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum Method {
+    #[default]
+    Get,
+    Post,
+    Head,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransformRules {
+    #[serde(default = "strip_all_headers")]
+    strip_headers: Vec<String>,
+    #[serde(default)]
+    keep_json_fields: Vec<String>,
+    #[serde(default)]
+    json_pointers: Vec<String>,
+}
+
+fn strip_all_headers() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+impl Default for TransformRules {
+    fn default() -> Self {
+        TransformRules {
+            strip_headers: strip_all_headers(),
+            keep_json_fields: vec![],
+            json_pointers: vec![],
+        }
+    }
+}
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "httpRequest", argv, http_request)
+    }
+}
+
+fn http_request(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let request: Request = args.json(1, "an HTTP request as JSON")?;
+
+    let body = args.optional_array_buffer(2)?;
+
+    let cycles_budget: u128 = args.parse(3)?;
+
+    // Every outcall is transformed, so that headers like Date never reach
+    // consensus unless the caller keeps them explicitly
+    let rules = request.transform.unwrap_or_default();
+
+    let transform = TransformContext {
+        function: TransformFunc(candid::Func {
+            principal: ic_cdk::id(),
+            method: TRANSFORM_METHOD.to_string(),
+        }),
+        context: serde_json::to_vec(&rules).unwrap(),
+    };
+
+    let http_request_argument = CanisterHttpRequestArgument {
+        url: request.url,
+        max_response_bytes: request.max_response_bytes,
+        method: match request.method {
+            Method::Get => HttpMethod::GET,
+            Method::Post => HttpMethod::POST,
+            Method::Head => HttpMethod::HEAD,
+        },
+        headers: request
+            .headers
+            .into_iter()
+            .map(|header| HttpHeader {
+                name: header.name,
+                value: header.value,
+            })
+            .collect(),
+        body,
+        transform: Some(transform),
+    };
+
+    let cost = estimate_cost(&http_request_argument);
+
+    if cost > cycles_budget {
+        let error_message = format!(
+            "httpRequest: the outcall costs an estimated {} cycles, over the budget of {}",
+            cost, cycles_budget
+        );
+        let error: JsValue = context.new_error(&error_message).into();
+
+        // The caller's event loop runs the rejection
+        async_bridge::reject_now(context, &promise_id, error);
+
+        return Ok(JsValue::UnDefined);
+    }
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::api::management_canister::http_request::http_request(
+            http_request_argument,
+            cycles_budget,
+        ),
+        |context, (response,)| response_to_js(context, &response),
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
+
+fn estimate_cost(argument: &CanisterHttpRequestArgument) -> u128 {
+    let request_bytes = argument.url.len()
+        + argument
+            .headers
+            .iter()
+            .map(|header| header.name.len() + header.value.len())
+            .sum::<usize>()
+        + argument.body.as_ref().map_or(0, |body| body.len())
+        + argument.transform.as_ref().map_or(0, |transform| {
+            transform.function.0.method.len() + transform.context.len()
+        });
+    let response_bytes = argument.max_response_bytes.unwrap_or(MAX_RESPONSE_BYTES);
+
+    BASE_FEE + REQUEST_BYTE_FEE * request_bytes as u128 + RESPONSE_BYTE_FEE * response_bytes as u128
+}
+
+fn response_to_js(context: &mut Context, response: &HttpResponse) -> JsValue {
+    let mut headers = context.new_array();
+
+    for (index, header) in response.headers.iter().enumerate() {
+        let mut header_js_object = context.new_object();
+
+        header_js_object.set("name", context.new_string(&header.name).into());
+        header_js_object.set("value", context.new_string(&header.value).into());
+
+        headers.put(index, header_js_object.into());
+    }
+
+    let mut response_js_object = context.new_object();
+
+    response_js_object.set(
+        "status",
+        context.new_string(&response.status.0.to_string()).into(),
+    );
+    response_js_object.set("headers", headers.into());
+    response_js_object.set("body", context.new_array_buffer(&response.body).into());
+
+    response_js_object.into()
+}
+
+#[ic_cdk_macros::query(name = "_azle_http_transform")]
+fn http_transform(args: TransformArgs) -> HttpResponse {
+    // Only this canister builds transform contexts, so they always parse
+    let rules: TransformRules = serde_json::from_slice(&args.context).unwrap_or_default();
+
+    apply_transform(&rules, args.response)
+}
+
+fn apply_transform(rules: &TransformRules, mut response: HttpResponse) -> HttpResponse {
+    if rules.strip_headers.iter().any(|name| name == "*") {
+        response.headers.clear();
+    } else {
+        response.headers.retain(|header| {
+            !rules
+                .strip_headers
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&header.name))
+        });
+    }
+
+    if rules.keep_json_fields.is_empty() && rules.json_pointers.is_empty() {
+        return response;
+    }
+
+    let mut json: serde_json::Value = match serde_json::from_slice(&response.body) {
+        Ok(json) => json,
+        Err(_) => return response,
+    };
+
+    if !rules.keep_json_fields.is_empty() {
+        if let serde_json::Value::Object(fields) = &mut json {
+            fields.retain(|name, _| rules.keep_json_fields.contains(name));
+        }
+    }
+
+    if !rules.json_pointers.is_empty() {
+        let selected: BTreeMap<&str, &serde_json::Value> = rules
+            .json_pointers
+            .iter()
+            .filter_map(|pointer| Some((pointer.as_str(), json.pointer(pointer)?)))
+            .collect();
+
+        json = serde_json::to_value(selected).unwrap();
+    }
+
+    response.body = serde_json::to_vec(&json).unwrap();
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> HttpHeader {
+        HttpHeader {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn response(headers: Vec<HttpHeader>, body: &str) -> HttpResponse {
+        HttpResponse {
+            status: candid::Nat::from(200u32),
+            headers,
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn rules(
+        strip_headers: &[&str],
+        keep_json_fields: &[&str],
+        json_pointers: &[&str],
+    ) -> TransformRules {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+        TransformRules {
+            strip_headers: strings(strip_headers),
+            keep_json_fields: strings(keep_json_fields),
+            json_pointers: strings(json_pointers),
+        }
+    }
+
+    fn argument(url: &str, max_response_bytes: Option<u64>) -> CanisterHttpRequestArgument {
+        CanisterHttpRequestArgument {
+            url: url.to_string(),
+            max_response_bytes,
+            method: HttpMethod::GET,
+            headers: vec![],
+            body: None,
+            transform: None,
+        }
+    }
+
+    #[test]
+    fn default_rules_strip_all_headers() {
+        let omitted: TransformRules = serde_json::from_str("{}").unwrap();
+        let headers = vec![header("Date", "now"), header("Content-Type", "text/plain")];
+
+        for rules in [TransformRules::default(), omitted] {
+            let transformed = apply_transform(&rules, response(headers.clone(), "body"));
+
+            assert!(transformed.headers.is_empty());
+            assert_eq!(transformed.body, b"body".to_vec());
+        }
+    }
+
+    #[test]
+    fn strips_named_headers_ignoring_case() {
+        let headers = vec![header("Date", "now"), header("Content-Type", "text/plain")];
+
+        let transformed = apply_transform(&rules(&["date"], &[], &[]), response(headers, ""));
+
+        assert_eq!(
+            transformed.headers,
+            vec![header("Content-Type", "text/plain")]
+        );
+    }
+
+    #[test]
+    fn keeps_json_fields_and_selects_pointers() {
+        let body = r#"{"price": 7, "timestamp": 1, "noise": {"a": 1}, "data": [{"price": 8}]}"#;
+
+        let kept = apply_transform(
+            &rules(&[], &["timestamp", "price"], &[]),
+            response(vec![], body),
+        );
+        assert_eq!(kept.body, br#"{"price":7,"timestamp":1}"#.to_vec());
+
+        let selected = apply_transform(
+            &rules(&[], &[], &["/data/0/price", "/missing"]),
+            response(vec![], body),
+        );
+        assert_eq!(selected.body, br#"{"/data/0/price":8}"#.to_vec());
+    }
+
+    #[test]
+    fn leaves_bodies_that_are_not_json() {
+        let transformed =
+            apply_transform(&rules(&[], &["price"], &[]), response(vec![], "price=7"));
+
+        assert_eq!(transformed.body, b"price=7".to_vec());
+    }
+
+    #[test]
+    fn estimates_cost_from_request_and_response_bytes() {
+        let url = "https://example.com";
+
+        assert_eq!(
+            estimate_cost(&argument(url, Some(1_000))),
+            BASE_FEE + REQUEST_BYTE_FEE * url.len() as u128 + RESPONSE_BYTE_FEE * 1_000
+        );
+        assert_eq!(
+            estimate_cost(&argument(url, None)),
+            BASE_FEE
+                + REQUEST_BYTE_FEE * url.len() as u128
+                + RESPONSE_BYTE_FEE * MAX_RESPONSE_BYTES as u128
+        );
+
+        let mut with_extras = argument(url, Some(1_000));
+        with_extras.headers = vec![header("Accept", "json")];
+        with_extras.body = Some(vec![0; 5]);
+        with_extras.transform = Some(TransformContext {
+            function: TransformFunc(candid::Func {
+                principal: candid::Principal::anonymous(),
+                method: TRANSFORM_METHOD.to_string(),
+            }),
+            context: vec![0; 3],
+        });
+        let extra_bytes = "Acceptjson".len() + 5 + TRANSFORM_METHOD.len() + 3;
+
+        assert_eq!(
+            estimate_cost(&with_extras),
+            estimate_cost(&argument(url, Some(1_000))) + REQUEST_BYTE_FEE * extra_bytes as u128
+        );
+    }
+}
@@ -1,16 +1,18 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let principal_bytes = if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(0).unwrap() {
-            js_array_buffer.to_vec()
-        } else {
-            panic!("conversion from JsValue to JsArrayBuffer failed")
-        };
+        native_args::call(context, "isController", argv, is_controller)
+    }
+}
 
-        let principal = candid::Principal::from_slice(&principal_bytes);
+fn is_controller(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let principal_bytes = args.array_buffer(0)?;
 
-        ic_cdk::api::is_controller(&principal).into()
-    }
+    let principal = candid::Principal::from_slice(&principal_bytes);
+
+    Ok(ic_cdk::api::is_controller(&principal).into())
 }
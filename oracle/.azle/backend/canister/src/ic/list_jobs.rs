@@ -0,0 +1,78 @@
+// listJobs() returns the stored jobs as
+// { id, callbackName, args: ArrayBuffer, nextRunNanos, intervalNanos, attempt,
+// maxAttempts, lastError, runningSinceNanos, failedAtNanos }, with ids and
+// nanoseconds as decimal strings and intervalNanos, lastError,
+// runningSinceNanos and failedAtNanos undefined when not set. While a run is in
+// flight, nextRunNanos is when it times out. A job that runs once and used up
+// its attempts has failedAtNanos set and stays listed until cancelJob.
+
+use wasmedge_quickjs::{AsObject, Context, JsFn, JsValue};
+
+use crate::scheduler::{self, Job};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        let mut jobs_js_array = context.new_array();
+
+        for (index, job) in scheduler::list().iter().enumerate() {
+            let job_js_value = job_to_js(context, job);
+
+            jobs_js_array.put(index, job_js_value);
+        }
+
+        jobs_js_array.into()
+    }
+}
+
+fn job_to_js(context: &mut Context, job: &Job) -> JsValue {
+    let mut job_js_object = context.new_object();
+
+    job_js_object.set("id", context.new_string(&job.id.to_string()).into());
+    job_js_object.set(
+        "callbackName",
+        context.new_string(&job.callback_name).into(),
+    );
+    job_js_object.set("args", context.new_array_buffer(&job.args).into());
+    job_js_object.set(
+        "nextRunNanos",
+        context.new_string(&job.next_run_nanos.to_string()).into(),
+    );
+    job_js_object.set(
+        "intervalNanos",
+        match job.interval_nanos {
+            Some(interval_nanos) => context.new_string(&interval_nanos.to_string()).into(),
+            None => JsValue::UnDefined,
+        },
+    );
+    job_js_object.set("attempt", JsValue::Int(job.attempt as i32));
+    job_js_object.set(
+        "maxAttempts",
+        JsValue::Int(job.retry_policy.max_attempts as i32),
+    );
+    job_js_object.set(
+        "lastError",
+        match &job.last_error {
+            Some(last_error) => context.new_string(last_error).into(),
+            None => JsValue::UnDefined,
+        },
+    );
+    job_js_object.set(
+        "runningSinceNanos",
+        match job.running_since_nanos {
+            Some(running_since_nanos) => {
+                context.new_string(&running_since_nanos.to_string()).into()
+            }
+            None => JsValue::UnDefined,
+        },
+    );
+    job_js_object.set(
+        "failedAtNanos",
+        match job.failed_at_nanos {
+            Some(failed_at_nanos) => context.new_string(&failed_at_nanos.to_string()).into(),
+            None => JsValue::UnDefined,
+        },
+    );
+
+    job_js_object.into()
+}
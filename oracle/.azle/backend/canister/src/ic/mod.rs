@@ -1,9 +1,13 @@
 mod accept_message;
 mod arg_data_raw;
 mod arg_data_raw_size;
+mod bounded_wait;
 mod call_raw;
 mod call_raw128;
+mod call_raw_bounded_wait;
 mod caller;
+mod cancel_job;
+mod cancel_promise;
 mod candid_compiler;
 mod candid_decode;
 mod candid_encode;
@@ -12,9 +16,12 @@ mod canister_balance128;
 mod canister_version;
 mod clear_timer;
 mod data_certificate;
+mod ecdsa_public_key;
+mod http_request;
 mod id;
 mod instruction_counter;
 mod is_controller;
+mod list_jobs;
 mod method_name;
 mod msg_cycles_accept;
 mod msg_cycles_accept128;
@@ -29,21 +36,36 @@ mod reject;
 mod reject_code;
 mod reject_message;
 mod reply_raw;
+mod schedule_job;
+mod schnorr_public_key;
 mod set_certified_data;
 mod set_timer;
 mod set_timer_interval;
+mod set_timer_interval_nanos;
+mod set_timer_nanos;
+mod settle_job;
+mod sign_with_ecdsa;
+mod sign_with_schnorr;
 mod stable64_grow;
 mod stable64_read;
 mod stable64_size;
 mod stable64_write;
 mod stable_b_tree_map_contains_key;
+mod stable_b_tree_map_entries;
+mod stable_b_tree_map_first_key_value;
 mod stable_b_tree_map_get;
 mod stable_b_tree_map_init;
 mod stable_b_tree_map_insert;
 mod stable_b_tree_map_is_empty;
 mod stable_b_tree_map_items;
 mod stable_b_tree_map_keys;
+mod stable_b_tree_map_last_key_value;
 mod stable_b_tree_map_len;
+mod stable_b_tree_map_lookup;
+mod stable_b_tree_map_pop_first;
+mod stable_b_tree_map_pop_last;
+mod stable_b_tree_map_prefix;
+mod stable_b_tree_map_range;
 mod stable_b_tree_map_remove;
 mod stable_b_tree_map_values;
 mod stable_bytes;
@@ -51,8 +73,14 @@ mod stable_grow;
 mod stable_read;
 mod stable_size;
 mod stable_write;
+mod threshold_signatures;
 mod time;
 mod trap;
+mod vetkd;
+mod vetkd_derive_key;
+mod vetkd_ibe_encrypt;
+mod vetkd_public_key;
+mod vetkd_verify_bls;
 
 use wasmedge_quickjs::AsObject;
 
@@ -93,11 +121,32 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "callRawBoundedWait",
+        context
+            .new_function::<call_raw_bounded_wait::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "caller",
         context.new_function::<caller::NativeFunction>("").into(),
     );
 
+    ic.set(
+        "cancelJob",
+        context
+            .new_function::<cancel_job::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "cancelPromise",
+        context
+            .new_function::<cancel_promise::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "candidCompiler",
         context
@@ -154,6 +203,20 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "ecdsaPublicKey",
+        context
+            .new_function::<ecdsa_public_key::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "httpRequest",
+        context
+            .new_function::<http_request::NativeFunction>("")
+            .into(),
+    );
+
     ic.set("id", context.new_function::<id::NativeFunction>("").into());
 
     ic.set(
@@ -170,6 +233,11 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "listJobs",
+        context.new_function::<list_jobs::NativeFunction>("").into(),
+    );
+
     ic.set(
         "methodName",
         context
@@ -262,6 +330,20 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
         context.new_function::<reply_raw::NativeFunction>("").into(),
     );
 
+    ic.set(
+        "scheduleJob",
+        context
+            .new_function::<schedule_job::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "schnorrPublicKey",
+        context
+            .new_function::<schnorr_public_key::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "setCertifiedData",
         context
@@ -281,6 +363,41 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "setTimerIntervalNanos",
+        context
+            .new_function::<set_timer_interval_nanos::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "setTimerNanos",
+        context
+            .new_function::<set_timer_nanos::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "settleJob",
+        context
+            .new_function::<settle_job::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "signWithEcdsa",
+        context
+            .new_function::<sign_with_ecdsa::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "signWithSchnorr",
+        context
+            .new_function::<sign_with_schnorr::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "stable64Grow",
         context
@@ -351,6 +468,13 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "stableBTreeMapFirstKeyValue",
+        context
+            .new_function::<stable_b_tree_map_first_key_value::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "stableBTreeMapGet",
         context
@@ -393,6 +517,13 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "stableBTreeMapLastKeyValue",
+        context
+            .new_function::<stable_b_tree_map_last_key_value::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "stableBTreeMapLen",
         context
@@ -400,6 +531,34 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
             .into(),
     );
 
+    ic.set(
+        "stableBTreeMapPopFirst",
+        context
+            .new_function::<stable_b_tree_map_pop_first::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "stableBTreeMapPopLast",
+        context
+            .new_function::<stable_b_tree_map_pop_last::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "stableBTreeMapPrefix",
+        context
+            .new_function::<stable_b_tree_map_prefix::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "stableBTreeMapRange",
+        context
+            .new_function::<stable_b_tree_map_range::NativeFunction>("")
+            .into(),
+    );
+
     ic.set(
         "stableBTreeMapRemove",
         context
@@ -424,5 +583,33 @@ pub fn register(context: &mut wasmedge_quickjs::Context) {
         context.new_function::<trap::NativeFunction>("").into(),
     );
 
+    ic.set(
+        "vetkdDeriveKey",
+        context
+            .new_function::<vetkd_derive_key::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "vetkdIbeEncrypt",
+        context
+            .new_function::<vetkd_ibe_encrypt::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "vetkdPublicKey",
+        context
+            .new_function::<vetkd_public_key::NativeFunction>("")
+            .into(),
+    );
+
+    ic.set(
+        "vetkdVerifyBls",
+        context
+            .new_function::<vetkd_verify_bls::NativeFunction>("")
+            .into(),
+    );
+
     context.get_global().set("_azleIc", ic.into());
 }
@@ -1,17 +1,18 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let max_amount_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-        let max_amount: u128 = max_amount_string.parse().unwrap();
-
-        context
-            .new_string(&ic_cdk::api::call::msg_cycles_accept128(max_amount).to_string())
-            .into()
+        native_args::call(context, "msgCyclesAccept128", argv, msg_cycles_accept128)
     }
 }
+
+fn msg_cycles_accept128(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let max_amount: u128 = args.parse(0)?;
+
+    Ok(context
+        .new_string(&ic_cdk::api::call::msg_cycles_accept128(max_amount).to_string())
+        .into())
+}
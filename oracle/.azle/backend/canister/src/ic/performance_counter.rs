@@ -1,19 +1,18 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let counter_type_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-
-        context
-            .new_string(
-                &ic_cdk::api::call::performance_counter(counter_type_string.parse().unwrap())
-                    .to_string(),
-            )
-            .into()
+        native_args::call(context, "performanceCounter", argv, performance_counter)
     }
 }
+
+fn performance_counter(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let counter_type: u32 = args.parse(0)?;
+
+    Ok(context
+        .new_string(&ic_cdk::api::call::performance_counter(counter_type).to_string())
+        .into())
+}
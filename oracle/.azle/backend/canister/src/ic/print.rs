@@ -1,17 +1,20 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let first_arg_option = argv.get(0);
-
-        if let Some(first_arg) = first_arg_option {
-            let string_to_print = first_arg.clone().to_string().unwrap().to_string();
-            ic_cdk::print(string_to_print);
-        } else {
-            ic_cdk::print("");
-        }
+        native_args::call(context, "print", argv, print)
+    }
+}
 
-        JsValue::UnDefined
+fn print(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    if args.get(0).is_some() {
+        ic_cdk::print(args.string(0)?);
+    } else {
+        ic_cdk::print("");
     }
+
+    Ok(JsValue::UnDefined)
 }
@@ -1,16 +1,18 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let message = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "reject", argv, reject)
+    }
+}
 
-        ic_cdk::api::call::reject(&message);
+fn reject(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let message = args.string(0)?;
 
-        JsValue::UnDefined
-    }
+    ic_cdk::api::call::reject(&message);
+
+    Ok(JsValue::UnDefined)
 }
@@ -1,19 +1,18 @@
 use wasmedge_quickjs::{Context, JsArrayBuffer, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let arg_0_js_value = argv.get(0).unwrap();
+        native_args::call(context, "replyRaw", argv, reply_raw)
+    }
+}
 
-        match arg_0_js_value {
-            JsValue::ArrayBuffer(js_array_buffer) => {
-                let buf = js_array_buffer.to_vec();
+fn reply_raw(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let buf = args.array_buffer(0)?;
 
-                ic_cdk::api::call::reply_raw(&buf);
-            }
-            _ => {}
-        };
+    ic_cdk::api::call::reply_raw(&buf);
 
-        JsValue::UnDefined
-    }
+    Ok(JsValue::UnDefined)
 }
@@ -0,0 +1,97 @@
+// scheduleJob(callbackName, args, delayNanos, optionsJson) stores a job that
+// calls _azleJobCallbacks[callbackName](args) after delayNanos (a decimal
+// string) and returns its id. The job survives upgrades (see scheduler).
+// optionsJson is
+//
+//     {
+//         "intervalNanos": 600000000000,
+//         "maxAttempts": 5,
+//         "initialBackoffNanos": 1000000000,
+//         "maxBackoffNanos": 3600000000000,
+//         "backoffMultiplier": 2,
+//         "runTimeoutNanos": 600000000000
+//     }
+//
+// where every field is optional: without intervalNanos the job runs once, and
+// by default a failed run is not retried. An attempt that has not settled
+// after runTimeoutNanos (10 minutes by default) counts as failed; until then
+// the job does not run again.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    scheduler::{self, RetryPolicy},
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Options {
+    interval_nanos: Option<u64>,
+    max_attempts: u32,
+    initial_backoff_nanos: u64,
+    max_backoff_nanos: u64,
+    backoff_multiplier: u32,
+    run_timeout_nanos: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            interval_nanos: None,
+            max_attempts: 1,
+            initial_backoff_nanos: 1_000_000_000,
+            max_backoff_nanos: 3_600_000_000_000,
+            backoff_multiplier: 2,
+            run_timeout_nanos: 600_000_000_000,
+        }
+    }
+}
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "scheduleJob", argv, schedule_job)
+    }
+}
+
+fn schedule_job(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let callback_name = args.string(0)?;
+
+    let job_args = args.array_buffer(1)?;
+
+    let delay_nanos: u64 = args.parse(2)?;
+
+    let options: Options = args.json(3, "job options as JSON")?;
+    if options.max_attempts == 0
+        || options.interval_nanos == Some(0)
+        || options.run_timeout_nanos == 0
+    {
+        return Err(ArgError {
+            found: "maxAttempts, intervalNanos or runTimeoutNanos 0".to_string(),
+            ..args.error(
+                3,
+                "job options with positive maxAttempts, intervalNanos and runTimeoutNanos",
+            )
+        });
+    }
+
+    let job_id = scheduler::schedule(
+        callback_name,
+        job_args,
+        Duration::from_nanos(delay_nanos),
+        options.interval_nanos.map(Duration::from_nanos),
+        RetryPolicy {
+            max_attempts: options.max_attempts,
+            initial_backoff_nanos: options.initial_backoff_nanos,
+            max_backoff_nanos: options.max_backoff_nanos,
+            backoff_multiplier: options.backoff_multiplier,
+            run_timeout_nanos: options.run_timeout_nanos,
+        },
+    );
+
+    Ok(context.new_string(&job_id.to_string()).into())
+}
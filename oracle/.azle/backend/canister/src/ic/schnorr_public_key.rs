@@ -0,0 +1,52 @@
+// schnorrPublicKey(promiseId, keyIdJson, derivationPathJson) resolves to
+// { publicKey: ArrayBuffer, chainCode: ArrayBuffer } for this canister:
+// a 33-byte compressed point for BIP340 keys (drop the first byte for the
+// x-only key) and a 32-byte key for Ed25519.
+
+use candid::Principal;
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::threshold_signatures::{
+    parse_derivation_path, public_key_to_js, SchnorrKeyId, SchnorrPublicKeyArgument,
+    SchnorrPublicKeyResponse,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "schnorrPublicKey", argv, schnorr_public_key)
+    }
+}
+
+fn schnorr_public_key(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id: SchnorrKeyId = args.json(1, "a Schnorr key id as JSON")?;
+
+    let derivation_path = parse_derivation_path(args, 2)?;
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::call::<_, (SchnorrPublicKeyResponse,)>(
+            Principal::management_canister(),
+            "schnorr_public_key",
+            (SchnorrPublicKeyArgument {
+                canister_id: None,
+                derivation_path,
+                key_id,
+            },),
+        ),
+        |context, (response,)| {
+            public_key_to_js(context, &response.public_key, &response.chain_code)
+        },
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
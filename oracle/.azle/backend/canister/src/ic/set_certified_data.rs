@@ -1,17 +1,18 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let certified_data_bytes =
-            if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(0).unwrap() {
-                js_array_buffer.to_vec()
-            } else {
-                panic!("conversion from JsValue to JsArrayBuffer failed")
-            };
+        native_args::call(context, "setCertifiedData", argv, set_certified_data)
+    }
+}
 
-        ic_cdk::api::set_certified_data(&certified_data_bytes);
+fn set_certified_data(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let certified_data_bytes = args.array_buffer(0)?;
 
-        JsValue::UnDefined
-    }
+    ic_cdk::api::set_certified_data(&certified_data_bytes);
+
+    Ok(JsValue::UnDefined)
 }
@@ -0,0 +1,41 @@
+// setTimerIntervalNanos(intervalNanos, callbackName, persistent) is
+// setTimerInterval with an interval in nanoseconds (a decimal string), which
+// must not be 0. See setTimerNanos for persistent.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    timers,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(
+            context,
+            "setTimerIntervalNanos",
+            argv,
+            set_timer_interval_nanos,
+        )
+    }
+}
+
+fn set_timer_interval_nanos(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let interval_nanos: u64 = args.parse(0)?;
+    if interval_nanos == 0 {
+        return Err(ArgError {
+            found: "\"0\"".to_string(),
+            ..args.error(0, "a string containing a positive u64")
+        });
+    }
+    let interval = core::time::Duration::from_nanos(interval_nanos);
+
+    let callback_name = args.string(1)?;
+
+    let persistent = args.bool(2)?;
+
+    let timer_id = timers::set(interval, Some(interval), callback_name, persistent);
+
+    Ok(context.new_string(&timer_id.to_string()).into())
+}
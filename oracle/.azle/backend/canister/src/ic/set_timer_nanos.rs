@@ -0,0 +1,31 @@
+// setTimerNanos(delayNanos, callbackName, persistent) is setTimer with a delay
+// in nanoseconds (a decimal string), e.g. 250_000_000 for Timer(250 ms). A
+// persistent timer survives upgrades (see timers); its callbackName must be
+// registered again in _azleTimerCallbacks by the JS post_upgrade.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    timers,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "setTimerNanos", argv, set_timer_nanos)
+    }
+}
+
+fn set_timer_nanos(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let delay_nanos: u64 = args.parse(0)?;
+    let delay = core::time::Duration::from_nanos(delay_nanos);
+
+    let callback_name = args.string(1)?;
+
+    let persistent = args.bool(2)?;
+
+    let timer_id = timers::set(delay, None, callback_name, persistent);
+
+    Ok(context.new_string(&timer_id.to_string()).into())
+}
@@ -0,0 +1,33 @@
+// settleJob(jobId, runId, succeeded, errorMessage) reports the outcome of the
+// attempt runId of a job. Only the _azleRunJob trampoline of the scheduler
+// calls it, and only the attempt in flight can settle its job.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    scheduler,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "settleJob", argv, settle_job)
+    }
+}
+
+fn settle_job(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let job_id: u64 = args.parse(0)?;
+
+    let run_id: u64 = args.parse(1)?;
+
+    let result = if args.bool(2)? {
+        Ok(())
+    } else {
+        Err(args.string(3)?)
+    };
+
+    scheduler::settle(job_id, run_id, result);
+
+    Ok(JsValue::UnDefined)
+}
@@ -0,0 +1,93 @@
+// signWithEcdsa(promiseId, keyIdJson, derivationPathJson, messageHash, format)
+// resolves to the signature of the 32-byte messageHash, encoded as format:
+// - "compact": r || s (64 bytes), e.g. for JWS ES256K
+// - "der": ASN.1 DER
+// - "ethereum": r || s || v (65 bytes) with low s and v = 27 + recovery id;
+//   add 8 + 2 * chainId - 27 to v for EIP-155 legacy transactions
+// The ethereum format also fetches the public key to find the recovery id.
+
+use ic_cdk::api::management_canister::ecdsa::{
+    EcdsaKeyId, EcdsaPublicKeyArgument, SignWithEcdsaArgument,
+};
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge::{self, rejection_message},
+    native_args::{self, ArgError, Args},
+};
+
+use super::threshold_signatures::{
+    parse_derivation_path, parse_ecdsa_signature_format, to_der, to_ethereum, EcdsaSignatureFormat,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "signWithEcdsa", argv, sign_with_ecdsa)
+    }
+}
+
+fn sign_with_ecdsa(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id: EcdsaKeyId = args.json(1, "an ECDSA key id as JSON")?;
+
+    let derivation_path = parse_derivation_path(args, 2)?;
+
+    let message_hash = args.array_buffer(3)?;
+    if message_hash.len() != 32 {
+        return Err(ArgError {
+            found: format!("{} bytes", message_hash.len()),
+            ..args.error(3, "a 32-byte message hash")
+        });
+    }
+
+    let format = parse_ecdsa_signature_format(args, 4)?;
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        sign(key_id, derivation_path, message_hash, format),
+        |context, signature| context.new_array_buffer(&signature).into(),
+        async_bridge::error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
+
+async fn sign(
+    key_id: EcdsaKeyId,
+    derivation_path: Vec<Vec<u8>>,
+    message_hash: Vec<u8>,
+    format: EcdsaSignatureFormat,
+) -> Result<Vec<u8>, String> {
+    let (response,) =
+        ic_cdk::api::management_canister::ecdsa::sign_with_ecdsa(SignWithEcdsaArgument {
+            message_hash: message_hash.clone(),
+            derivation_path: derivation_path.clone(),
+            key_id: key_id.clone(),
+        })
+        .await
+        .map_err(|rejection| rejection_message(&rejection))?;
+
+    match format {
+        EcdsaSignatureFormat::Compact => Ok(response.signature),
+        EcdsaSignatureFormat::Der => to_der(&response.signature),
+        EcdsaSignatureFormat::Ethereum => {
+            let (public_key_response,) =
+                ic_cdk::api::management_canister::ecdsa::ecdsa_public_key(EcdsaPublicKeyArgument {
+                    canister_id: None,
+                    derivation_path,
+                    key_id,
+                })
+                .await
+                .map_err(|rejection| rejection_message(&rejection))?;
+
+            to_ethereum(
+                &response.signature,
+                &message_hash,
+                &public_key_response.public_key,
+            )
+        }
+    }
+}
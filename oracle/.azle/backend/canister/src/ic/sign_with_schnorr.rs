@@ -0,0 +1,54 @@
+// signWithSchnorr(promiseId, keyIdJson, derivationPathJson, message) resolves
+// to the 64-byte signature of message: BIP340 over the message bytes for
+// bip340secp256k1 keys, or Ed25519.
+
+use candid::Principal;
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::threshold_signatures::{
+    parse_derivation_path, sign_with_schnorr_fee, SchnorrKeyId, SignWithSchnorrArgument,
+    SignWithSchnorrResponse,
+};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "signWithSchnorr", argv, sign_with_schnorr)
+    }
+}
+
+fn sign_with_schnorr(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id: SchnorrKeyId = args.json(1, "a Schnorr key id as JSON")?;
+
+    let derivation_path = parse_derivation_path(args, 2)?;
+
+    let message = args.array_buffer(3)?;
+
+    let fee = sign_with_schnorr_fee(&key_id);
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::api::call::call_with_payment128::<_, (SignWithSchnorrResponse,)>(
+            Principal::management_canister(),
+            "sign_with_schnorr",
+            (SignWithSchnorrArgument {
+                message,
+                derivation_path,
+                key_id,
+            },),
+            fee,
+        ),
+        |context, (response,)| context.new_array_buffer(&response.signature).into(),
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
@@ -1,22 +1,20 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let offset_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "stable64Write", argv, stable64_write)
+    }
+}
 
-        let buf = if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(1).unwrap() {
-            js_array_buffer.to_vec()
-        } else {
-            panic!("conversion from JsValue to JsArrayBuffer failed")
-        };
+fn stable64_write(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let offset: u64 = args.parse(0)?;
 
-        ic_cdk::api::stable::stable64_write(offset_string.parse().unwrap(), &buf);
+    let buf = args.array_buffer(1)?;
 
-        JsValue::UnDefined
-    }
+    ic_cdk::api::stable::stable64_write(offset, &buf);
+
+    Ok(JsValue::UnDefined)
 }
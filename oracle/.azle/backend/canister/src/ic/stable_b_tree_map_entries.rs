@@ -0,0 +1,311 @@
+// Shared by the StableBTreeMap natives that page, return entries or take key bounds.
+// Keys are ordered by their serialized bytes, so ranges and prefixes apply to
+// the bytes produced by the map's key serializer, not to the JS values.
+
+use std::ops::Bound;
+
+use wasmedge_quickjs::{Context, JsValue};
+
+use crate::{
+    native_args::{ArgError, Args},
+    AzleStableBTreeMapKey,
+    AzleStableBTreeMapValue,
+};
+
+pub(super) type Entry = (AzleStableBTreeMapKey, AzleStableBTreeMapValue);
+
+// "NOT_SET" means no limit, as for stableBTreeMapItems
+pub(super) fn parse_limit(args: &Args, index: usize) -> Result<usize, ArgError> {
+    if args.string(index)? == "NOT_SET" {
+        Ok(usize::MAX)
+    } else {
+        args.parse(index)
+    }
+}
+
+// An undefined key is unbounded; otherwise the key is included or excluded
+pub(super) fn parse_bound(
+    args: &Args,
+    key_index: usize,
+    inclusive_index: usize,
+) -> Result<Bound<AzleStableBTreeMapKey>, ArgError> {
+    let key = match args.optional_array_buffer(key_index)? {
+        Some(bytes) => AzleStableBTreeMapKey { bytes },
+        None => return Ok(Bound::Unbounded),
+    };
+
+    if args.bool(inclusive_index)? {
+        Ok(Bound::Included(key))
+    } else {
+        Ok(Bound::Excluded(key))
+    }
+}
+
+// The smallest key greater than every key starting with prefix
+pub(super) fn prefix_end(prefix: &[u8]) -> Bound<AzleStableBTreeMapKey> {
+    let mut end = prefix.to_vec();
+
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+
+            return Bound::Excluded(AzleStableBTreeMapKey { bytes: end });
+        }
+    }
+
+    Bound::Unbounded
+}
+
+// The keys starting with prefix that come after cursor, or before it when
+// reversed; the cursor itself is excluded
+pub(super) fn prefix_bounds(
+    prefix: &[u8],
+    cursor: Option<AzleStableBTreeMapKey>,
+    reverse: bool,
+) -> (Bound<AzleStableBTreeMapKey>, Bound<AzleStableBTreeMapKey>) {
+    let start = Bound::Included(AzleStableBTreeMapKey {
+        bytes: prefix.to_vec(),
+    });
+    let end = prefix_end(prefix);
+
+    match cursor {
+        Some(cursor) if reverse => (start, Bound::Excluded(cursor)),
+        Some(cursor) => (Bound::Excluded(cursor), end),
+        None => (start, end),
+    }
+}
+
+// std and stable BTreeMap ranges must not start after they end
+pub(super) fn is_empty_range(
+    start: &Bound<AzleStableBTreeMapKey>,
+    end: &Bound<AzleStableBTreeMapKey>,
+) -> bool {
+    match (start, end) {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (Bound::Included(start), Bound::Excluded(end))
+        | (Bound::Excluded(start), Bound::Included(end))
+        | (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+        _ => false,
+    }
+}
+
+// [key, value] as two ArrayBuffers
+pub(super) fn entry_to_js(context: &mut Context, entry: &Entry) -> JsValue {
+    let mut tuple = context.new_array();
+
+    tuple.put(0, context.new_array_buffer(&entry.0.bytes).into());
+    tuple.put(1, context.new_array_buffer(&entry.1.bytes).into());
+
+    tuple.into()
+}
+
+pub(super) fn option_entry_to_js(context: &mut Context, entry: Option<Entry>) -> JsValue {
+    match entry {
+        Some(entry) => entry_to_js(context, &entry),
+        None => JsValue::UnDefined,
+    }
+}
+
+pub(super) fn entries_to_js(context: &mut Context, entries: &[Entry]) -> JsValue {
+    let mut js_array = context.new_array();
+
+    for (index, entry) in entries.iter().enumerate() {
+        js_array.put(index, entry_to_js(context, entry));
+    }
+
+    js_array.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn key(bytes: &[u8]) -> AzleStableBTreeMapKey {
+        AzleStableBTreeMapKey {
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    // Bound<AzleStableBTreeMapKey> is not Debug
+    fn bytes(bound: Bound<AzleStableBTreeMapKey>) -> Bound<Vec<u8>> {
+        match bound {
+            Bound::Included(key) => Bound::Included(key.bytes),
+            Bound::Excluded(key) => Bound::Excluded(key.bytes),
+            Bound::Unbounded => Bound::Unbounded,
+        }
+    }
+
+    fn keys(all: &[&[u8]]) -> BTreeSet<AzleStableBTreeMapKey> {
+        all.iter().map(|bytes| key(bytes)).collect()
+    }
+
+    // One stableBTreeMapPrefix page, over a std BTreeSet with the same ordering
+    fn page(
+        keys: &BTreeSet<AzleStableBTreeMapKey>,
+        prefix: &[u8],
+        cursor: Option<&[u8]>,
+        reverse: bool,
+        limit: usize,
+    ) -> Vec<Vec<u8>> {
+        let (start, end) = prefix_bounds(prefix, cursor.map(key), reverse);
+
+        if is_empty_range(&start, &end) {
+            return vec![];
+        }
+
+        let range = keys.range((start, end)).map(|key| key.bytes.clone());
+
+        if reverse {
+            range.rev().take(limit).collect()
+        } else {
+            range.take(limit).collect()
+        }
+    }
+
+    // Pages through prefix until a page comes back short
+    fn all_pages(
+        keys: &BTreeSet<AzleStableBTreeMapKey>,
+        prefix: &[u8],
+        reverse: bool,
+    ) -> Vec<Vec<Vec<u8>>> {
+        let mut pages = vec![];
+        let mut cursor: Option<Vec<u8>> = None;
+
+        loop {
+            let next = page(keys, prefix, cursor.as_deref(), reverse, 2);
+            let done = next.len() < 2;
+
+            cursor = next.last().cloned();
+            pages.push(next);
+
+            if done {
+                return pages;
+            }
+        }
+    }
+
+    #[test]
+    fn prefix_end_increments_the_last_byte() {
+        assert_eq!(bytes(prefix_end(b"ab")), Bound::Excluded(b"ac".to_vec()));
+        assert_eq!(bytes(prefix_end(&[0])), Bound::Excluded(vec![1]));
+    }
+
+    #[test]
+    fn prefix_end_carries_over_trailing_0xff_bytes() {
+        assert_eq!(bytes(prefix_end(&[1, 0xFF])), Bound::Excluded(vec![2]));
+        assert_eq!(
+            bytes(prefix_end(&[1, 0xFE, 0xFF, 0xFF])),
+            Bound::Excluded(vec![1, 0xFF])
+        );
+    }
+
+    #[test]
+    fn prefix_end_is_unbounded_for_all_0xff_and_empty_prefixes() {
+        assert_eq!(bytes(prefix_end(&[0xFF])), Bound::Unbounded);
+        assert_eq!(bytes(prefix_end(&[0xFF, 0xFF, 0xFF])), Bound::Unbounded);
+        assert_eq!(bytes(prefix_end(&[])), Bound::Unbounded);
+    }
+
+    #[test]
+    fn prefix_end_excludes_exactly_the_keys_outside_the_prefix() {
+        let keys = keys(&[
+            &[0xFE],
+            &[0xFF],
+            &[0xFF, 0x00],
+            &[0xFF, 0xFF],
+            &[0xFF, 0xFF, 0x01],
+        ]);
+
+        assert_eq!(
+            page(&keys, &[0xFF], None, false, usize::MAX),
+            vec![
+                vec![0xFF],
+                vec![0xFF, 0x00],
+                vec![0xFF, 0xFF],
+                vec![0xFF, 0xFF, 0x01]
+            ]
+        );
+        assert_eq!(
+            page(&keys, &[0xFF, 0xFF], None, false, usize::MAX),
+            vec![vec![0xFF, 0xFF], vec![0xFF, 0xFF, 0x01]]
+        );
+        assert_eq!(
+            page(&keys, &[0xFE], None, false, usize::MAX),
+            vec![vec![0xFE]]
+        );
+    }
+
+    #[test]
+    fn is_empty_range_compares_bounds_by_inclusiveness() {
+        let included = |bytes: &[u8]| Bound::Included(key(bytes));
+        let excluded = |bytes: &[u8]| Bound::Excluded(key(bytes));
+
+        assert!(!is_empty_range(&included(b"a"), &included(b"a")));
+        assert!(is_empty_range(&included(b"b"), &included(b"a")));
+
+        assert!(is_empty_range(&included(b"a"), &excluded(b"a")));
+        assert!(is_empty_range(&excluded(b"a"), &included(b"a")));
+        assert!(is_empty_range(&excluded(b"a"), &excluded(b"a")));
+        assert!(!is_empty_range(&excluded(b"a"), &excluded(b"b")));
+
+        assert!(!is_empty_range(&Bound::Unbounded, &excluded(b"a")));
+        assert!(!is_empty_range(&excluded(b"a"), &Bound::Unbounded));
+    }
+
+    #[test]
+    fn the_cursor_is_an_exclusive_bound() {
+        let keys = keys(&[b"a", b"a1", b"a2", b"a3", b"b"]);
+
+        assert_eq!(
+            page(&keys, b"a", Some(b"a1"), false, usize::MAX),
+            vec![b"a2".to_vec(), b"a3".to_vec()]
+        );
+        assert_eq!(
+            page(&keys, b"a", Some(b"a2"), true, usize::MAX),
+            vec![b"a1".to_vec(), b"a".to_vec()]
+        );
+
+        // A cursor on the last key of the page direction leaves an empty range
+        assert_eq!(
+            page(&keys, b"a", Some(b"a"), true, 2),
+            Vec::<Vec<u8>>::new()
+        );
+    }
+
+    #[test]
+    fn pages_forward_and_in_reverse_without_gaps_or_repeats() {
+        let keys = keys(&[b"a", b"a1", b"a2", b"a3", b"a4", b"b"]);
+
+        assert_eq!(
+            all_pages(&keys, b"a", false),
+            vec![
+                vec![b"a".to_vec(), b"a1".to_vec()],
+                vec![b"a2".to_vec(), b"a3".to_vec()],
+                vec![b"a4".to_vec()],
+            ]
+        );
+        assert_eq!(
+            all_pages(&keys, b"a", true),
+            vec![
+                vec![b"a4".to_vec(), b"a3".to_vec()],
+                vec![b"a2".to_vec(), b"a1".to_vec()],
+                vec![b"a".to_vec()],
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_in_reverse_through_an_all_0xff_prefix() {
+        let keys = keys(&[&[0xFE, 0xFF], &[0xFF], &[0xFF, 0xFF], &[0xFF, 0xFF, 0xFF]]);
+
+        assert_eq!(
+            all_pages(&keys, &[0xFF], true),
+            vec![
+                vec![vec![0xFF, 0xFF, 0xFF], vec![0xFF, 0xFF]],
+                vec![vec![0xFF]],
+            ]
+        );
+    }
+}
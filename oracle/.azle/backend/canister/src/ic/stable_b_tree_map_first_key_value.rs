@@ -0,0 +1,34 @@
+// stableBTreeMapFirstKeyValue(memoryId) returns the [key, value] entry with the
+// smallest key, or undefined if the map is empty.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::native_args::{self, ArgError, Args};
+
+use super::stable_b_tree_map_entries::option_entry_to_js;
+use super::stable_b_tree_map_lookup::with_stable_b_tree_map;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(
+            context,
+            "stableBTreeMapFirstKeyValue",
+            argv,
+            stable_b_tree_map_first_key_value,
+        )
+    }
+}
+
+fn stable_b_tree_map_first_key_value(
+    context: &mut Context,
+    args: &Args,
+) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
+
+    let entry_option = with_stable_b_tree_map(args, memory_id, |stable_b_tree_map| {
+        stable_b_tree_map.first_key_value()
+    })?;
+
+    Ok(option_entry_to_js(context, entry_option))
+}
@@ -1,28 +1,33 @@
-use ic_stable_structures::{memory_manager::MemoryId, StableBTreeMap};
+use ic_stable_structures::StableBTreeMap;
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
-use crate::{MEMORY_MANAGER_REF_CELL, STABLE_B_TREE_MAPS};
+use crate::{
+    layout,
+    native_args::{self, ArgError, Args},
+    STABLE_B_TREE_MAPS,
+};
 
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let memory_id_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-        let memory_id: u8 = memory_id_string.parse().unwrap();
+        native_args::call(context, "stableBTreeMapInit", argv, stable_b_tree_map_init)
+    }
+}
 
-        STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
-            let mut stable_b_tree_maps = stable_b_tree_maps.borrow_mut();
-            stable_b_tree_maps.insert(
-                memory_id,
-                StableBTreeMap::init(
-                    MEMORY_MANAGER_REF_CELL.with(|m| m.borrow().get(MemoryId::new(memory_id))),
-                ),
-            );
-        });
+fn stable_b_tree_map_init(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
 
-        JsValue::UnDefined
+    if !layout::is_js_region(memory_id) {
+        return Err(ArgError {
+            found: format!("memory id {}", memory_id),
+            ..args.error(0, "a memory id not reserved by the runtime")
+        });
     }
+
+    STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
+        let mut stable_b_tree_maps = stable_b_tree_maps.borrow_mut();
+        stable_b_tree_maps.insert(memory_id, StableBTreeMap::init(layout::memory(memory_id)));
+    });
+
+    Ok(JsValue::UnDefined)
 }
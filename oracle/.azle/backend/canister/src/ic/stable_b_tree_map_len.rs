@@ -1,23 +1,20 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
-use crate::STABLE_B_TREE_MAPS;
+use crate::native_args::{self, ArgError, Args};
+
+use super::stable_b_tree_map_lookup::with_stable_b_tree_map;
 
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let memory_id_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-        let memory_id: u8 = memory_id_string.parse().unwrap();
+        native_args::call(context, "stableBTreeMapLen", argv, stable_b_tree_map_len)
+    }
+}
 
-        let len = STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
-            let stable_b_tree_maps = stable_b_tree_maps.borrow();
+fn stable_b_tree_map_len(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
 
-            stable_b_tree_maps[&memory_id].len()
-        });
+    let len = with_stable_b_tree_map(args, memory_id, |stable_b_tree_map| stable_b_tree_map.len())?;
 
-        context.new_string(&len.to_string()).into()
-    }
+    Ok(context.new_string(&len.to_string()).into())
 }
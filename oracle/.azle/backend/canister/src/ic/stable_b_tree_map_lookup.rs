@@ -0,0 +1,46 @@
+// Shared by the StableBTreeMap natives that act on an initialized map. Every
+// one of them takes the map's memory id as argument 0; a memory id that
+// stableBTreeMapInit never initialized is an argument error, not a trap.
+
+use crate::{
+    native_args::{ArgError, Args},
+    AzleStableBTreeMap,
+    STABLE_B_TREE_MAPS,
+};
+
+fn missing(args: &Args, memory_id: u8) -> ArgError {
+    ArgError {
+        found: format!("memory id {} has no StableBTreeMap", memory_id),
+        ..args.error(0, "the memory id of an initialized StableBTreeMap")
+    }
+}
+
+pub(super) fn with_stable_b_tree_map<R>(
+    args: &Args,
+    memory_id: u8,
+    f: impl FnOnce(&AzleStableBTreeMap) -> R,
+) -> Result<R, ArgError> {
+    STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
+        let stable_b_tree_maps = stable_b_tree_maps.borrow();
+
+        match stable_b_tree_maps.get(&memory_id) {
+            Some(stable_b_tree_map) => Ok(f(stable_b_tree_map)),
+            None => Err(missing(args, memory_id)),
+        }
+    })
+}
+
+pub(super) fn with_stable_b_tree_map_mut<R>(
+    args: &Args,
+    memory_id: u8,
+    f: impl FnOnce(&mut AzleStableBTreeMap) -> R,
+) -> Result<R, ArgError> {
+    STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
+        let mut stable_b_tree_maps = stable_b_tree_maps.borrow_mut();
+
+        match stable_b_tree_maps.get_mut(&memory_id) {
+            Some(stable_b_tree_map) => Ok(f(stable_b_tree_map)),
+            None => Err(missing(args, memory_id)),
+        }
+    })
+}
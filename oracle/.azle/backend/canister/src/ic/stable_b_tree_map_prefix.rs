@@ -0,0 +1,55 @@
+// stableBTreeMapPrefix(memoryId, prefix, cursor, reverse, limit) returns up to
+// limit [key, value] entries whose key bytes start with prefix, in key order
+// or reversed. cursor is undefined for the first page, then the last key
+// returned; iteration resumes just past it.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    native_args::{self, ArgError, Args},
+    AzleStableBTreeMapKey,
+};
+
+use super::stable_b_tree_map_entries::{
+    entries_to_js, is_empty_range, parse_limit, prefix_bounds, Entry,
+};
+use super::stable_b_tree_map_lookup::with_stable_b_tree_map;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "stableBTreeMapPrefix", argv, stable_b_tree_map_prefix)
+    }
+}
+
+fn stable_b_tree_map_prefix(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
+
+    let prefix = args.array_buffer(1)?;
+
+    let cursor = args
+        .optional_array_buffer(2)?
+        .map(|bytes| AzleStableBTreeMapKey { bytes });
+
+    let reverse = args.bool(3)?;
+
+    let limit = parse_limit(args, 4)?;
+
+    let (start, end) = prefix_bounds(&prefix, cursor, reverse);
+
+    let entries: Vec<Entry> = with_stable_b_tree_map(args, memory_id, |stable_b_tree_map| {
+        if is_empty_range(&start, &end) {
+            return vec![];
+        }
+
+        let range = stable_b_tree_map.range((start, end));
+
+        if reverse {
+            range.rev().take(limit).collect()
+        } else {
+            range.take(limit).collect()
+        }
+    })?;
+
+    Ok(entries_to_js(context, &entries))
+}
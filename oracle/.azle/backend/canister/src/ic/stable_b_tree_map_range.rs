@@ -0,0 +1,47 @@
+// stableBTreeMapRange(memoryId, startKey, startInclusive, endKey, endInclusive, reverse, limit)
+// returns up to limit [key, value] entries between the bounds, in key order or
+// reversed. An undefined key leaves that side unbounded. To page, pass the
+// last returned key as the new exclusive start (or end when reversed).
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::native_args::{self, ArgError, Args};
+
+use super::stable_b_tree_map_entries::{
+    entries_to_js, is_empty_range, parse_bound, parse_limit, Entry,
+};
+use super::stable_b_tree_map_lookup::with_stable_b_tree_map;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "stableBTreeMapRange", argv, stable_b_tree_map_range)
+    }
+}
+
+fn stable_b_tree_map_range(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
+
+    let start = parse_bound(args, 1, 2)?;
+    let end = parse_bound(args, 3, 4)?;
+
+    let reverse = args.bool(5)?;
+
+    let limit = parse_limit(args, 6)?;
+
+    let entries: Vec<Entry> = with_stable_b_tree_map(args, memory_id, |stable_b_tree_map| {
+        if is_empty_range(&start, &end) {
+            return vec![];
+        }
+
+        let range = stable_b_tree_map.range((start, end));
+
+        if reverse {
+            range.rev().take(limit).collect()
+        } else {
+            range.take(limit).collect()
+        }
+    })?;
+
+    Ok(entries_to_js(context, &entries))
+}
@@ -1,36 +1,31 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
-use crate::{AzleStableBTreeMapKey, STABLE_B_TREE_MAPS};
+use crate::{
+    native_args::{self, ArgError, Args},
+    AzleStableBTreeMapKey,
+};
+
+use super::stable_b_tree_map_lookup::with_stable_b_tree_map_mut;
 
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let memory_id_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-        let memory_id: u8 = memory_id_string.parse().unwrap();
+        native_args::call(context, "stableBTreeMapRemove", argv, stable_b_tree_map_remove)
+    }
+}
 
-        let key = if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(1).unwrap() {
-            js_array_buffer.to_vec()
-        } else {
-            panic!("conversion from JsValue to JsArrayBuffer failed")
-        };
+fn stable_b_tree_map_remove(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let memory_id: u8 = args.parse(0)?;
 
-        let value_option = STABLE_B_TREE_MAPS.with(|stable_b_tree_maps| {
-            let mut stable_b_tree_maps = stable_b_tree_maps.borrow_mut();
+    let key = args.array_buffer(1)?;
 
-            stable_b_tree_maps
-                .get_mut(&memory_id)
-                .unwrap()
-                .remove(&AzleStableBTreeMapKey { bytes: key })
-        });
+    let value_option = with_stable_b_tree_map_mut(args, memory_id, |stable_b_tree_map| {
+        stable_b_tree_map.remove(&AzleStableBTreeMapKey { bytes: key })
+    })?;
 
-        // TODO could we somehow encode the entire option here more easily
-        match value_option {
-            Some(value) => context.new_array_buffer(&value.bytes).into(),
-            None => JsValue::UnDefined,
-        }
-    }
+    // TODO could we somehow encode the entire option here more easily
+    Ok(match value_option {
+        Some(value) => context.new_array_buffer(&value.bytes).into(),
+        None => JsValue::UnDefined,
+    })
 }
@@ -1,20 +1,22 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let new_pages_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-
-        context
-            .new_string(
-                &ic_cdk::api::stable::stable_grow(new_pages_string.parse().unwrap())
-                    .unwrap()
-                    .to_string(),
-            )
-            .into()
+        native_args::call(context, "stableGrow", argv, stable_grow)
     }
 }
+
+fn stable_grow(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let new_pages: u32 = args.parse(0)?;
+
+    Ok(context
+        .new_string(
+            &ic_cdk::api::stable::stable_grow(new_pages)
+                .unwrap()
+                .to_string(),
+        )
+        .into())
+}
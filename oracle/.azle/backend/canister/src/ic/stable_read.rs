@@ -1,24 +1,21 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let offset_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string() // TODO it would be great to have a direct conversion to u64 but seems the bindings don't support it
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "stableRead", argv, stable_read)
+    }
+}
 
-        let length_string = if let JsValue::String(js_string) = argv.get(1).unwrap() {
-            js_string.to_string() // TODO it would be great to have a direct conversion to u64 but seems the bindings don't support it
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+fn stable_read(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let offset: u32 = args.parse(0)?;
+    let length: usize = args.parse(1)?;
 
-        let mut buf: Vec<u8> = vec![0; length_string.parse().unwrap()];
+    let mut buf: Vec<u8> = vec![0; length];
 
-        ic_cdk::api::stable::stable_read(offset_string.parse().unwrap(), &mut buf);
+    ic_cdk::api::stable::stable_read(offset, &mut buf);
 
-        context.new_array_buffer(&buf).into()
-    }
+    Ok(context.new_array_buffer(&buf).into())
 }
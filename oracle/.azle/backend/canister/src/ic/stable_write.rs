@@ -1,22 +1,20 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let offset_string = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string() // TODO it would be great to have a direct conversion to u64 but seems the bindings don't support it
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
+        native_args::call(context, "stableWrite", argv, stable_write)
+    }
+}
 
-        let buf = if let JsValue::ArrayBuffer(js_array_buffer) = argv.get(1).unwrap() {
-            js_array_buffer.to_vec()
-        } else {
-            panic!("conversion from JsValue to JsArrayBuffer failed")
-        };
+fn stable_write(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let offset: u32 = args.parse(0)?;
 
-        ic_cdk::api::stable::stable_write(offset_string.parse().unwrap(), &buf);
+    let buf = args.array_buffer(1)?;
 
-        JsValue::UnDefined
-    }
+    ic_cdk::api::stable::stable_write(offset, &buf);
+
+    Ok(JsValue::UnDefined)
 }
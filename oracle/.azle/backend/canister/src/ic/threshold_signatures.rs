@@ -0,0 +1,261 @@
+// Shared by the threshold ECDSA and Schnorr natives.
+//
+// Key ids are passed as the management canister's key_id in JSON, e.g.
+// {"curve":"secp256k1","name":"key_1"} or
+// {"algorithm":"bip340secp256k1","name":"key_1"} ("ed25519" for Ed25519).
+//
+// Derivation paths are a JSON array of segments. A segment is hex when
+// prefixed with 0x and UTF-8 otherwise, so ["0x00ff", "akash"] is the path
+// [[0x00, 0xff], b"akash"].
+
+use candid::{CandidType, Deserialize, Principal};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use wasmedge_quickjs::{AsObject, Context, JsValue};
+
+use crate::native_args::{ArgError, Args};
+
+// The management canister accepts at most 255 derivation path segments
+const MAX_DERIVATION_PATH_LEN: usize = 255;
+
+// sign_with_schnorr costs the same as sign_with_ecdsa: 26_153_846_153 cycles
+// with key_1 on the 34-node signing subnet and 10_000_000_000 with the test keys
+// on 13-node subnets
+const SIGN_WITH_SCHNORR_FEE: u128 = 26_153_846_153;
+const SIGN_WITH_SCHNORR_TEST_KEY_FEE: u128 = 10_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Copy)]
+pub(super) enum SchnorrAlgorithm {
+    #[serde(rename = "bip340secp256k1")]
+    Bip340Secp256k1,
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub(super) struct SchnorrKeyId {
+    pub algorithm: SchnorrAlgorithm,
+    pub name: String,
+}
+
+#[derive(CandidType)]
+pub(super) struct SchnorrPublicKeyArgument {
+    pub canister_id: Option<Principal>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+pub(super) struct SchnorrPublicKeyResponse {
+    pub public_key: Vec<u8>,
+    pub chain_code: Vec<u8>,
+}
+
+#[derive(CandidType)]
+pub(super) struct SignWithSchnorrArgument {
+    pub message: Vec<u8>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: SchnorrKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+pub(super) struct SignWithSchnorrResponse {
+    pub signature: Vec<u8>,
+}
+
+// What is not used is refunded, so unknown key names are sent the key_1 price
+pub(super) fn sign_with_schnorr_fee(key_id: &SchnorrKeyId) -> u128 {
+    match key_id.name.as_str() {
+        "test_key_1" | "dfx_test_key" => SIGN_WITH_SCHNORR_TEST_KEY_FEE,
+        _ => SIGN_WITH_SCHNORR_FEE,
+    }
+}
+
+// The encodings signWithEcdsa can return
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum EcdsaSignatureFormat {
+    // r || s, 64 bytes, as the management canister returns it
+    Compact,
+    // ASN.1 DER, as X.509, Bitcoin and OpenSSL expect
+    Der,
+    // r || s || v with low s and v = 27 + recovery id, 65 bytes
+    Ethereum,
+}
+
+pub(super) fn parse_derivation_path(args: &Args, index: usize) -> Result<Vec<Vec<u8>>, ArgError> {
+    let expected = "a derivation path as a JSON array of strings";
+    let segments: Vec<String> = args.json(index, expected)?;
+
+    if segments.len() > MAX_DERIVATION_PATH_LEN {
+        return Err(ArgError {
+            found: format!("{} segments", segments.len()),
+            ..args.error(index, "a derivation path of at most 255 segments")
+        });
+    }
+
+    segments
+        .iter()
+        .map(|segment| match segment.strip_prefix("0x") {
+            Some(hex_segment) => hex::decode(hex_segment).map_err(|_| ArgError {
+                found: format!("{:?}", segment),
+                ..args.error(index, "a derivation path whose 0x segments are hex")
+            }),
+            None => Ok(segment.as_bytes().to_vec()),
+        })
+        .collect()
+}
+
+pub(super) fn parse_ecdsa_signature_format(
+    args: &Args,
+    index: usize,
+) -> Result<EcdsaSignatureFormat, ArgError> {
+    match args.string(index)?.as_str() {
+        "compact" => Ok(EcdsaSignatureFormat::Compact),
+        "der" => Ok(EcdsaSignatureFormat::Der),
+        "ethereum" => Ok(EcdsaSignatureFormat::Ethereum),
+        format => Err(ArgError {
+            found: format!("{:?}", format),
+            ..args.error(index, "\"compact\", \"der\" or \"ethereum\"")
+        }),
+    }
+}
+
+pub(super) fn to_der(compact: &[u8]) -> Result<Vec<u8>, String> {
+    let signature = Signature::from_slice(compact).map_err(|e| e.to_string())?;
+
+    Ok(signature.to_der().as_bytes().to_vec())
+}
+
+// Finds the recovery id by recovering the public key both ways. s is
+// normalized to the lower half of the curve order first, as Ethereum requires.
+pub(super) fn to_ethereum(
+    compact: &[u8],
+    message_hash: &[u8],
+    public_key: &[u8],
+) -> Result<Vec<u8>, String> {
+    let signature = Signature::from_slice(compact).map_err(|e| e.to_string())?;
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let verifying_key = VerifyingKey::from_sec1_bytes(public_key).map_err(|e| e.to_string())?;
+
+    for recovery_byte in 0..2 {
+        let recovery_id = RecoveryId::from_byte(recovery_byte).unwrap();
+
+        let recovered = VerifyingKey::recover_from_prehash(message_hash, &signature, recovery_id);
+
+        if recovered.ok() == Some(verifying_key) {
+            let mut ethereum_signature = signature.to_bytes().to_vec();
+            ethereum_signature.push(27 + recovery_byte);
+
+            return Ok(ethereum_signature);
+        }
+    }
+
+    Err("the signature does not recover to the public key".to_string())
+}
+
+pub(super) fn public_key_to_js(
+    context: &mut Context,
+    public_key: &[u8],
+    chain_code: &[u8],
+) -> JsValue {
+    let mut public_key_js_object = context.new_object();
+
+    public_key_js_object.set("publicKey", context.new_array_buffer(public_key).into());
+    public_key_js_object.set("chainCode", context.new_array_buffer(chain_code).into());
+
+    public_key_js_object.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::{signature::hazmat::PrehashSigner, SigningKey};
+    use sha2::{Digest, Sha256};
+
+    fn signing_key(byte: u8) -> SigningKey {
+        SigningKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn sign(key: &SigningKey, message_hash: &[u8]) -> Signature {
+        key.sign_prehash(message_hash).unwrap()
+    }
+
+    // The same signature with s in the upper half of the curve order
+    fn with_high_s(signature: &Signature) -> Signature {
+        let (r, s) = signature.split_scalars();
+
+        Signature::from_scalars(r, -s).unwrap()
+    }
+
+    #[test]
+    fn der_round_trips() {
+        let message_hash = Sha256::digest(b"message");
+        let signature = sign(&signing_key(1), &message_hash);
+
+        let der = to_der(&signature.to_bytes()).unwrap();
+
+        assert_eq!(der[0], 0x30);
+        assert_eq!(Signature::from_der(&der).unwrap(), signature);
+    }
+
+    #[test]
+    fn der_rejects_invalid_signatures() {
+        assert!(to_der(&[0; 64]).is_err());
+        assert!(to_der(&[1; 63]).is_err());
+    }
+
+    #[test]
+    fn ethereum_signatures_recover_the_public_key() {
+        let key = signing_key(2);
+        let public_key = key.verifying_key().to_sec1_bytes();
+
+        for message in [&b"first"[..], &b"second"[..], &b"third"[..]] {
+            let message_hash = Sha256::digest(message);
+            let signature = sign(&key, &message_hash);
+
+            for compact in [with_high_s(&signature), signature] {
+                let ethereum =
+                    to_ethereum(&compact.to_bytes(), &message_hash, &public_key).unwrap();
+
+                assert_eq!(ethereum.len(), 65);
+                assert!(ethereum[64] == 27 || ethereum[64] == 28);
+
+                let recovered_signature = Signature::from_slice(&ethereum[..64]).unwrap();
+                assert!(recovered_signature.normalize_s().is_none(), "s is not low");
+
+                let recovery_id = RecoveryId::from_byte(ethereum[64] - 27).unwrap();
+                let recovered = VerifyingKey::recover_from_prehash(
+                    &message_hash,
+                    &recovered_signature,
+                    recovery_id,
+                )
+                .unwrap();
+                assert_eq!(&recovered, key.verifying_key());
+            }
+        }
+    }
+
+    #[test]
+    fn ethereum_rejects_another_public_key() {
+        let message_hash = Sha256::digest(b"message");
+        let signature = sign(&signing_key(3), &message_hash);
+        let other_public_key = signing_key(4).verifying_key().to_sec1_bytes();
+
+        assert!(to_ethereum(&signature.to_bytes(), &message_hash, &other_public_key).is_err());
+    }
+
+    #[test]
+    fn schnorr_fee_follows_the_key_name() {
+        let key_id = |name: &str| SchnorrKeyId {
+            algorithm: SchnorrAlgorithm::Ed25519,
+            name: name.to_string(),
+        };
+
+        assert_eq!(sign_with_schnorr_fee(&key_id("key_1")), 26_153_846_153);
+        assert_eq!(sign_with_schnorr_fee(&key_id("test_key_1")), 10_000_000_000);
+        assert_eq!(
+            sign_with_schnorr_fee(&key_id("dfx_test_key")),
+            10_000_000_000
+        );
+        assert_eq!(sign_with_schnorr_fee(&key_id("key_2")), 26_153_846_153);
+    }
+}
@@ -1,14 +1,16 @@
 use wasmedge_quickjs::{Context, JsFn, JsValue};
 
+use crate::native_args::{self, ArgError, Args};
+
 pub struct NativeFunction;
 impl JsFn for NativeFunction {
     fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
-        let message = if let JsValue::String(js_string) = argv.get(0).unwrap() {
-            js_string.to_string()
-        } else {
-            panic!("conversion from JsValue to JsString failed")
-        };
-
-        ic_cdk::api::trap(&message);
+        native_args::call(context, "trap", argv, trap)
     }
 }
+
+fn trap(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let message = args.string(0)?;
+
+    ic_cdk::api::trap(&message);
+}
@@ -0,0 +1,150 @@
+// Shared by the VetKD natives. ic-cdk 0.12 predates VetKD, so the management
+// canister's types are declared here.
+//
+// Key ids are passed as the management canister's key_id in JSON, e.g.
+// {"curve":"bls12_381_g2","name":"key_1"}.
+
+use candid::{CandidType, Deserialize, Principal};
+
+use crate::native_args::{ArgError, Args};
+
+// vetkd_derive_key costs 26_153_846_153 cycles with key_1 and less with the
+// test keys; what is not used is refunded
+pub(super) const VETKD_DERIVE_KEY_FEE: u128 = 26_153_846_153;
+
+#[allow(non_camel_case_types)]
+#[derive(CandidType, Deserialize, Clone, Copy)]
+pub(super) enum VetKDCurve {
+    #[serde(rename = "bls12_381_g2")]
+    Bls12_381_G2,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub(super) struct VetKDKeyId {
+    pub curve: VetKDCurve,
+    pub name: String,
+}
+
+#[derive(CandidType)]
+pub(super) struct VetKDPublicKeyArgs {
+    pub canister_id: Option<Principal>,
+    pub context: Vec<u8>,
+    pub key_id: VetKDKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+pub(super) struct VetKDPublicKeyResult {
+    pub public_key: Vec<u8>,
+}
+
+#[derive(CandidType)]
+pub(super) struct VetKDDeriveKeyArgs {
+    pub input: Vec<u8>,
+    pub context: Vec<u8>,
+    pub transport_public_key: Vec<u8>,
+    pub key_id: VetKDKeyId,
+}
+
+#[derive(CandidType, Deserialize)]
+pub(super) struct VetKDDeriveKeyResult {
+    pub encrypted_key: Vec<u8>,
+}
+
+pub(super) fn parse_key_id(args: &Args, index: usize) -> Result<VetKDKeyId, ArgError> {
+    args.json(index, "a VetKD key id as JSON")
+}
+
+pub(super) fn parse_derived_public_key(
+    args: &Args,
+    index: usize,
+) -> Result<ic_vetkeys::DerivedPublicKey, ArgError> {
+    let bytes = args.array_buffer(index)?;
+
+    derived_public_key(&bytes).map_err(|found| ArgError {
+        found,
+        ..args.error(index, "a compressed BLS12-381 G2 public key")
+    })
+}
+
+// The key, or what to report as found instead
+fn derived_public_key(bytes: &[u8]) -> Result<ic_vetkeys::DerivedPublicKey, String> {
+    ic_vetkeys::DerivedPublicKey::deserialize(bytes)
+        .map_err(|_| format!("{} bytes that are not one", bytes.len()))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use ic_bls12_381::{
+        hash_to_curve::{ExpandMsgXmd, HashToCurve},
+        G1Affine, G1Projective, G2Affine, Scalar,
+    };
+    use ic_vetkeys::{DerivedPublicKey, VetKey};
+
+    // Stand-in for a VetKD key and context, derived locally from a fixed secret
+    pub(in crate::ic) struct SimulatedKey {
+        secret: Scalar,
+        public: G2Affine,
+    }
+
+    impl SimulatedKey {
+        pub(in crate::ic) fn new(seed: u8) -> Self {
+            let secret = Scalar::from_bytes_wide(&[seed; 64]);
+            let public = G2Affine::from(G2Affine::generator() * secret);
+
+            SimulatedKey { secret, public }
+        }
+
+        // What vetkdPublicKey resolves to
+        pub(in crate::ic) fn public_key_bytes(&self) -> Vec<u8> {
+            self.public.to_compressed().to_vec()
+        }
+
+        pub(in crate::ic) fn derived_public_key(&self) -> DerivedPublicKey {
+            DerivedPublicKey::deserialize(&self.public_key_bytes()).unwrap()
+        }
+
+        // What vetkdDeriveKey yields for input once transport-decrypted:
+        // secret * H(public key || input) with the augmented BLS hash
+        pub(in crate::ic) fn vetkey(&self, input: &[u8]) -> VetKey {
+            const DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_";
+
+            let mut message = self.public_key_bytes();
+            message.extend_from_slice(input);
+
+            let hash = <G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(
+                &message, DST,
+            );
+
+            VetKey::deserialize(&G1Affine::from(hash * self.secret).to_compressed()).unwrap()
+        }
+    }
+
+    #[test]
+    fn parses_a_derived_public_key() {
+        let key = SimulatedKey::new(1);
+
+        let parsed = derived_public_key(&key.public_key_bytes()).unwrap();
+
+        assert_eq!(parsed.serialize(), key.derived_public_key().serialize());
+    }
+
+    #[test]
+    fn rejects_a_malformed_public_key() {
+        let mut bytes = SimulatedKey::new(1).public_key_bytes();
+
+        assert_eq!(
+            derived_public_key(&bytes[..95]).err(),
+            Some("95 bytes that are not one".to_string())
+        );
+        assert_eq!(
+            derived_public_key(&[0; 96]).err(),
+            Some("96 bytes that are not one".to_string())
+        );
+        assert!(derived_public_key(&[]).is_err());
+
+        // The x coordinate of a G2 point, with the compression flag cleared
+        bytes[0] &= 0x7F;
+        assert!(derived_public_key(&bytes).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+// vetkdDeriveKey(promiseId, keyIdJson, context, input, transportPublicKey)
+// resolves to the vetKey for (context, input) of this canister, encrypted to
+// transportPublicKey. Only the holder of the transport secret key, usually a
+// client outside the canister, can decrypt it.
+
+use candid::Principal;
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::vetkd::{parse_key_id, VetKDDeriveKeyArgs, VetKDDeriveKeyResult, VETKD_DERIVE_KEY_FEE};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "vetkdDeriveKey", argv, vetkd_derive_key)
+    }
+}
+
+fn vetkd_derive_key(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id = parse_key_id(args, 1)?;
+
+    let vetkd_context = args.array_buffer(2)?;
+
+    let input = args.array_buffer(3)?;
+
+    let transport_public_key = args.array_buffer(4)?;
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::api::call::call_with_payment128::<_, (VetKDDeriveKeyResult,)>(
+            Principal::management_canister(),
+            "vetkd_derive_key",
+            (VetKDDeriveKeyArgs {
+                input,
+                context: vetkd_context,
+                transport_public_key,
+                key_id,
+            },),
+            VETKD_DERIVE_KEY_FEE,
+        ),
+        |context, (result,)| context.new_array_buffer(&result.encrypted_key).into(),
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
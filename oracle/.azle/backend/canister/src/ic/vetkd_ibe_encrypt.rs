@@ -0,0 +1,123 @@
+// vetkdIbeEncrypt(promiseId, derivedPublicKey, identity, plaintext) resolves
+// to plaintext sealed with ic_vetkeys IBE to identity under derivedPublicKey
+// (from vetkdPublicKey). Whoever obtains the vetKey for identity from that
+// key and context can open it with IbeCiphertext::decrypt. The encryption
+// seed comes from raw_rand, hence the promise.
+
+use ic_vetkeys::{DerivedPublicKey, IbeCiphertext, IbeIdentity, IbeSeed};
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge::{self, rejection_message},
+    native_args::{self, ArgError, Args},
+};
+
+use super::vetkd::parse_derived_public_key;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "vetkdIbeEncrypt", argv, vetkd_ibe_encrypt)
+    }
+}
+
+fn vetkd_ibe_encrypt(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let derived_public_key = parse_derived_public_key(args, 1)?;
+
+    let identity = args.array_buffer(2)?;
+
+    let plaintext = args.array_buffer(3)?;
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        encrypt(derived_public_key, identity, plaintext),
+        |context, ciphertext| context.new_array_buffer(&ciphertext).into(),
+        async_bridge::error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
+
+async fn encrypt(
+    derived_public_key: DerivedPublicKey,
+    identity: Vec<u8>,
+    plaintext: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let (random_bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+        .await
+        .map_err(|rejection| rejection_message(&rejection))?;
+
+    seal(&derived_public_key, &identity, &plaintext, &random_bytes)
+}
+
+fn seal(
+    derived_public_key: &DerivedPublicKey,
+    identity: &[u8],
+    plaintext: &[u8],
+    random_bytes: &[u8],
+) -> Result<Vec<u8>, String> {
+    let seed = IbeSeed::from_bytes(random_bytes).map_err(|e| e.to_string())?;
+
+    Ok(IbeCiphertext::encrypt(
+        derived_public_key,
+        &IbeIdentity::from_bytes(identity),
+        plaintext,
+        &seed,
+    )
+    .serialize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::vetkd::tests::SimulatedKey;
+    use super::*;
+
+    fn sealed(key: &SimulatedKey, identity: &[u8], plaintext: &[u8]) -> IbeCiphertext {
+        let ciphertext = seal(&key.derived_public_key(), identity, plaintext, &[7; 32]).unwrap();
+
+        IbeCiphertext::deserialize(&ciphertext).unwrap()
+    }
+
+    #[test]
+    fn the_vetkey_for_the_identity_decrypts() {
+        let key = SimulatedKey::new(1);
+
+        let ciphertext = sealed(&key, b"node|tee-7", b"akash mnemonic");
+
+        assert_eq!(
+            ciphertext.decrypt(&key.vetkey(b"node|tee-7")).unwrap(),
+            b"akash mnemonic".to_vec()
+        );
+    }
+
+    #[test]
+    fn the_vetkey_for_another_identity_does_not_decrypt() {
+        let key = SimulatedKey::new(1);
+
+        let ciphertext = sealed(&key, b"node|tee-7", b"secret");
+
+        assert!(ciphertext.decrypt(&key.vetkey(b"node|tee-8")).is_err());
+    }
+
+    #[test]
+    fn the_vetkey_of_another_key_does_not_decrypt() {
+        let ciphertext = sealed(&SimulatedKey::new(1), b"node|tee-7", b"secret");
+
+        let other = SimulatedKey::new(2).vetkey(b"node|tee-7");
+
+        assert!(ciphertext.decrypt(&other).is_err());
+    }
+
+    #[test]
+    fn the_seed_randomizes_the_ciphertext() {
+        let key = SimulatedKey::new(1).derived_public_key();
+
+        let first = seal(&key, b"node|tee-7", b"secret", &[7; 32]).unwrap();
+        let second = seal(&key, b"node|tee-7", b"secret", &[8; 32]).unwrap();
+
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,51 @@
+// vetkdPublicKey(promiseId, keyIdJson, context, canisterId) resolves to the
+// VetKD public key (a compressed BLS12-381 G2 point) for context, of this
+// canister or of canisterId when it is given. Sealing to the identities of
+// another canister, such as the vetkeys canister's TEE nodes, needs its key.
+
+use candid::Principal;
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::{
+    async_bridge,
+    native_args::{self, ArgError, Args},
+};
+
+use super::vetkd::{parse_key_id, VetKDPublicKeyArgs, VetKDPublicKeyResult};
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "vetkdPublicKey", argv, vetkd_public_key)
+    }
+}
+
+fn vetkd_public_key(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let promise_id = args.string(0)?;
+
+    let key_id = parse_key_id(args, 1)?;
+
+    let vetkd_context = args.array_buffer(2)?;
+
+    let canister_id = args
+        .optional_array_buffer(3)?
+        .map(|canister_id_bytes| Principal::from_slice(&canister_id_bytes));
+
+    async_bridge::spawn(
+        context,
+        promise_id,
+        ic_cdk::call::<_, (VetKDPublicKeyResult,)>(
+            Principal::management_canister(),
+            "vetkd_public_key",
+            (VetKDPublicKeyArgs {
+                canister_id,
+                context: vetkd_context,
+                key_id,
+            },),
+        ),
+        |context, (result,)| context.new_array_buffer(&result.public_key).into(),
+        async_bridge::rejection_error,
+    );
+
+    Ok(JsValue::UnDefined)
+}
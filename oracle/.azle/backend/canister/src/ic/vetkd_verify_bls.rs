@@ -0,0 +1,73 @@
+// vetkdVerifyBls(derivedPublicKey, message, signature) returns whether
+// signature is a valid BLS signature of message under derivedPublicKey, i.e.
+// whether it is the vetKey for input message of that key and context.
+
+use wasmedge_quickjs::{Context, JsFn, JsValue};
+
+use crate::native_args::{self, ArgError, Args};
+
+use super::vetkd::parse_derived_public_key;
+
+pub struct NativeFunction;
+impl JsFn for NativeFunction {
+    fn call(context: &mut Context, this_val: JsValue, argv: &[JsValue]) -> JsValue {
+        native_args::call(context, "vetkdVerifyBls", argv, vetkd_verify_bls)
+    }
+}
+
+fn vetkd_verify_bls(context: &mut Context, args: &Args) -> Result<JsValue, ArgError> {
+    let derived_public_key = parse_derived_public_key(args, 0)?;
+
+    let message = args.array_buffer(1)?;
+
+    let signature = args.array_buffer(2)?;
+
+    Ok(ic_vetkeys::verify_bls_signature(&derived_public_key, &message, &signature).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::vetkd::tests::SimulatedKey;
+
+    fn verify(key: &SimulatedKey, message: &[u8], signature: &[u8]) -> bool {
+        ic_vetkeys::verify_bls_signature(&key.derived_public_key(), message, signature)
+    }
+
+    // The vetKey for input message, as vetkdDeriveKey returns it
+    fn signature(key: &SimulatedKey, message: &[u8]) -> Vec<u8> {
+        key.vetkey(message).signature_bytes().to_vec()
+    }
+
+    #[test]
+    fn a_vetkey_is_a_signature_of_its_input() {
+        let key = SimulatedKey::new(1);
+        let signature = signature(&key, b"deploy tee-7");
+
+        assert!(verify(&key, b"deploy tee-7", &signature));
+    }
+
+    #[test]
+    fn rejects_a_signature_of_another_message() {
+        let key = SimulatedKey::new(1);
+        let signature = signature(&key, b"deploy tee-7");
+
+        assert!(!verify(&key, b"deploy tee-8", &signature));
+    }
+
+    #[test]
+    fn rejects_a_signature_under_another_key() {
+        let signature = signature(&SimulatedKey::new(2), b"deploy tee-7");
+
+        assert!(!verify(&SimulatedKey::new(1), b"deploy tee-7", &signature));
+    }
+
+    #[test]
+    fn rejects_a_malformed_signature() {
+        let key = SimulatedKey::new(1);
+        let signature = signature(&key, b"deploy tee-7");
+
+        assert!(!verify(&key, b"deploy tee-7", &signature[..47]));
+        assert!(!verify(&key, b"deploy tee-7", &[0; 48]));
+        assert!(!verify(&key, b"deploy tee-7", &[]));
+    }
+}
@@ -0,0 +1,119 @@
+// wasmedge-quickjs does not expose the thrown value of a JsException, so JS
+// callbacks are invoked through a small JS trampoline that catches the
+// exception and hands its name, message and stack back to Rust as plain
+// strings. Everything that calls into JS from a canister entry point, a timer
+// or a call callback should go through `call` and surface the resulting
+// JsError with `trap` (or as a guard's Err).
+
+use std::fmt;
+
+use wasmedge_quickjs::{AsObject, Context, JsFunction, JsObject, JsValue};
+
+const CALL_CATCHING: &str = "_azleCallCatching";
+
+const CALL_CATCHING_JS: &str = r#"
+globalThis._azleCallCatching = function (callback, ...args) {
+    try {
+        return { ok: true, value: callback(...args) };
+    } catch (error) {
+        const isError = error instanceof Error;
+
+        return {
+            ok: false,
+            name: isError ? String(error.name) : typeof error,
+            message: isError ? String(error.message) : String(error),
+            stack: isError && error.stack ? String(error.stack) : undefined
+        };
+    }
+};
+"#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsError {
+    pub name: String,
+    pub message: String,
+    pub stack: Option<String>,
+}
+
+impl JsError {
+    // The trampoline itself failed (e.g. out of memory or stack), so the
+    // exception could not be caught; QuickJS still has it and can dump it.
+    fn uncaught() -> Self {
+        JsError {
+            name: "InternalError".to_string(),
+            message: "JavaScript exception without error information (see canister logs)"
+                .to_string(),
+            stack: None,
+        }
+    }
+}
+
+impl fmt::Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Uncaught {}: {}", self.name, self.message)?;
+
+        if let Some(stack) = &self.stack {
+            write!(f, "\n{}", stack.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn string_field(object: &JsObject, key: &str) -> Option<String> {
+    match object.get(key) {
+        JsValue::String(js_string) => Some(js_string.to_string()),
+        _ => None,
+    }
+}
+
+fn call_catching(context: &mut Context) -> JsFunction {
+    if let Some(function) = context.get_global().get(CALL_CATCHING).to_function() {
+        return function;
+    }
+
+    context.eval_global_str(CALL_CATCHING_JS.to_string());
+
+    context
+        .get_global()
+        .get(CALL_CATCHING)
+        .to_function()
+        .expect("_azleCallCatching could not be installed")
+}
+
+/// Calls `function` with `args`, returning what it threw as a JsError.
+pub fn call(
+    context: &mut Context,
+    function: &JsFunction,
+    args: &[JsValue],
+) -> Result<JsValue, JsError> {
+    let mut trampoline_args = Vec::with_capacity(args.len() + 1);
+    trampoline_args.push(JsValue::Function(function.clone()));
+    trampoline_args.extend_from_slice(args);
+
+    let outcome = call_catching(context).call(&trampoline_args);
+
+    let outcome = match &outcome {
+        JsValue::Exception(js_exception) => {
+            js_exception.dump_error();
+            return Err(JsError::uncaught());
+        }
+        _ => outcome.to_obj().ok_or_else(JsError::uncaught)?,
+    };
+
+    if let JsValue::Bool(true) = outcome.get("ok") {
+        return Ok(outcome.get("value"));
+    }
+
+    Err(JsError {
+        name: string_field(&outcome, "name").unwrap_or_else(|| "Error".to_string()),
+        message: string_field(&outcome, "message").unwrap_or_default(),
+        stack: string_field(&outcome, "stack"),
+    })
+}
+
+/// Logs `error` and traps with it, so the caller sees the JS error as the reject message.
+pub fn trap(error: &JsError) -> ! {
+    ic_cdk::println!("{}", error);
+    ic_cdk::trap(&error.to_string())
+}
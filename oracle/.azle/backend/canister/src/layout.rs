@@ -0,0 +1,88 @@
+// Stable memory layout of the Azle runtime, checked with the shared
+// dooor_memory_layout crate like the other Dooor canisters. Memory ids 0 to
+// 251 belong to the StableBTreeMaps that JS creates with stableBTreeMapInit;
+// the runtime keeps 252 (scheduled jobs) and 253 (persistent timers), and the
+// crate keeps 254 (the layout manifest) for itself.
+//
+// init and post_upgrade validate the layout against the manifest in stable
+// memory before anything else touches it, so an upgrade that moves a runtime
+// region onto data of another one traps instead. Controllers can see the size
+// of every region with the _azle_memory_stats query.
+
+use dooor_memory_layout::{Layout, Region, RegionStats};
+use ic_stable_structures::memory_manager::MemoryId;
+
+use crate::{Memory, MEMORY_MANAGER_REF_CELL};
+
+/// Stable memory id of the scheduled jobs.
+pub const JOBS: u8 = 252;
+
+/// Stable memory id of the persistent timers.
+pub const TIMERS: u8 = 253;
+
+/// Owner of the regions that JS StableBTreeMaps use.
+const JS: &str = "js";
+
+// One region per memory id that JS may use, then the runtime's own regions
+macro_rules! regions {
+    ($($id:literal)*) => {
+        &[
+            $(Region::new($id, concat!("js.stable_b_tree_map.", $id), JS),)*
+            Region::new(JOBS, "azle.scheduler.jobs", "scheduler"),
+            Region::new(TIMERS, "azle.timers.persistent", "timers"),
+        ]
+    };
+}
+
+pub const LAYOUT: Layout = Layout {
+    regions: regions!(
+        0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+        32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53 54 55 56 57 58 59 60
+        61 62 63 64 65 66 67 68 69 70 71 72 73 74 75 76 77 78 79 80 81 82 83 84 85 86 87 88 89
+        90 91 92 93 94 95 96 97 98 99 100 101 102 103 104 105 106 107 108 109 110 111 112 113
+        114 115 116 117 118 119 120 121 122 123 124 125 126 127 128 129 130 131 132 133 134 135
+        136 137 138 139 140 141 142 143 144 145 146 147 148 149 150 151 152 153 154 155 156 157
+        158 159 160 161 162 163 164 165 166 167 168 169 170 171 172 173 174 175 176 177 178 179
+        180 181 182 183 184 185 186 187 188 189 190 191 192 193 194 195 196 197 198 199 200 201
+        202 203 204 205 206 207 208 209 210 211 212 213 214 215 216 217 218 219 220 221 222 223
+        224 225 226 227 228 229 230 231 232 233 234 235 236 237 238 239 240 241 242 243 244 245
+        246 247 248 249 250 251
+    ),
+    retired: &[],
+};
+
+/// Checks the layout against the manifest in stable memory, trapping on a
+/// mismatch. Runs first in init and post_upgrade.
+pub fn validate() {
+    if let Err(error) = MEMORY_MANAGER_REF_CELL.with(|m| LAYOUT.validate(&m.borrow())) {
+        ic_cdk::trap(&format!("stable memory layout: {}", error));
+    }
+}
+
+/// Whether JS may put a StableBTreeMap in memory `id`: declared in the layout
+/// and not kept by the runtime.
+pub fn is_js_region(id: u8) -> bool {
+    LAYOUT.region(id).map_or(false, |region| region.owner == JS)
+}
+
+/// The memory `id`, which the layout must declare.
+pub fn memory(id: u8) -> Memory {
+    if LAYOUT.region(id).is_none() {
+        ic_cdk::trap(&format!("memory id {} is not declared in the layout", id));
+    }
+
+    MEMORY_MANAGER_REF_CELL.with(|m| m.borrow().get(MemoryId::new(id)))
+}
+
+fn caller_is_controller() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        Ok(())
+    } else {
+        Err("only controllers can read memory stats".to_string())
+    }
+}
+
+#[ic_cdk_macros::query(name = "_azle_memory_stats", guard = "caller_is_controller")]
+fn memory_stats() -> Vec<RegionStats> {
+    MEMORY_MANAGER_REF_CELL.with(|m| LAYOUT.stats(&m.borrow()))
+}
@@ -10,7 +10,13 @@ use ic_stable_structures::{
 use include_dir::{include_dir, Dir};
 use wasmedge_quickjs::AsObject;
 
+mod async_bridge;
 mod ic;
+mod js_error;
+mod layout;
+mod native_args;
+mod scheduler;
+mod timers;
 mod web_assembly;
 
 #[cfg(all(target_arch = "wasm32", target_os = "wasi"))]
@@ -112,18 +118,9 @@ fn execute_js(function_name: &str, pass_arg_data: bool) {
 
             let method_callback_function = method_callback.to_function().unwrap();
 
-            let result = method_callback_function.call(&[candid_args_js_value]);
-
-            // TODO error handling is mostly done in JS right now
-            // TODO we would really like wasmedge-quickjs to add
-            // TODO good error info to JsException and move error handling
-            // TODO out of our own code
-            match &result {
-                wasmedge_quickjs::JsValue::Exception(js_exception) => {
-                    js_exception.dump_error();
-                    panic!("TODO needs error info");
-                }
-                _ => run_event_loop(context),
+            match js_error::call(context, &method_callback_function, &[candid_args_js_value]) {
+                Ok(_) => run_event_loop(context),
+                Err(error) => js_error::trap(&error),
             };
         });
     });
@@ -156,20 +153,14 @@ pub fn get_candid_pointer() -> *mut std::os::raw::c_char {
 
             let candid_info_function = global.get("candidInfoFunction").to_function().unwrap();
 
-            let candid_info = candid_info_function.call(&[]);
-
-            // TODO error handling is mostly done in JS right now
-            // TODO we would really like wasmedge-quickjs to add
-            // TODO good error info to JsException and move error handling
-            // TODO out of our own code
-            match &candid_info {
-                wasmedge_quickjs::JsValue::Exception(js_exception) => {
-                    js_exception.dump_error();
-                    panic!("TODO needs error info");
-                }
-                _ => run_event_loop(context),
+            // This runs at build time outside of a replica, so there is nothing to trap
+            let candid_info = match js_error::call(context, &candid_info_function, &[]) {
+                Ok(candid_info) => candid_info,
+                Err(error) => panic!("{}", error),
             };
 
+            run_event_loop(context);
+
             let candid_info_string = candid_info.to_string().unwrap().to_string();
 
             let c_string = std::ffi::CString::new(candid_info_string).unwrap();
@@ -0,0 +1,168 @@
+// Argument extraction for native functions. A native that receives an
+// argument of the wrong type throws a JS TypeError naming the native, the
+// argument index and the expected type, instead of panicking and trapping the
+// whole message with no context:
+//
+//     TypeError: stableBTreeMapGet: argument 1 must be an ArrayBuffer, got string
+//
+// Natives implement their body as a function of `Args` that returns
+// `Result<JsValue, ArgError>` and hand it to `call`.
+
+use std::{fmt, str::FromStr};
+
+use serde::de::DeserializeOwned;
+use wasmedge_quickjs::{Context, JsValue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgError {
+    pub native: String,
+    pub index: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: argument {} must be {}, got {}",
+            self.native, self.index, self.expected, self.found
+        )
+    }
+}
+
+impl ArgError {
+    /// Throws the error as a TypeError in `context`.
+    pub fn throw(&self, context: &mut Context) -> JsValue {
+        JsValue::Exception(context.throw_type_error(&self.to_string()))
+    }
+}
+
+pub struct Args<'a> {
+    native: &'a str,
+    argv: &'a [JsValue],
+}
+
+impl<'a> Args<'a> {
+    pub fn new(native: &'a str, argv: &'a [JsValue]) -> Self {
+        Args { native, argv }
+    }
+
+    /// The argument at `index`, or None if it was not passed.
+    pub fn get(&self, index: usize) -> Option<&'a JsValue> {
+        self.argv.get(index)
+    }
+
+    pub fn error(&self, index: usize, expected: &str) -> ArgError {
+        ArgError {
+            native: self.native.to_string(),
+            index,
+            expected: expected.to_string(),
+            found: type_name(self.get(index)).to_string(),
+        }
+    }
+
+    pub fn string(&self, index: usize) -> Result<String, ArgError> {
+        match self.get(index) {
+            Some(JsValue::String(js_string)) => Ok(js_string.to_string()),
+            _ => Err(self.error(index, "a string")),
+        }
+    }
+
+    pub fn array_buffer(&self, index: usize) -> Result<Vec<u8>, ArgError> {
+        match self.get(index) {
+            Some(JsValue::ArrayBuffer(js_array_buffer)) => Ok(js_array_buffer.to_vec()),
+            _ => Err(self.error(index, "an ArrayBuffer")),
+        }
+    }
+
+    /// An ArrayBuffer, or None for undefined.
+    pub fn optional_array_buffer(&self, index: usize) -> Result<Option<Vec<u8>>, ArgError> {
+        match self.get(index) {
+            None | Some(JsValue::UnDefined) => Ok(None),
+            Some(JsValue::ArrayBuffer(js_array_buffer)) => Ok(Some(js_array_buffer.to_vec())),
+            _ => Err(self.error(index, "an ArrayBuffer or undefined")),
+        }
+    }
+
+    pub fn bool(&self, index: usize) -> Result<bool, ArgError> {
+        match self.get(index) {
+            Some(JsValue::Bool(bool)) => Ok(*bool),
+            _ => Err(self.error(index, "a boolean")),
+        }
+    }
+
+    pub fn int(&self, index: usize) -> Result<i32, ArgError> {
+        match self.get(index) {
+            Some(JsValue::Int(int)) => Ok(*int),
+            _ => Err(self.error(index, "an integer")),
+        }
+    }
+
+    // QuickJS stores integral numbers as Int, so both variants are numbers
+    pub fn float(&self, index: usize) -> Result<f64, ArgError> {
+        match self.get(index) {
+            Some(JsValue::Float(float)) => Ok(*float),
+            Some(JsValue::Int(int)) => Ok(*int as f64),
+            _ => Err(self.error(index, "a number")),
+        }
+    }
+
+    /// A string parsed into `T`, e.g. the decimal strings that carry u64 and
+    /// u128 values from JS.
+    pub fn parse<T: FromStr>(&self, index: usize) -> Result<T, ArgError> {
+        let string = self.string(index)?;
+
+        string.parse().map_err(|_| ArgError {
+            found: format!("{:?}", string),
+            ..self.error(index, &format!("a string containing a {}", short_type_name::<T>()))
+        })
+    }
+
+    /// A string of JSON deserialized into `T`; `expected` describes `T`.
+    pub fn json<T: DeserializeOwned>(&self, index: usize, expected: &str) -> Result<T, ArgError> {
+        let string = self.string(index)?;
+
+        serde_json::from_str(&string).map_err(|error| ArgError {
+            found: format!("invalid JSON ({})", error),
+            ..self.error(index, expected)
+        })
+    }
+}
+
+/// Runs a native's body and throws its ArgError, if any, as a TypeError.
+pub fn call<F>(context: &mut Context, native: &str, argv: &[JsValue], body: F) -> JsValue
+where
+    F: FnOnce(&mut Context, &Args) -> Result<JsValue, ArgError>,
+{
+    let args = Args::new(native, argv);
+
+    match body(context, &args) {
+        Ok(js_value) => js_value,
+        Err(error) => error.throw(context),
+    }
+}
+
+fn type_name(js_value: Option<&JsValue>) -> &'static str {
+    match js_value {
+        None | Some(JsValue::UnDefined) => "undefined",
+        Some(JsValue::Null) => "null",
+        Some(JsValue::Bool(_)) => "boolean",
+        Some(JsValue::Int(_)) | Some(JsValue::Float(_)) => "number",
+        Some(JsValue::BigNum(_)) => "bigint",
+        Some(JsValue::String(_)) => "string",
+        Some(JsValue::ArrayBuffer(_)) => "ArrayBuffer",
+        Some(JsValue::Array(_)) => "Array",
+        Some(JsValue::Function(_)) => "function",
+        Some(JsValue::Promise(_)) => "Promise",
+        Some(JsValue::Object(_)) => "object",
+        Some(_) => "an unsupported value",
+    }
+}
+
+// "u64" rather than "core::primitive::u64"
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+
+    name.rsplit("::").next().unwrap_or(name)
+}
@@ -0,0 +1,398 @@
+// Persistent job scheduler. A job calls _azleJobCallbacks[callbackName](args)
+// at its next run time, once or every interval, and retries with exponential
+// backoff when the callback throws or its promise rejects. Jobs live in stable
+// memory (layout::JOBS) and are re-armed on post_upgrade, before the JS
+// post_upgrade runs, so callbackName must be a stable name that the JS init
+// and post_upgrade register.
+//
+// The callback runs through the _azleRunJob trampoline, which awaits it and
+// reports the outcome with the settleJob native, passing the run id that
+// identifies the attempt. Only the attempt in flight can settle its job; the
+// report of an earlier attempt is ignored.
+//
+// While an attempt is in flight the job starts no other. If the attempt has not
+// reported back within run_timeout_nanos, e.g. because it trapped in the
+// callback of a call it awaits, it counts as failed like one that throws.
+//
+// A job that runs once and has used up its attempts is kept as failed: it no
+// longer runs but stays in listJobs, with its last_error, until cancelJob
+// removes it.
+
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, time::Duration};
+
+use candid::{CandidType, Deserialize};
+use ic_stable_structures::{storable::Bound, StableBTreeMap, Storable};
+use wasmedge_quickjs::{AsObject, Context, JsFunction, JsValue};
+
+use crate::{async_bridge, js_error, layout, run_event_loop, Memory};
+
+const CALLBACKS: &str = "_azleJobCallbacks";
+
+const RUN_JOB: &str = "_azleRunJob";
+
+const RUN_JOB_JS: &str = r#"
+globalThis._azleRunJob = function (jobId, runId, callback, args) {
+    Promise.resolve()
+        .then(() => callback(args))
+        .then(
+            () => globalThis._azleIc.settleJob(jobId, runId, true, undefined),
+            (error) =>
+                globalThis._azleIc.settleJob(
+                    jobId,
+                    runId,
+                    false,
+                    error instanceof Error
+                        ? `${error.name}: ${error.message}`
+                        : String(error)
+                )
+        );
+};
+"#;
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per run, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry, multiplied by backoff_multiplier for
+    /// each further retry up to max_backoff_nanos.
+    pub initial_backoff_nanos: u64,
+    pub max_backoff_nanos: u64,
+    pub backoff_multiplier: u32,
+    /// How long an attempt may take before it counts as failed.
+    pub run_timeout_nanos: u64,
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> u64 {
+        let multiplier = (self.backoff_multiplier as u64).saturating_pow(attempt.saturating_sub(1));
+
+        self.initial_backoff_nanos
+            .saturating_mul(multiplier)
+            .min(self.max_backoff_nanos)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Job {
+    pub id: u64,
+    pub callback_name: String,
+    pub args: Vec<u8>,
+    /// In nanoseconds since the epoch, like ic_cdk::api::time.
+    pub next_run_nanos: u64,
+    /// None for a job that runs once.
+    pub interval_nanos: Option<u64>,
+    pub retry_policy: RetryPolicy,
+    /// Attempts made in the current run.
+    pub attempt: u32,
+    pub last_error: Option<String>,
+    /// Id of the latest attempt, counted over the job's lifetime.
+    pub run_id: u64,
+    /// Start of the attempt in flight, None between attempts.
+    pub running_since_nanos: Option<u64>,
+    /// When the last attempt of a job that runs once failed. A failed job is
+    /// kept, unarmed, until it is cancelled. None for jobs stored before
+    /// failed jobs were kept.
+    pub failed_at_nanos: Option<u64>,
+}
+
+impl Storable for Job {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        candid::encode_one(self)
+            .expect("candid encoding of a job failed")
+            .into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("candid decoding of a job failed")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+thread_local! {
+    static JOBS: RefCell<StableBTreeMap<u64, Job, Memory>> = RefCell::new(
+        StableBTreeMap::init(layout::memory(layout::JOBS))
+    );
+
+    static JOB_TIMERS: RefCell<BTreeMap<u64, ic_cdk_timers::TimerId>> = RefCell::new(BTreeMap::new());
+
+    static NEXT_JOB_ID: RefCell<u64> = RefCell::new(0);
+}
+
+/// Stores a job that first runs after `delay` and arms it. Returns its id.
+pub fn schedule(
+    callback_name: String,
+    args: Vec<u8>,
+    delay: Duration,
+    interval: Option<Duration>,
+    retry_policy: RetryPolicy,
+) -> u64 {
+    let last_job_id = JOBS.with(|jobs| jobs.borrow().last_key_value().map(|(id, _)| id));
+
+    let id = NEXT_JOB_ID.with(|next_job_id| {
+        let mut next_job_id = next_job_id.borrow_mut();
+        let id = (*next_job_id).max(last_job_id.map_or(0, |last_job_id| last_job_id + 1));
+        *next_job_id = id + 1;
+        id
+    });
+
+    store_and_arm(Job {
+        id,
+        callback_name,
+        args,
+        next_run_nanos: ic_cdk::api::time().saturating_add(delay.as_nanos() as u64),
+        interval_nanos: interval.map(|interval| interval.as_nanos() as u64),
+        retry_policy,
+        attempt: 0,
+        last_error: None,
+        run_id: 0,
+        running_since_nanos: None,
+        failed_at_nanos: None,
+    });
+
+    id
+}
+
+/// Removes the job `id`, failed or not. Returns false if there is no such job.
+pub fn cancel(id: u64) -> bool {
+    disarm(id);
+
+    JOBS.with(|jobs| jobs.borrow_mut().remove(&id)).is_some()
+}
+
+/// All jobs, by id.
+pub fn list() -> Vec<Job> {
+    JOBS.with(|jobs| jobs.borrow().iter().map(|(_, job)| job).collect())
+}
+
+/// Arms the jobs of the previous code, except failed ones. Runs in
+/// post_upgrade.
+pub fn rearm() {
+    for job in list() {
+        if job.failed_at_nanos.is_none() {
+            store_and_arm(job);
+        }
+    }
+}
+
+/// Records the outcome of the attempt `run_id` of the job `id`, as reported by
+/// _azleRunJob. Ignored unless that attempt is still in flight.
+pub fn settle(id: u64, run_id: u64, result: Result<(), String>) {
+    if let Err(error) = &result {
+        ic_cdk::println!("job {} run {}: {}", id, run_id, error);
+    }
+
+    // The job was cancelled, or the attempt timed out
+    let mut job = match JOBS.with(|jobs| jobs.borrow().get(&id)) {
+        Some(job) if job.run_id == run_id && job.running_since_nanos.is_some() => job,
+        _ => return,
+    };
+
+    job.running_since_nanos = None;
+
+    match result {
+        Ok(()) => {
+            job.attempt = 0;
+            job.last_error = None;
+
+            match job.interval_nanos {
+                Some(interval_nanos) => {
+                    job.next_run_nanos = ic_cdk::api::time().saturating_add(interval_nanos);
+
+                    store_and_arm(job);
+                }
+                None => {
+                    disarm(id);
+
+                    JOBS.with(|jobs| jobs.borrow_mut().remove(&id));
+                }
+            }
+        }
+        Err(error) => {
+            job.last_error = Some(error);
+
+            reschedule_as_failed(job);
+        }
+    }
+}
+
+fn store_and_arm(job: Job) {
+    let id = job.id;
+    let delay = Duration::from_nanos(job.next_run_nanos.saturating_sub(ic_cdk::api::time()));
+
+    disarm(id);
+
+    let timer_id = ic_cdk_timers::set_timer(delay, move || run(id));
+
+    JOB_TIMERS.with(|job_timers| job_timers.borrow_mut().insert(id, timer_id));
+    JOBS.with(|jobs| jobs.borrow_mut().insert(id, job));
+}
+
+fn disarm(id: u64) {
+    if let Some(timer_id) = JOB_TIMERS.with(|job_timers| job_timers.borrow_mut().remove(&id)) {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+}
+
+// Starts the next attempt of the job, or, if an attempt is in flight, the
+// timer is its timeout and it has failed
+fn run(id: u64) {
+    JOB_TIMERS.with(|job_timers| job_timers.borrow_mut().remove(&id));
+
+    let mut job = match JOBS.with(|jobs| jobs.borrow().get(&id)) {
+        Some(job) if job.failed_at_nanos.is_none() => job,
+        _ => return,
+    };
+
+    if job.running_since_nanos.is_some() {
+        ic_cdk::println!("job {} run {}: timed out", id, job.run_id);
+
+        job.running_since_nanos = None;
+        job.last_error = Some("the attempt timed out".to_string());
+
+        reschedule_as_failed(job);
+
+        return;
+    }
+
+    let now = ic_cdk::api::time();
+
+    job.attempt += 1;
+    job.run_id += 1;
+    job.running_since_nanos = Some(now);
+    job.next_run_nanos = now.saturating_add(job.retry_policy.run_timeout_nanos);
+
+    let run_id = job.run_id;
+    let callback_name = job.callback_name.clone();
+    let args = job.args.clone();
+
+    store_and_arm(job);
+
+    async_bridge::with_context(|context| {
+        let callback = match context
+            .get_global()
+            .get(CALLBACKS)
+            .to_obj()
+            .and_then(|callbacks| callbacks.get(&callback_name).to_function())
+        {
+            Some(callback) => callback,
+            None => {
+                let error = format!("{}.{} is not a function", CALLBACKS, callback_name);
+
+                settle(id, run_id, Err(error));
+
+                return;
+            }
+        };
+
+        let run_job = run_job_trampoline(context);
+        let run_job_args = [
+            context.new_string(&id.to_string()).into(),
+            context.new_string(&run_id.to_string()).into(),
+            JsValue::Function(callback),
+            context.new_array_buffer(&args).into(),
+        ];
+
+        match js_error::call(context, &run_job, &run_job_args) {
+            Ok(_) => run_event_loop(context),
+            Err(error) => js_error::trap(&error),
+        };
+    });
+}
+
+// Schedules the retry, or once the attempts are used up the next run, or
+// keeps a job that runs once as failed
+fn reschedule_as_failed(mut job: Job) {
+    let now = ic_cdk::api::time();
+
+    if job.attempt < job.retry_policy.max_attempts {
+        job.next_run_nanos = now.saturating_add(job.retry_policy.backoff(job.attempt));
+
+        store_and_arm(job);
+    } else if let Some(interval_nanos) = job.interval_nanos {
+        job.attempt = 0;
+        job.next_run_nanos = now.saturating_add(interval_nanos);
+
+        store_and_arm(job);
+    } else {
+        disarm(job.id);
+
+        job.failed_at_nanos = Some(now);
+
+        JOBS.with(|jobs| jobs.borrow_mut().insert(job.id, job));
+    }
+}
+
+fn run_job_trampoline(context: &mut Context) -> JsFunction {
+    if let Some(function) = context.get_global().get(RUN_JOB).to_function() {
+        return function;
+    }
+
+    context.eval_global_str(RUN_JOB_JS.to_string());
+
+    context
+        .get_global()
+        .get(RUN_JOB)
+        .to_function()
+        .expect("_azleRunJob could not be installed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(
+        initial_backoff_nanos: u64,
+        max_backoff_nanos: u64,
+        backoff_multiplier: u32,
+    ) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: u32::MAX,
+            initial_backoff_nanos,
+            max_backoff_nanos,
+            backoff_multiplier,
+            run_timeout_nanos: 0,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_by_the_multiplier() {
+        let policy = policy(1_000, u64::MAX, 3);
+
+        assert_eq!(policy.backoff(1), 1_000);
+        assert_eq!(policy.backoff(2), 3_000);
+        assert_eq!(policy.backoff(3), 9_000);
+        assert_eq!(policy.backoff(0), 1_000);
+    }
+
+    #[test]
+    fn backoff_is_capped_at_max_backoff_nanos() {
+        let policy = policy(1_000, 5_000, 2);
+
+        let backoffs: Vec<u64> = (1..=6).map(|attempt| policy.backoff(attempt)).collect();
+
+        assert_eq!(backoffs, vec![1_000, 2_000, 4_000, 5_000, 5_000, 5_000]);
+        assert_eq!(policy.backoff(u32::MAX), 5_000);
+    }
+
+    #[test]
+    fn backoff_saturates_instead_of_overflowing() {
+        let uncapped = policy(u64::MAX / 2, u64::MAX, 2);
+
+        assert_eq!(uncapped.backoff(3), u64::MAX);
+        assert_eq!(uncapped.backoff(u32::MAX), u64::MAX);
+
+        let capped = policy(1, 60_000_000_000, u32::MAX);
+
+        assert_eq!(capped.backoff(64), 60_000_000_000);
+        assert_eq!(capped.backoff(u32::MAX), 60_000_000_000);
+    }
+
+    #[test]
+    fn backoff_without_growth_stays_at_the_initial_delay() {
+        let policy = policy(1_000, u64::MAX, 1);
+
+        assert_eq!(policy.backoff(1), 1_000);
+        assert_eq!(policy.backoff(u32::MAX), 1_000);
+    }
+}
@@ -0,0 +1,202 @@
+// Registry of the timers set from JS. Every timer gets an id of its own, which
+// setTimer and friends return and clearTimer takes, and calls the function
+// _azleTimerCallbacks[callbackName] when it fires. Interval timers are
+// re-armed as one-shot ic-cdk timers on every firing, so the registry always
+// knows when each timer fires next.
+//
+// A persistent timer is also stored in stable memory (layout::TIMERS) and is
+// re-armed on post_upgrade, before the JS post_upgrade runs, at its stored next
+// fire time (right away if that has passed). Its callbackName must therefore
+// be a stable name that the JS init and post_upgrade register, not a
+// per-closure id.
+//
+// Controllers can inspect the registry with the _azle_timers query and cancel
+// timers with the _azle_cancel_timer update.
+
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, time::Duration};
+
+use candid::{CandidType, Deserialize};
+use ic_stable_structures::{storable::Bound, StableBTreeMap, Storable};
+
+use crate::{async_bridge, layout, Memory};
+
+const CALLBACKS: &str = "_azleTimerCallbacks";
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct TimerInfo {
+    pub id: u64,
+    pub callback_name: String,
+    /// None for a one-shot timer.
+    pub interval_nanos: Option<u64>,
+    /// In nanoseconds since the epoch, like ic_cdk::api::time.
+    pub next_fire_nanos: u64,
+    pub persistent: bool,
+}
+
+impl Storable for TimerInfo {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        candid::encode_one(self)
+            .expect("candid encoding of a timer failed")
+            .into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("candid decoding of a timer failed")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+struct Timer {
+    info: TimerInfo,
+    timer_id: ic_cdk_timers::TimerId,
+}
+
+thread_local! {
+    static TIMERS: RefCell<BTreeMap<u64, Timer>> = RefCell::new(BTreeMap::new());
+
+    static NEXT_TIMER_ID: RefCell<u64> = RefCell::new(0);
+
+    static PERSISTENT_TIMERS: RefCell<StableBTreeMap<u64, TimerInfo, Memory>> = RefCell::new(
+        StableBTreeMap::init(layout::memory(layout::TIMERS))
+    );
+}
+
+/// Sets a timer that fires after `delay`, then every `interval` if given, and
+/// returns its id.
+pub fn set(
+    delay: Duration,
+    interval: Option<Duration>,
+    callback_name: String,
+    persistent: bool,
+) -> u64 {
+    let id = NEXT_TIMER_ID.with(|next_timer_id| {
+        let mut next_timer_id = next_timer_id.borrow_mut();
+        let id = *next_timer_id;
+        *next_timer_id += 1;
+        id
+    });
+
+    arm(TimerInfo {
+        id,
+        callback_name,
+        interval_nanos: interval.map(|interval| interval.as_nanos() as u64),
+        next_fire_nanos: ic_cdk::api::time().saturating_add(delay.as_nanos() as u64),
+        persistent,
+    });
+
+    id
+}
+
+/// Cancels the timer `id`. Returns false if there is no such timer.
+pub fn clear(id: u64) -> bool {
+    match remove(id) {
+        Some(timer) => {
+            ic_cdk_timers::clear_timer(timer.timer_id);
+            true
+        }
+        None => false,
+    }
+}
+
+/// All active timers, by id.
+pub fn list() -> Vec<TimerInfo> {
+    TIMERS.with(|timers| {
+        timers
+            .borrow()
+            .values()
+            .map(|timer| timer.info.clone())
+            .collect()
+    })
+}
+
+/// Re-arms the persistent timers of the previous code. Runs in post_upgrade.
+pub fn rearm() {
+    let persistent_timers: Vec<TimerInfo> = PERSISTENT_TIMERS.with(|persistent_timers| {
+        persistent_timers
+            .borrow()
+            .iter()
+            .map(|(_, info)| info)
+            .collect()
+    });
+
+    if let Some(last) = persistent_timers.last() {
+        NEXT_TIMER_ID.with(|next_timer_id| *next_timer_id.borrow_mut() = last.id + 1);
+    }
+
+    for info in persistent_timers {
+        arm(info);
+    }
+}
+
+// Schedules the timer for info.next_fire_nanos and registers it
+fn arm(info: TimerInfo) {
+    let id = info.id;
+    let delay = Duration::from_nanos(info.next_fire_nanos.saturating_sub(ic_cdk::api::time()));
+
+    let timer_id = ic_cdk_timers::set_timer(delay, move || fire(id));
+
+    if info.persistent {
+        PERSISTENT_TIMERS
+            .with(|persistent_timers| persistent_timers.borrow_mut().insert(id, info.clone()));
+    }
+
+    TIMERS.with(|timers| timers.borrow_mut().insert(id, Timer { info, timer_id }));
+}
+
+fn remove(id: u64) -> Option<Timer> {
+    PERSISTENT_TIMERS.with(|persistent_timers| persistent_timers.borrow_mut().remove(&id));
+
+    TIMERS.with(|timers| timers.borrow_mut().remove(&id))
+}
+
+fn fire(id: u64) {
+    let info = match remove(id) {
+        Some(timer) => timer.info,
+        None => return,
+    };
+
+    let callback_name = info.callback_name.clone();
+
+    // Re-armed before the callback runs, so that the callback can clear it
+    if let Some(interval_nanos) = info.interval_nanos {
+        let now = ic_cdk::api::time();
+        let next_fire_nanos = match info.next_fire_nanos.saturating_add(interval_nanos) {
+            next_fire_nanos if next_fire_nanos > now => next_fire_nanos,
+            // Skip the firings that were missed rather than run them back to back
+            _ => now.saturating_add(interval_nanos),
+        };
+
+        arm(TimerInfo {
+            next_fire_nanos,
+            ..info
+        });
+    }
+
+    if !async_bridge::run_callback(CALLBACKS, &callback_name) {
+        ic_cdk::println!(
+            "timer {}: {}.{} is not a function",
+            id,
+            CALLBACKS,
+            callback_name
+        );
+    }
+}
+
+fn caller_is_controller() -> Result<(), String> {
+    if ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        Ok(())
+    } else {
+        Err("only controllers can manage timers".to_string())
+    }
+}
+
+#[ic_cdk_macros::query(name = "_azle_timers", guard = "caller_is_controller")]
+fn timers() -> Vec<TimerInfo> {
+    list()
+}
+
+#[ic_cdk_macros::update(name = "_azle_cancel_timer", guard = "caller_is_controller")]
+fn cancel_timer(id: u64) -> bool {
+    clear(id)
+}
@@ -65,6 +65,8 @@ pub fn canister_methods(_: TokenStream) -> TokenStream {
     let init_method = quote! {
         #[ic_cdk_macros::init]
         fn init() {
+            layout::validate();
+
             // let polyfill_memory =
             //     MEMORY_MANAGER_REF_CELL.with(|manager| manager.borrow().get(MemoryId::new(254)));
             // ic_wasi_polyfill::init_with_memory(&[], &[#(#env_vars),*], polyfill_memory);
@@ -90,6 +92,9 @@ pub fn canister_methods(_: TokenStream) -> TokenStream {
     let post_upgrade_method = quote! {
         #[ic_cdk_macros::post_upgrade]
         fn post_upgrade() {
+            // Before anything reads stable memory through the previous layout
+            layout::validate();
+
             // let polyfill_memory =
             //     MEMORY_MANAGER_REF_CELL.with(|manager| manager.borrow().get(MemoryId::new(254)));
             // ic_wasi_polyfill::init_with_memory(&[], &[#(#env_vars),*], polyfill_memory);
@@ -98,6 +103,10 @@ pub fn canister_methods(_: TokenStream) -> TokenStream {
 
             ASSETS_DIR.extract("/").unwrap();
 
+            // Before the JS post_upgrade, which may set timers and jobs of its own
+            timers::rearm();
+            scheduler::rearm();
+
             initialize_js(std::str::from_utf8(MAIN_JS).unwrap(), false);
         }
     };
@@ -320,18 +329,13 @@ fn get_guard_token_stream(
 
                         let guard_function = guard_functions.get(#guard_name).to_function().unwrap();
 
-                        let result = guard_function.call(&[]);
-
-                        // TODO error handling is mostly done in JS right now
-                        // TODO we would really like wasmedge-quickjs to add
-                        // TODO good error info to JsException and move error handling
-                        // TODO out of our own code
-                        match &result {
-                            wasmedge_quickjs::JsValue::Exception(js_exception) => {
-                                js_exception.dump_error();
-                                Err("TODO needs error info".to_string())
+                        // A throwing guard rejects the call with the JS error as the message
+                        match js_error::call(context, &guard_function, &[]) {
+                            Err(error) => {
+                                ic_cdk::println!("{}", error);
+                                Err(error.to_string())
                             }
-                            _ => {
+                            Ok(_) => {
                                 // TODO what if errors happen in here?
                                 // TODO can guard functions even be async?
                                 // TODO I don't think they can
@@ -0,0 +1,411 @@
+//! Registry of VetKD key ids.
+//!
+//! Controllers register named key ids (e.g. `test_key_1`, `key_1`) under an
+//! alias and assign them to tenants or purposes. Each derivation resolves its
+//! key in this order: explicit `key` selector, tenant assignment, purpose
+//! assignment, then the built-in `default` alias.
+//!
+//! ## Migration
+//! `default` is always `key_1` on BLS12-381 G2, exactly what the canisters
+//! used before the registry existed, and cannot be changed. Contexts and
+//! inputs do not depend on the key, so callers that pass no selector and have
+//! no assignment keep deriving the same keys. Aliases cannot be repointed and
+//! assignments cannot be changed once made.
+//!
+//! Assigning a tenant or purpose switches the keys it derives from then on, so
+//! the registry records every scope whose keys a derivation depended on
+//! ([`KeyRegistry::resolve_for_derivation`]) and refuses to assign it unless
+//! the assignment is forced. Scopes that derived before the record existed are
+//! not in it; assign those before their first derivation.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::management_canister::{VetKDCurve, VetKDKeyId};
+use ic_stable_structures::{Memory, StableBTreeMap};
+use std::fmt;
+
+/// Alias of the built-in key every derivation used before the registry existed.
+pub const DEFAULT_ALIAS: &str = "default";
+const DEFAULT_KEY_NAME: &str = "key_1";
+
+const MAX_ALIAS_LEN: usize = 32;
+
+/// Curves VetKD supports; mirrors `VetKDCurve`.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, CandidType, Deserialize, PartialEq, Eq, Debug)]
+pub enum KeyCurve {
+    Bls12_381_G2,
+}
+
+#[derive(Clone, CandidType, Deserialize, PartialEq, Eq, Debug)]
+pub struct KeyConfig {
+    /// Key name configured in the subnet (e.g. `key_1`, `test_key_1`).
+    pub name: String,
+    pub curve: KeyCurve,
+}
+
+#[derive(Clone, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum KeyScope {
+    Tenant(Principal),
+    Purpose(String),
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct KeyEntry {
+    pub alias: String,
+    pub config: KeyConfig,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct KeyAssignment {
+    pub scope: KeyScope,
+    pub alias: String,
+}
+
+crate::candid_storable!(KeyConfig);
+crate::candid_storable!(KeyScope);
+
+/// Why a registry change or lookup was refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyError {
+    /// The alias is empty or longer than 32 bytes.
+    InvalidAlias,
+    /// The key config has an empty name.
+    EmptyKeyName,
+    /// The alias is already registered.
+    AliasTaken(String),
+    /// No key is registered under the alias.
+    UnknownAlias(String),
+    /// `default` cannot be removed.
+    DefaultKey,
+    /// The alias is still assigned to a scope.
+    StillAssigned(String),
+    /// The purpose is not one the canister derives with.
+    UnknownPurpose { purpose: String, expected: &'static [&'static str] },
+    /// The scope already has a key assignment.
+    AlreadyAssigned,
+    /// The scope has derived keys, which the assignment would switch.
+    ScopeHasDerived,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::InvalidAlias => write!(f, "alias must be 1..={MAX_ALIAS_LEN} bytes"),
+            KeyError::EmptyKeyName => write!(f, "key name must not be empty"),
+            KeyError::AliasTaken(alias) => write!(f, "alias `{alias}` is already registered"),
+            KeyError::UnknownAlias(alias) => write!(f, "unknown key alias `{alias}`"),
+            KeyError::DefaultKey => write!(f, "the default key cannot be removed"),
+            KeyError::StillAssigned(alias) => write!(f, "alias `{alias}` is still assigned"),
+            KeyError::UnknownPurpose { purpose, expected } => {
+                write!(f, "unknown purpose `{purpose}`; expected one of {expected:?}")
+            }
+            KeyError::AlreadyAssigned => write!(f, "scope already has a key assignment"),
+            KeyError::ScopeHasDerived => write!(
+                f,
+                "scope has already derived keys, which the assignment would switch; \
+                 pass `force` to assign it anyway"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+fn default_config() -> KeyConfig {
+    KeyConfig { name: DEFAULT_KEY_NAME.into(), curve: KeyCurve::Bls12_381_G2 }
+}
+
+fn to_key_id(config: KeyConfig) -> VetKDKeyId {
+    let curve = match config.curve {
+        KeyCurve::Bls12_381_G2 => VetKDCurve::Bls12_381_G2,
+    };
+    VetKDKeyId { name: config.name, curve }
+}
+
+/// Registered aliases and assignments of one canister.
+pub struct KeyRegistry<M: Memory> {
+    /// Purposes that can be assigned a key.
+    purposes: &'static [&'static str],
+    /// alias -> key config (`default` is implicit and never stored)
+    keys: StableBTreeMap<String, KeyConfig, M>,
+    /// scope -> alias
+    assignments: StableBTreeMap<KeyScope, String, M>,
+    /// scopes whose keys a derivation depended on
+    derived: StableBTreeMap<KeyScope, (), M>,
+}
+
+impl<M: Memory> KeyRegistry<M> {
+    /// Opens the registry stored in `keys`, `assignments` and `derived`.
+    pub fn init(purposes: &'static [&'static str], keys: M, assignments: M, derived: M) -> Self {
+        KeyRegistry {
+            purposes,
+            keys: StableBTreeMap::init(keys),
+            assignments: StableBTreeMap::init(assignments),
+            derived: StableBTreeMap::init(derived),
+        }
+    }
+
+    fn config_of(&self, alias: &str) -> Option<KeyConfig> {
+        if alias == DEFAULT_ALIAS {
+            return Some(default_config());
+        }
+        self.keys.get(&alias.to_string())
+    }
+
+    /// Resolves the key id for a derivation.
+    ///
+    /// `tenant` is the principal whose material is derived, `None` for
+    /// canister-wide contexts.
+    pub fn resolve(
+        &self,
+        selector: Option<String>,
+        tenant: Option<Principal>,
+        purpose: &str,
+    ) -> Result<VetKDKeyId, KeyError> {
+        let alias = selector
+            .or_else(|| tenant.and_then(|t| self.assignments.get(&KeyScope::Tenant(t))))
+            .or_else(|| self.assignments.get(&KeyScope::Purpose(purpose.to_string())))
+            .unwrap_or_else(|| DEFAULT_ALIAS.to_string());
+        self.config_of(&alias).map(to_key_id).ok_or(KeyError::UnknownAlias(alias))
+    }
+
+    /// Resolves the key id like [`Self::resolve`] and records the scopes whose
+    /// assignment would switch it: none with a selector, else the tenant if it
+    /// is unassigned, and the purpose if neither is assigned.
+    pub fn resolve_for_derivation(
+        &mut self,
+        selector: Option<String>,
+        tenant: Option<Principal>,
+        purpose: &str,
+    ) -> Result<VetKDKeyId, KeyError> {
+        let key_id = self.resolve(selector.clone(), tenant, purpose)?;
+        if selector.is_some() {
+            return Ok(key_id);
+        }
+        if let Some(t) = tenant {
+            let scope = KeyScope::Tenant(t);
+            if self.assignments.contains_key(&scope) {
+                return Ok(key_id);
+            }
+            self.mark_derived(scope);
+        }
+        let scope = KeyScope::Purpose(purpose.to_string());
+        if !self.assignments.contains_key(&scope) {
+            self.mark_derived(scope);
+        }
+        Ok(key_id)
+    }
+
+    fn mark_derived(&mut self, scope: KeyScope) {
+        if !self.derived.contains_key(&scope) {
+            self.derived.insert(scope, ());
+        }
+    }
+
+    /// Whether a derivation has depended on the keys of `scope`.
+    pub fn has_derived(&self, scope: &KeyScope) -> bool {
+        self.derived.contains_key(scope)
+    }
+
+    /// Registers `config` under `alias`. Aliases are permanent.
+    pub fn add(&mut self, alias: String, config: KeyConfig) -> Result<(), KeyError> {
+        if alias.is_empty() || alias.len() > MAX_ALIAS_LEN {
+            return Err(KeyError::InvalidAlias);
+        }
+        if config.name.is_empty() {
+            return Err(KeyError::EmptyKeyName);
+        }
+        if self.config_of(&alias).is_some() {
+            return Err(KeyError::AliasTaken(alias));
+        }
+        self.keys.insert(alias, config);
+        Ok(())
+    }
+
+    /// Removes an alias that is not assigned to anything. Returns whether it
+    /// was registered.
+    pub fn remove(&mut self, alias: &str) -> Result<bool, KeyError> {
+        if alias == DEFAULT_ALIAS {
+            return Err(KeyError::DefaultKey);
+        }
+        if self.assignments.iter().any(|(_, v)| v == alias) {
+            return Err(KeyError::StillAssigned(alias.to_string()));
+        }
+        Ok(self.keys.remove(&alias.to_string()).is_some())
+    }
+
+    /// Assigns `alias` to `scope`. Assignments are permanent. A scope that has
+    /// derived keys is refused unless `force` is set.
+    pub fn assign(&mut self, scope: KeyScope, alias: String, force: bool) -> Result<(), KeyError> {
+        if let KeyScope::Purpose(p) = &scope {
+            if !self.purposes.contains(&p.as_str()) {
+                return Err(KeyError::UnknownPurpose {
+                    purpose: p.clone(),
+                    expected: self.purposes,
+                });
+            }
+        }
+        if self.config_of(&alias).is_none() {
+            return Err(KeyError::UnknownAlias(alias));
+        }
+        if self.assignments.contains_key(&scope) {
+            return Err(KeyError::AlreadyAssigned);
+        }
+        if !force && self.has_derived(&scope) {
+            return Err(KeyError::ScopeHasDerived);
+        }
+        self.assignments.insert(scope, alias);
+        Ok(())
+    }
+
+    /// All aliases, `default` first.
+    pub fn list(&self) -> Vec<KeyEntry> {
+        let mut out = vec![KeyEntry { alias: DEFAULT_ALIAS.into(), config: default_config() }];
+        out.extend(self.keys.iter().map(|(alias, config)| KeyEntry { alias, config }));
+        out
+    }
+
+    pub fn assignments(&self) -> Vec<KeyAssignment> {
+        self.assignments.iter().map(|(scope, alias)| KeyAssignment { scope, alias }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    const PURPOSES: &[&str] = &["db", "ibe"];
+
+    fn registry() -> KeyRegistry<VirtualMemory<DefaultMemoryImpl>> {
+        let mm = MemoryManager::init(DefaultMemoryImpl::default());
+        let memory = |id| mm.get(MemoryId::new(id));
+        KeyRegistry::init(PURPOSES, memory(0), memory(1), memory(2))
+    }
+
+    fn test_key() -> KeyConfig {
+        KeyConfig { name: "test_key_1".into(), curve: KeyCurve::Bls12_381_G2 }
+    }
+
+    fn tenant(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    #[test]
+    fn unassigned_derivations_use_key_1() {
+        let r = registry();
+        let key_id = r.resolve(None, Some(tenant(1)), "db").unwrap();
+        assert_eq!(key_id.name, "key_1");
+        assert_eq!(key_id.curve, VetKDCurve::Bls12_381_G2);
+    }
+
+    #[test]
+    fn resolves_selector_then_tenant_then_purpose() {
+        let mut r = registry();
+        r.add("test".into(), test_key()).unwrap();
+        r.add("other".into(), KeyConfig { name: "key_2".into(), ..test_key() }).unwrap();
+        r.assign(KeyScope::Purpose("db".into()), "test".into(), false).unwrap();
+        r.assign(KeyScope::Tenant(tenant(1)), "other".into(), false).unwrap();
+
+        assert_eq!(r.resolve(Some("default".into()), Some(tenant(1)), "db").unwrap().name, "key_1");
+        assert_eq!(r.resolve(None, Some(tenant(1)), "db").unwrap().name, "key_2");
+        assert_eq!(r.resolve(None, Some(tenant(2)), "db").unwrap().name, "test_key_1");
+        assert_eq!(r.resolve(None, None, "ibe").unwrap().name, "key_1");
+        assert_eq!(
+            r.resolve(Some("missing".into()), None, "db"),
+            Err(KeyError::UnknownAlias("missing".into()))
+        );
+    }
+
+    #[test]
+    fn aliases_are_validated_and_permanent() {
+        let mut r = registry();
+        assert_eq!(r.add(String::new(), test_key()), Err(KeyError::InvalidAlias));
+        assert_eq!(r.add("a".repeat(33), test_key()), Err(KeyError::InvalidAlias));
+        let unnamed = KeyConfig { name: String::new(), ..test_key() };
+        assert_eq!(r.add("test".into(), unnamed), Err(KeyError::EmptyKeyName));
+        assert_eq!(
+            r.add("default".into(), test_key()),
+            Err(KeyError::AliasTaken("default".into()))
+        );
+        r.add("test".into(), test_key()).unwrap();
+        assert_eq!(r.add("test".into(), test_key()), Err(KeyError::AliasTaken("test".into())));
+        assert_eq!(
+            r.list().iter().map(|e| e.alias.as_str()).collect::<Vec<_>>(),
+            ["default", "test"]
+        );
+    }
+
+    #[test]
+    fn assigned_aliases_cannot_be_removed() {
+        let mut r = registry();
+        r.add("test".into(), test_key()).unwrap();
+        r.add("spare".into(), test_key()).unwrap();
+        r.assign(KeyScope::Purpose("ibe".into()), "test".into(), false).unwrap();
+
+        assert_eq!(r.remove("default"), Err(KeyError::DefaultKey));
+        assert_eq!(r.remove("test"), Err(KeyError::StillAssigned("test".into())));
+        assert_eq!(r.remove("spare"), Ok(true));
+        assert_eq!(r.remove("spare"), Ok(false));
+    }
+
+    #[test]
+    fn assignments_are_validated_and_permanent() {
+        let mut r = registry();
+        r.add("test".into(), test_key()).unwrap();
+        let scope = KeyScope::Tenant(tenant(1));
+
+        assert_eq!(
+            r.assign(KeyScope::Purpose("sign".into()), "test".into(), false),
+            Err(KeyError::UnknownPurpose { purpose: "sign".into(), expected: PURPOSES })
+        );
+        assert_eq!(
+            r.assign(scope.clone(), "missing".into(), false),
+            Err(KeyError::UnknownAlias("missing".into()))
+        );
+        r.assign(scope.clone(), "test".into(), false).unwrap();
+        assert_eq!(
+            r.assign(scope.clone(), "default".into(), false),
+            Err(KeyError::AlreadyAssigned)
+        );
+        assert_eq!(r.assignments().len(), 1);
+    }
+
+    #[test]
+    fn derivations_record_the_scopes_an_assignment_would_switch() {
+        let mut r = registry();
+        r.add("test".into(), test_key()).unwrap();
+        let tenant_scope = |n| KeyScope::Tenant(tenant(n));
+        let db = KeyScope::Purpose("db".into());
+
+        r.resolve_for_derivation(Some("test".into()), Some(tenant(1)), "db").unwrap();
+        assert!(!r.has_derived(&tenant_scope(1)));
+        assert!(!r.has_derived(&db));
+
+        r.resolve_for_derivation(None, Some(tenant(1)), "db").unwrap();
+        assert!(r.has_derived(&tenant_scope(1)));
+        assert!(r.has_derived(&db));
+
+        r.assign(tenant_scope(2), "test".into(), false).unwrap();
+        r.resolve_for_derivation(None, Some(tenant(2)), "ibe").unwrap();
+        assert!(!r.has_derived(&KeyScope::Purpose("ibe".into())));
+
+        r.resolve_for_derivation(None, None, "ibe").unwrap();
+        assert!(r.has_derived(&KeyScope::Purpose("ibe".into())));
+    }
+
+    #[test]
+    fn scopes_that_derived_are_only_assigned_when_forced() {
+        let mut r = registry();
+        r.add("test".into(), test_key()).unwrap();
+        r.resolve_for_derivation(None, Some(tenant(1)), "db").unwrap();
+        let db = KeyScope::Purpose("db".into());
+
+        assert_eq!(r.assign(db.clone(), "test".into(), false), Err(KeyError::ScopeHasDerived));
+        assert_eq!(r.resolve(None, Some(tenant(1)), "db").unwrap().name, "key_1");
+        r.assign(KeyScope::Tenant(tenant(2)), "test".into(), false).unwrap();
+        r.assign(db, "test".into(), true).unwrap();
+        assert_eq!(r.resolve(None, Some(tenant(1)), "db").unwrap().name, "test_key_1");
+    }
+}
@@ -0,0 +1,32 @@
+//! Code shared by the Dooor VetKD canisters (`vetkeys`, `vetkeys_demo`).
+//!
+//! Each module owns the state and rules of one subsystem over caller-supplied
+//! stable memory. The canisters keep their `thread_local!` wiring, endpoints,
+//! access control and audit events.
+
+pub mod keys;
+pub mod metering;
+
+#[doc(hidden)]
+pub use candid;
+#[doc(hidden)]
+pub use ic_stable_structures;
+
+/// Implements `Storable` for a Candid-serializable type (unbounded).
+#[macro_export]
+macro_rules! candid_storable {
+    ($t:ty) => {
+        impl $crate::ic_stable_structures::Storable for $t {
+            fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+                $crate::candid::encode_one(self)
+                    .expect("candid encoding of stable value failed")
+                    .into()
+            }
+            fn from_bytes(b: std::borrow::Cow<[u8]>) -> Self {
+                $crate::candid::decode_one(&b).expect("candid decoding of stable value failed")
+            }
+            const BOUND: $crate::ic_stable_structures::storable::Bound =
+                $crate::ic_stable_structures::storable::Bound::Unbounded;
+        }
+    };
+}
@@ -0,0 +1,269 @@
+//! Prepaid per-principal cycle balances for VetKD derivations.
+//!
+//! A [`Meter`] holds each principal's balance and the [`MeteringConfig`].
+//! While metering is enabled, a derivation is charged the management
+//! canister's current price for its key plus `margin_percent`; controllers are
+//! never charged. The canister defines its metered operations (`Op`) and
+//! decides when to charge, refund and audit.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::management_canister::{VetKDCurve, VetKDKeyId};
+use ic_stable_structures::{Memory, StableBTreeMap, StableCell};
+use std::fmt;
+
+/// Largest accepted `margin_percent`.
+pub const MAX_MARGIN_PERCENT: u32 = 1_000;
+
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct MeteringConfig {
+    pub enabled: bool,
+    /// Added on top of the management canister's price, in percent.
+    pub margin_percent: u32,
+}
+
+crate::candid_storable!(MeteringConfig);
+
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        MeteringConfig { enabled: false, margin_percent: 20 }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct CostEstimate<Op> {
+    pub op: Op,
+    pub key_name: String,
+    /// Price of one `vetkd_derive_key` call for the key.
+    pub vetkd_cycles: u128,
+    pub margin_percent: u32,
+    /// What the caller is charged: `vetkd_cycles` plus the margin, or 0 if
+    /// the caller is not metered.
+    pub cycles: u128,
+    /// The caller's current balance.
+    pub balance: u128,
+}
+
+/// Why a metered call was refused. Metered endpoints return it as the `Err`
+/// of their `Result`.
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub enum MeteringError<Op> {
+    InsufficientCycles { op: Op, required: u128, available: u128 },
+}
+
+impl<Op: fmt::Debug> fmt::Display for MeteringError<Op> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // For logs; metered endpoints return the variant itself.
+            MeteringError::InsufficientCycles { op, required, available } => write!(
+                f,
+                "insufficient_cycles: op={op:?} required={required} available={available}; \
+                 top up with `top_up`"
+            ),
+        }
+    }
+}
+
+/// Why a new [`MeteringConfig`] was refused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    MarginTooHigh,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MarginTooHigh => {
+                write!(f, "margin_percent must be at most {MAX_MARGIN_PERCENT}")
+            }
+        }
+    }
+}
+
+/// `cycles` plus `margin_percent` percent, saturating.
+pub fn with_margin(cycles: u128, margin_percent: u32) -> u128 {
+    cycles.saturating_add(cycles.saturating_mul(margin_percent as u128) / 100)
+}
+
+/// Management canister price of one derivation with `key_id`. Traps if the
+/// key cannot be priced.
+pub fn vetkd_cost(key_id: &VetKDKeyId) -> u128 {
+    let curve = match key_id.curve {
+        VetKDCurve::Bls12_381_G2 => 0,
+    };
+    ic_cdk::api::cost_vetkd_derive_key(&key_id.name, curve)
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("cannot price key `{}`: {e:?}", key_id.name)))
+}
+
+/// Cycle balances and metering config of one canister.
+pub struct Meter<M: Memory> {
+    /// principal bytes -> balance
+    balances: StableBTreeMap<Vec<u8>, u128, M>,
+    config: StableCell<MeteringConfig, M>,
+}
+
+impl<M: Memory> Meter<M> {
+    /// Opens the balances and config stored in `balances` and `config`. A
+    /// config region that was never written starts disabled.
+    pub fn init(balances: M, config: M) -> Self {
+        Meter {
+            balances: StableBTreeMap::init(balances),
+            config: StableCell::init(config, MeteringConfig::default())
+                .expect("failed to init metering config cell"),
+        }
+    }
+
+    pub fn config(&self) -> MeteringConfig {
+        self.config.get().clone()
+    }
+
+    /// Replaces the config, refusing a margin above [`MAX_MARGIN_PERCENT`].
+    pub fn set_config(&mut self, config: MeteringConfig) -> Result<(), ConfigError> {
+        if config.margin_percent > MAX_MARGIN_PERCENT {
+            return Err(ConfigError::MarginTooHigh);
+        }
+        self.config.set(config).expect("failed to persist metering config");
+        Ok(())
+    }
+
+    pub fn balance_of(&self, p: Principal) -> u128 {
+        self.balances.get(&p.as_slice().to_vec()).unwrap_or(0)
+    }
+
+    /// Adds `amount` to the balance of `p` and returns the new balance.
+    pub fn credit(&mut self, p: Principal, amount: u128) -> u128 {
+        let balance = self.balance_of(p).saturating_add(amount);
+        self.balances.insert(p.as_slice().to_vec(), balance);
+        balance
+    }
+
+    /// Takes `required` from the balance of `payer`, or leaves it untouched
+    /// if it is too low.
+    pub fn debit<Op>(
+        &mut self,
+        payer: Principal,
+        op: Op,
+        required: u128,
+    ) -> Result<(), MeteringError<Op>> {
+        let available = self.balance_of(payer);
+        if available < required {
+            return Err(MeteringError::InsufficientCycles { op, required, available });
+        }
+        self.balances.insert(payer.as_slice().to_vec(), available - required);
+        Ok(())
+    }
+
+    fn is_metered(&self, p: Principal) -> bool {
+        self.config.get().enabled && !ic_cdk::api::is_controller(&p)
+    }
+
+    /// What `payer` is charged for one derivation with `key_id`: the price
+    /// plus the margin, or 0 for unmetered callers.
+    pub fn price(&self, payer: Principal, key_id: &VetKDKeyId) -> u128 {
+        match self.is_metered(payer) {
+            true => with_margin(vetkd_cost(key_id), self.config.get().margin_percent),
+            false => 0,
+        }
+    }
+
+    /// Debits `payer` for one `op` derivation with `key_id`. Returns the
+    /// amount debited, which is 0 for unmetered callers.
+    pub fn charge<Op>(
+        &mut self,
+        payer: Principal,
+        op: Op,
+        key_id: &VetKDKeyId,
+    ) -> Result<u128, MeteringError<Op>> {
+        let required = self.price(payer, key_id);
+        if required > 0 {
+            self.debit(payer, op, required)?;
+        }
+        Ok(required)
+    }
+
+    /// What one `op` derivation with `key_id` would cost `caller` now.
+    pub fn estimate<Op>(&self, caller: Principal, op: Op, key_id: VetKDKeyId) -> CostEstimate<Op> {
+        let margin_percent = self.config.get().margin_percent;
+        let vetkd_cycles = vetkd_cost(&key_id);
+        let cycles = match self.is_metered(caller) {
+            true => with_margin(vetkd_cycles, margin_percent),
+            false => 0,
+        };
+        CostEstimate {
+            op,
+            key_name: key_id.name,
+            vetkd_cycles,
+            margin_percent,
+            cycles,
+            balance: self.balance_of(caller),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    fn meter() -> Meter<VirtualMemory<DefaultMemoryImpl>> {
+        let mm = MemoryManager::init(DefaultMemoryImpl::default());
+        Meter::init(mm.get(MemoryId::new(0)), mm.get(MemoryId::new(1)))
+    }
+
+    fn alice() -> Principal {
+        Principal::from_slice(&[1; 29])
+    }
+
+    #[test]
+    fn margin_is_added_and_saturates() {
+        assert_eq!(with_margin(1_000, 0), 1_000);
+        assert_eq!(with_margin(1_000, 20), 1_200);
+        assert_eq!(with_margin(u128::MAX, 20), u128::MAX);
+        assert_eq!(with_margin(u128::MAX / 2, MAX_MARGIN_PERCENT), u128::MAX);
+    }
+
+    #[test]
+    fn starts_disabled_with_default_margin() {
+        assert_eq!(meter().config(), MeteringConfig { enabled: false, margin_percent: 20 });
+    }
+
+    #[test]
+    fn rejects_a_margin_above_the_maximum() {
+        let mut m = meter();
+        let high = MeteringConfig { enabled: true, margin_percent: MAX_MARGIN_PERCENT + 1 };
+        assert_eq!(m.set_config(high), Err(ConfigError::MarginTooHigh));
+        let max = MeteringConfig { enabled: true, margin_percent: MAX_MARGIN_PERCENT };
+        m.set_config(max.clone()).unwrap();
+        assert_eq!(m.config(), max);
+    }
+
+    #[test]
+    fn credits_saturate() {
+        let mut m = meter();
+        assert_eq!(m.balance_of(alice()), 0);
+        assert_eq!(m.credit(alice(), 100), 100);
+        assert_eq!(m.credit(alice(), u128::MAX), u128::MAX);
+    }
+
+    #[test]
+    fn debit_refuses_an_insufficient_balance_and_keeps_it() {
+        let mut m = meter();
+        m.credit(alice(), 100);
+        assert_eq!(
+            m.debit(alice(), "op", 101),
+            Err(MeteringError::InsufficientCycles { op: "op", required: 101, available: 100 })
+        );
+        assert_eq!(m.balance_of(alice()), 100);
+        m.debit(alice(), "op", 100).unwrap();
+        assert_eq!(m.balance_of(alice()), 0);
+    }
+
+    #[test]
+    fn insufficient_cycles_message_is_stable() {
+        let err = MeteringError::InsufficientCycles { op: "op", required: 2, available: 1 };
+        assert_eq!(
+            err.to_string(),
+            "insufficient_cycles: op=\"op\" required=2 available=1; top up with `top_up`"
+        );
+    }
+}
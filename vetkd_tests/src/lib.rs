@@ -0,0 +1,196 @@
+//! PocketIC harness for the VetKD canister integration tests.
+//!
+//! Each test boots a fresh PocketIC instance with an application subnet and an
+//! II subnet (which hosts the `key_1` / `test_key_1` VetKD test keys), installs
+//! one canister and drives it through Candid calls.
+//!
+//! Canister wasm files come from `VETKEYS_WASM` / `VETKEYS_DEMO_WASM` if set,
+//! otherwise they are built once per test binary with
+//! `cargo build --target wasm32-unknown-unknown --release`. The PocketIC server
+//! is taken from `POCKET_IC_BIN`, which must point at a local binary: without
+//! it the `pocket-ic` crate would download the server, so tests fail instead.
+
+use candid::{decode_one, encode_args, utils::ArgumentEncoder, CandidType, Principal};
+use pocket_ic::{PocketIc, PocketIcBuilder, RejectResponse};
+use serde::de::DeserializeOwned;
+use std::{path::PathBuf, process::Command, sync::OnceLock, time::Duration};
+
+/// Cycles given to every installed canister.
+const INIT_CYCLES: u128 = 100_000_000_000_000;
+
+/// Cycles that `Env::fund` grants, enough for many derivations.
+const FUND_CYCLES: u128 = 1_000_000_000_000;
+
+/// Canisters under test.
+#[derive(Clone, Copy)]
+pub enum Canister {
+    Vetkeys,
+    VetkeysDemo,
+}
+
+impl Canister {
+    fn package(self) -> &'static str {
+        match self {
+            Canister::Vetkeys => "vetkeys",
+            Canister::VetkeysDemo => "vetkeys_demo",
+        }
+    }
+
+    fn wasm_env(self) -> &'static str {
+        match self {
+            Canister::Vetkeys => "VETKEYS_WASM",
+            Canister::VetkeysDemo => "VETKEYS_DEMO_WASM",
+        }
+    }
+
+    fn cache(self) -> &'static OnceLock<Vec<u8>> {
+        static VETKEYS: OnceLock<Vec<u8>> = OnceLock::new();
+        static VETKEYS_DEMO: OnceLock<Vec<u8>> = OnceLock::new();
+        match self {
+            Canister::Vetkeys => &VETKEYS,
+            Canister::VetkeysDemo => &VETKEYS_DEMO,
+        }
+    }
+}
+
+/// Returns the canister's wasm module, building it on first use.
+pub fn wasm(canister: Canister) -> Vec<u8> {
+    canister
+        .cache()
+        .get_or_init(|| {
+            let path = match std::env::var(canister.wasm_env()) {
+                Ok(path) => PathBuf::from(path),
+                Err(_) => build(canister),
+            };
+            std::fs::read(&path)
+                .unwrap_or_else(|e| panic!("cannot read wasm {}: {e}", path.display()))
+        })
+        .clone()
+}
+
+fn build(canister: Canister) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let target_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/canisters");
+    let manifest = root.join(canister.package()).join("Cargo.toml");
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .args(["build", "--release", "--target", "wasm32-unknown-unknown"])
+        .arg("--manifest-path")
+        .arg(&manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "building {} failed", canister.package());
+    target_dir
+        .join("wasm32-unknown-unknown/release")
+        .join(format!("{}.wasm", canister.package()))
+}
+
+/// Panics unless `POCKET_IC_BIN` names an existing PocketIC server binary.
+fn require_server() {
+    let path = std::env::var_os("POCKET_IC_BIN").unwrap_or_else(|| {
+        panic!("POCKET_IC_BIN is not set; point it at a local pocket-ic server binary")
+    });
+    let path = PathBuf::from(path);
+    assert!(path.is_file(), "POCKET_IC_BIN={} is not a file", path.display());
+}
+
+/// Deterministic test principal `n`.
+pub fn user(n: u8) -> Principal {
+    Principal::self_authenticating([n])
+}
+
+/// One PocketIC instance with one installed canister.
+pub struct Env {
+    pub pic: PocketIc,
+    pub canister_id: Principal,
+    pub controller: Principal,
+    canister: Canister,
+}
+
+impl Env {
+    pub fn new(canister: Canister) -> Self {
+        require_server();
+        let pic = PocketIcBuilder::new()
+            .with_application_subnet()
+            .with_ii_subnet()
+            .build();
+        let controller = user(0);
+        let canister_id = pic.create_canister_with_settings(Some(controller), None);
+        pic.add_cycles(canister_id, INIT_CYCLES);
+        let init_arg = encode_args(()).unwrap();
+        pic.install_canister(canister_id, wasm(canister), init_arg, Some(controller));
+        Env { pic, canister_id, controller, canister }
+    }
+
+    /// Makes an update call as `sender` and decodes the single return value.
+    pub fn update<R: CandidType + DeserializeOwned>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, RejectResponse> {
+        let payload = encode_args(args).expect("failed to encode args");
+        self.pic
+            .update_call(self.canister_id, sender, method, payload)
+            .map(|bytes| decode_one(&bytes).expect("failed to decode reply"))
+    }
+
+    /// Makes a query call as `sender` and decodes the single return value.
+    pub fn query<R: CandidType + DeserializeOwned>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R, RejectResponse> {
+        let payload = encode_args(args).expect("failed to encode args");
+        self.pic
+            .query_call(self.canister_id, sender, method, payload)
+            .map(|bytes| decode_one(&bytes).expect("failed to decode reply"))
+    }
+
+    /// Reinstalls the same wasm with `post_upgrade`, keeping stable memory.
+    pub fn upgrade(&self) {
+        self.pic
+            .upgrade_canister(
+                self.canister_id,
+                wasm(self.canister),
+                encode_args(()).unwrap(),
+                Some(self.controller),
+            )
+            .expect("upgrade failed");
+    }
+
+    /// Grants `principal` prepaid cycles for metered derivations.
+    pub fn fund(&self, principal: Principal) {
+        self.update::<u128>(self.controller, "grant_cycles", (principal, FUND_CYCLES))
+            .expect("grant_cycles failed");
+    }
+
+    /// Current IC time in nanoseconds.
+    pub fn now(&self) -> u64 {
+        self.pic.get_time().as_nanos_since_unix_epoch()
+    }
+
+    /// Advances time by `d` and executes enough rounds for timers (and the
+    /// VetKD calls they make) to complete.
+    pub fn advance(&self, d: Duration) {
+        self.pic.advance_time(d);
+        for _ in 0..20 {
+            self.pic.tick();
+        }
+    }
+}
+
+/// Asserts that `res` was rejected with a message containing `needle`.
+#[track_caller]
+pub fn assert_rejected<T>(res: Result<T, RejectResponse>, needle: &str) {
+    match res {
+        Ok(_) => panic!("expected a rejection containing `{needle}`"),
+        Err(e) => assert!(
+            e.reject_message.contains(needle),
+            "rejection `{}` does not contain `{needle}`",
+            e.reject_message
+        ),
+    }
+}
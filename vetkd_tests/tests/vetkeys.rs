@@ -0,0 +1,412 @@
+//! Integration tests for the `vetkeys` private DB canister.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey, TransportSecretKey, VetKey};
+use std::time::Duration;
+use vetkd_tests::{assert_rejected, user, Canister, Env};
+
+#[derive(CandidType, Deserialize)]
+struct BlsPk {
+    pk: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct EncryptedKey {
+    encrypted_key: Vec<u8>,
+}
+
+/// Reply of the metered `derive_*` endpoints.
+type KeyReply = Result<EncryptedKey, MeteringError>;
+
+#[allow(non_camel_case_types)]
+#[derive(CandidType, Deserialize)]
+enum KeyCurve {
+    Bls12_381_G2,
+}
+
+#[derive(CandidType, Deserialize)]
+struct KeyConfig {
+    name: String,
+    curve: KeyCurve,
+}
+
+#[derive(CandidType, Deserialize)]
+enum KeyScope {
+    Tenant(Principal),
+    Purpose(String),
+}
+
+#[derive(CandidType, Deserialize)]
+struct RegionStats {
+    id: u8,
+    name: String,
+    owner: String,
+    retired: bool,
+    pages: u64,
+    bytes: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum RecoveryStatus {
+    Open,
+    Approved,
+    Expired,
+    Cancelled,
+}
+
+// Mirrors the canister's Candid type; not every variant is used here.
+#[allow(dead_code)]
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum MeteredOp {
+    DeriveDataKey,
+    DeriveRecoveryKey,
+    DeriveIbeKey,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum MeteringError {
+    InsufficientCycles { op: MeteredOp, required: u128, available: u128 },
+}
+
+// Mirrors the canister's Candid type; not every variant is used here.
+#[allow(dead_code)]
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum AuditKind {
+    NodeRegistered,
+    NodeAttested,
+    NodeStatusChanged,
+    RecoveryOpened,
+    RecoveryApproved,
+    RecoveryStatusChanged,
+    RecoveryKeyDerived,
+    KeyRegistryChanged,
+    MeteringChanged,
+    NodeConfigChanged,
+}
+
+#[derive(CandidType, Deserialize)]
+struct AuditEvent {
+    actor: Principal,
+    kind: AuditKind,
+    subject: String,
+    detail: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct MeteringConfig {
+    enabled: bool,
+    margin_percent: u32,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CostEstimate {
+    vetkd_cycles: u128,
+    margin_percent: u32,
+    cycles: u128,
+    balance: u128,
+}
+
+const NO_KEY: Option<String> = None;
+
+fn transport_key(seed: u8) -> TransportSecretKey {
+    TransportSecretKey::from_seed(vec![seed; 32]).unwrap()
+}
+
+fn data_key_input(record_id: &[u8]) -> Vec<u8> {
+    [b"db|v1|".as_slice(), record_id].concat()
+}
+
+/// Derives `record_id`'s data key as `caller` (after funding it) and decrypts it.
+fn data_key(env: &Env, caller: Principal, record_id: &[u8], tsk_seed: u8) -> VetKey {
+    env.fund(caller);
+    let tsk = transport_key(tsk_seed);
+    let args = (record_id.to_vec(), tsk.public_key(), NO_KEY);
+    let ek = env.update::<KeyReply>(caller, "derive_data_key", args).unwrap().unwrap();
+    let pk: BlsPk = env.update(caller, "bls_public_key", (NO_KEY,)).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    EncryptedVetKey::deserialize(&ek.encrypted_key)
+        .unwrap()
+        .decrypt_and_verify(&tsk, &dpk, &data_key_input(record_id))
+        .expect("derived key does not verify")
+}
+
+#[test]
+fn data_key_round_trip() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+
+    let k1 = data_key(&env, alice, b"rec-1", 1);
+    // Same record, different transport key: same data key.
+    let k2 = data_key(&env, alice, b"rec-1", 2);
+    assert_eq!(k1.signature_bytes(), k2.signature_bytes());
+
+    // Different record or caller: different data key.
+    assert_ne!(k1.signature_bytes(), data_key(&env, alice, b"rec-2", 1).signature_bytes());
+    assert_ne!(k1.signature_bytes(), data_key(&env, user(2), b"rec-1", 1).signature_bytes());
+}
+
+#[test]
+fn data_key_does_not_verify_for_other_record() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+    env.fund(alice);
+    let tsk = transport_key(1);
+    let args = (b"rec-1".to_vec(), tsk.public_key(), NO_KEY);
+    let ek = env.update::<KeyReply>(alice, "derive_data_key", args).unwrap().unwrap();
+    let pk: BlsPk = env.update(alice, "bls_public_key", (NO_KEY,)).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    let res = EncryptedVetKey::deserialize(&ek.encrypted_key)
+        .unwrap()
+        .decrypt_and_verify(&tsk, &dpk, &data_key_input(b"rec-2"));
+    assert!(res.is_err());
+}
+
+#[test]
+fn rejects_malformed_transport_key() {
+    let env = Env::new(Canister::Vetkeys);
+    let res: Result<KeyReply, _> =
+        env.update(user(1), "derive_data_key", (b"rec".to_vec(), vec![0u8; 32], NO_KEY));
+    assert_rejected(res, "transport_public_key must be 48 bytes");
+}
+
+#[test]
+fn records_are_scoped_to_the_caller() {
+    let env = Env::new(Canister::Vetkeys);
+    let (alice, bob) = (user(1), user(2));
+    let rid = b"rec".to_vec();
+
+    env.update::<()>(alice, "put_record", (rid.clone(), b"envelope".to_vec())).unwrap();
+
+    let theirs: Option<Vec<u8>> = env.query(bob, "get_record", (rid.clone(),)).unwrap();
+    assert_eq!(theirs, None);
+    let ids: Vec<Vec<u8>> = env.query(bob, "list_record_ids", ()).unwrap();
+    assert!(ids.is_empty());
+    let deleted: bool = env.update(bob, "delete_record", (rid.clone(),)).unwrap();
+    assert!(!deleted);
+
+    let mine: Option<Vec<u8>> = env.query(alice, "get_record", (rid.clone(),)).unwrap();
+    assert_eq!(mine, Some(b"envelope".to_vec()));
+    let deleted: bool = env.update(alice, "delete_record", (rid.clone(),)).unwrap();
+    assert!(deleted);
+    let mine: Option<Vec<u8>> = env.query(alice, "get_record", (rid,)).unwrap();
+    assert_eq!(mine, None);
+}
+
+#[test]
+fn upgrade_preserves_records_and_keys() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+    let before = data_key(&env, alice, b"rec", 1);
+    env.update::<()>(alice, "put_record", (b"rec".to_vec(), b"envelope".to_vec())).unwrap();
+
+    env.upgrade();
+
+    let stored: Option<Vec<u8>> = env.query(alice, "get_record", (b"rec".to_vec(),)).unwrap();
+    assert_eq!(stored, Some(b"envelope".to_vec()));
+    let ids: Vec<Vec<u8>> = env.query(alice, "list_record_ids", ()).unwrap();
+    assert_eq!(ids, vec![b"rec".to_vec()]);
+    assert_eq!(before.signature_bytes(), data_key(&env, alice, b"rec", 2).signature_bytes());
+}
+
+#[test]
+fn memory_stats_reports_layout() {
+    let env = Env::new(Canister::Vetkeys);
+    env.update::<()>(user(1), "put_record", (b"rec".to_vec(), b"envelope".to_vec())).unwrap();
+    env.upgrade();
+
+    let res: Result<Vec<RegionStats>, _> = env.query(user(1), "memory_stats", ());
+    assert_rejected(res, "caller is not a controller");
+    let stats: Vec<RegionStats> = env.query(env.controller, "memory_stats", ()).unwrap();
+    let ids: Vec<u8> = stats.iter().map(|r| r.id).collect();
+    assert_eq!(ids, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 254]);
+    let db = &stats[0];
+    assert_eq!((db.name.as_str(), db.owner.as_str(), db.retired), ("db.records", "lib", false));
+    assert!(db.pages > 0);
+    assert_eq!(db.bytes, db.pages * 65536);
+    assert_eq!(stats[11].name, "layout.manifest");
+}
+
+#[test]
+fn metering_charges_prepaid_cycles() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+    let config: MeteringConfig = env.query(alice, "get_metering_config", ()).unwrap();
+    assert!(config.enabled, "metering is enabled on install");
+    let config = MeteringConfig { enabled: true, margin_percent: 50 };
+    let res: Result<(), _> = env.update(alice, "set_metering_config", (&config,));
+    assert_rejected(res, "caller is not a controller");
+    env.update::<()>(env.controller, "set_metering_config", (&config,)).unwrap();
+
+    let est: CostEstimate =
+        env.query(alice, "get_cost_estimate", (MeteredOp::DeriveDataKey, NO_KEY)).unwrap();
+    assert!(est.vetkd_cycles > 0);
+    assert_eq!(est.margin_percent, 50);
+    assert_eq!(est.cycles, est.vetkd_cycles + est.vetkd_cycles / 2);
+    assert_eq!(est.balance, 0);
+
+    let args = (b"rec".to_vec(), transport_key(1).public_key(), NO_KEY);
+    let derive = |caller| env.update::<KeyReply>(caller, "derive_data_key", args.clone()).unwrap();
+    let refused = |available| MeteringError::InsufficientCycles {
+        op: MeteredOp::DeriveDataKey,
+        required: est.cycles,
+        available,
+    };
+    assert_eq!(derive(alice).unwrap_err(), refused(0));
+
+    // Controllers are never charged.
+    derive(env.controller).unwrap();
+
+    env.update::<u128>(env.controller, "grant_cycles", (alice, est.cycles - 1)).unwrap();
+    assert_eq!(derive(alice).unwrap_err(), refused(est.cycles - 1));
+    env.update::<u128>(env.controller, "grant_cycles", (alice, 1u128)).unwrap();
+    derive(alice).unwrap();
+    let balance: u128 = env.query(alice, "cycles_balance", ()).unwrap();
+    assert_eq!(balance, 0);
+    assert_eq!(derive(alice).unwrap_err(), refused(0));
+}
+
+#[test]
+fn node_admin_is_controller_only() {
+    let env = Env::new(Canister::Vetkeys);
+    let res: Result<(), _> = env.update(user(1), "register_node", ("tee-1", user(9)));
+    assert_rejected(res, "caller is not a controller");
+    let res: Result<(), _> = env.update(user(1), "set_attestation_interval", (60u64,));
+    assert_rejected(res, "caller is not a controller");
+}
+
+#[test]
+fn attestation_interval_changes_are_audited() {
+    let env = Env::new(Canister::Vetkeys);
+    env.update::<()>(env.controller, "set_attestation_interval", (3_600u64,)).unwrap();
+    env.update::<()>(env.controller, "set_attestation_interval", (60u64,)).unwrap();
+
+    let log: Vec<AuditEvent> = env.query(env.controller, "get_audit_log", (0u64, 10u64)).unwrap();
+    let changes: Vec<_> = log
+        .iter()
+        .filter(|e| e.kind == AuditKind::NodeConfigChanged)
+        .map(|e| (e.actor, e.subject.as_str(), e.detail.as_str()))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (env.controller, "attestation_interval", "86400s -> 3600s"),
+            (env.controller, "attestation_interval", "3600s -> 60s"),
+        ]
+    );
+}
+
+#[test]
+fn overdue_node_is_quarantined() {
+    let env = Env::new(Canister::Vetkeys);
+    let node = user(9);
+    env.update::<()>(env.controller, "register_node", ("tee-1", node)).unwrap();
+    env.update::<()>(node, "submit_attestation", (b"quote".to_vec(),)).unwrap();
+    env.update::<()>(node, "put_record", (b"rec".to_vec(), b"env".to_vec())).unwrap();
+
+    env.advance(Duration::from_secs(25 * 60 * 60));
+
+    let res: Result<(), _> = env.update(node, "put_record", (b"rec".to_vec(), b"env".to_vec()));
+    assert_rejected(res, "Quarantined");
+
+    // Fresh evidence reactivates the node.
+    env.update::<()>(node, "submit_attestation", (b"quote".to_vec(),)).unwrap();
+    env.update::<()>(node, "put_record", (b"rec".to_vec(), b"env".to_vec())).unwrap();
+}
+
+#[test]
+fn approved_replacement_opens_failed_node_records() {
+    let env = Env::new(Canister::Vetkeys);
+    let (failed, replacement, approver) = (user(9), user(10), user(11));
+    for (id, node) in [("tee-1", failed), ("tee-2", replacement), ("tee-3", approver)] {
+        env.update::<()>(env.controller, "register_node", (id, node)).unwrap();
+        env.update::<()>(node, "submit_attestation", (b"quote".to_vec(),)).unwrap();
+    }
+    let sealed = data_key(&env, failed, b"rec", 1);
+    env.update::<()>(failed, "put_record", (b"rec".to_vec(), b"env".to_vec())).unwrap();
+    let failed_pk: BlsPk = env.update(failed, "bls_public_key", (NO_KEY,)).unwrap();
+
+    // Every node is swept; only the survivors re-attest.
+    env.advance(Duration::from_secs(25 * 60 * 60));
+    for node in [replacement, approver] {
+        env.update::<()>(node, "submit_attestation", (b"quote".to_vec(),)).unwrap();
+    }
+
+    let id: u64 = env.update(replacement, "open_recovery", (failed,)).unwrap();
+    env.fund(replacement);
+    let tsk = transport_key(2);
+    let derive = || {
+        env.update::<KeyReply>(
+            replacement,
+            "derive_recovery_key",
+            (failed, Some(id), b"rec".to_vec(), tsk.public_key(), NO_KEY),
+        )
+    };
+    assert_rejected(derive(), "Open");
+    let status: RecoveryStatus = env.update(approver, "approve_recovery", (id,)).unwrap();
+    assert_eq!(status, RecoveryStatus::Open);
+    let status: RecoveryStatus = env.update(env.controller, "approve_recovery", (id,)).unwrap();
+    assert_eq!(status, RecoveryStatus::Approved);
+
+    let ids: Vec<Vec<u8>> =
+        env.query(replacement, "list_recovered_record_ids", (id,)).unwrap();
+    assert_eq!(ids, vec![b"rec".to_vec()]);
+    let envelope: Option<Vec<u8>> =
+        env.query(replacement, "get_recovered_record", (id, b"rec".to_vec())).unwrap();
+    assert_eq!(envelope, Some(b"env".to_vec()));
+
+    // The recovered key is the one the failed node sealed the record with.
+    let recovered = EncryptedVetKey::deserialize(&derive().unwrap().unwrap().encrypted_key)
+        .unwrap()
+        .decrypt_and_verify(
+            &tsk,
+            &DerivedPublicKey::deserialize(&failed_pk.pk).unwrap(),
+            &data_key_input(b"rec"),
+        )
+        .expect("recovered key does not verify");
+    assert_eq!(recovered.signature_bytes(), sealed.signature_bytes());
+
+    let res: Result<KeyReply, _> = env.update(
+        approver,
+        "derive_recovery_key",
+        (failed, Some(id), b"rec".to_vec(), tsk.public_key(), NO_KEY),
+    );
+    assert_rejected(res, "caller is not the replacement node");
+}
+
+#[test]
+fn key_selector_switches_key() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+    let config = KeyConfig { name: "test_key_1".into(), curve: KeyCurve::Bls12_381_G2 };
+    let res: Result<(), _> = env.update(alice, "add_key", ("test", &config));
+    assert_rejected(res, "caller is not a controller");
+    env.update::<()>(env.controller, "add_key", ("test", &config)).unwrap();
+
+    let default: BlsPk = env.update(alice, "bls_public_key", (NO_KEY,)).unwrap();
+    let test: BlsPk = env.update(alice, "bls_public_key", (Some("test"),)).unwrap();
+    assert_ne!(default.pk, test.pk);
+
+    let res: Result<BlsPk, _> = env.update(alice, "bls_public_key", (Some("nope"),));
+    assert_rejected(res, "unknown key alias");
+}
+
+#[test]
+fn assign_key_refuses_scopes_that_derived() {
+    let env = Env::new(Canister::Vetkeys);
+    let alice = user(1);
+    let config = KeyConfig { name: "test_key_1".into(), curve: KeyCurve::Bls12_381_G2 };
+    env.update::<()>(env.controller, "add_key", ("test", &config)).unwrap();
+    let before = data_key(&env, alice, b"rec", 1);
+    let assign = |scope: KeyScope, force: Option<bool>| -> Result<(), _> {
+        env.update(env.controller, "assign_key", (scope, "test", force))
+    };
+
+    assert_rejected(assign(KeyScope::Tenant(alice), None), "scope has already derived keys");
+    let res = assign(KeyScope::Purpose("db".into()), Some(false));
+    assert_rejected(res, "scope has already derived keys");
+    assign(KeyScope::Purpose("ibe".into()), None).unwrap();
+    assert_eq!(data_key(&env, alice, b"rec", 1).signature_bytes(), before.signature_bytes());
+
+    assign(KeyScope::Tenant(alice), Some(true)).unwrap();
+    assert_ne!(data_key(&env, alice, b"rec", 1).signature_bytes(), before.signature_bytes());
+}
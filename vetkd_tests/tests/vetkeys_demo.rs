@@ -0,0 +1,424 @@
+//! Integration tests for the `vetkeys_demo` BLS signing canister.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_vetkeys::{
+    verify_bls_signature, DerivedPublicKey, EncryptedVetKey, IbeCiphertext, IbeIdentity,
+    IbeSeed, TransportSecretKey, VetKey,
+};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use vetkd_tests::{assert_rejected, user, Canister, Env};
+
+#[derive(CandidType, Deserialize)]
+struct BlsPk {
+    pk: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct BlsSig {
+    signature: Vec<u8>,
+}
+
+// Mirrors the canister's Candid type; not every variant is used here.
+#[allow(dead_code)]
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum MeteredOp {
+    SignCaller,
+    SignRequest,
+    SignAsCanister,
+    CertificateShare,
+    Beacon,
+    Timelock,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum MeteringError {
+    InsufficientCycles { op: MeteredOp, required: u128, available: u128 },
+}
+
+/// Reply of the metered signing endpoints.
+type SignReply = Result<BlsSig, MeteringError>;
+/// Reply of `beacon`.
+type BeaconReply = Result<BeaconEntry, MeteringError>;
+/// Reply of `register_timelock`.
+type TimelockReply = Result<Vec<u8>, MeteringError>;
+
+// Mirrors the canister's Candid type; not every variant is used here.
+#[allow(dead_code)]
+#[derive(CandidType, Deserialize, Clone)]
+enum SignPurpose {
+    Jwt,
+    Manifest,
+    Deployment,
+    Custom(String),
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum AuditKind {
+    KeyRegistryChanged,
+    MeteringChanged,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(CandidType, Deserialize)]
+enum KeyCurve {
+    Bls12_381_G2,
+}
+
+#[derive(CandidType, Deserialize)]
+struct KeyConfig {
+    name: String,
+    curve: KeyCurve,
+}
+
+#[derive(CandidType, Deserialize)]
+enum KeyScope {
+    Tenant(Principal),
+    Purpose(String),
+}
+
+#[derive(CandidType, Deserialize)]
+struct AuditEvent {
+    actor: Principal,
+    kind: AuditKind,
+    subject: String,
+    detail: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct BeaconEntry {
+    epoch: u64,
+    purpose: String,
+    input: Vec<u8>,
+    signature: Vec<u8>,
+    randomness: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct SignRequest {
+    purpose: SignPurpose,
+    nonce: Vec<u8>,
+    expires_at: u64,
+    payload: Vec<u8>,
+}
+
+const NO_KEY: Option<String> = None;
+const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+
+fn transport_key(seed: u8) -> TransportSecretKey {
+    TransportSecretKey::from_seed(vec![seed; 32]).unwrap()
+}
+
+fn push_lp(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Client-side copy of the documented `sign_request` input encoding.
+fn encode_input(signer: Principal, req: &SignRequest) -> Vec<u8> {
+    let purpose = match &req.purpose {
+        SignPurpose::Jwt => b"jwt".to_vec(),
+        SignPurpose::Manifest => b"manifest".to_vec(),
+        SignPurpose::Deployment => b"deployment".to_vec(),
+        SignPurpose::Custom(name) => [b"custom:".as_slice(), name.as_bytes()].concat(),
+    };
+    let mut out = b"dooor-sign-request-v1".to_vec();
+    push_lp(&mut out, signer.as_slice());
+    push_lp(&mut out, &purpose);
+    push_lp(&mut out, &req.nonce);
+    out.extend_from_slice(&req.expires_at.to_be_bytes());
+    push_lp(&mut out, &req.payload);
+    out
+}
+
+fn request(env: &Env, nonce: &[u8]) -> SignRequest {
+    SignRequest {
+        purpose: SignPurpose::Deployment,
+        nonce: nonce.to_vec(),
+        expires_at: env.now() + HOUR_NS,
+        payload: b"deploy tee-7".to_vec(),
+    }
+}
+
+/// Signs `req` as `signer` (after funding it) and decrypts the signature locally.
+fn sign(env: &Env, signer: Principal, req: &SignRequest) -> Vec<u8> {
+    env.fund(signer);
+    let tsk = transport_key(1);
+    let args = (req, tsk.public_key(), NO_KEY);
+    let sig = env.update::<SignReply>(signer, "sign_request", args).unwrap().unwrap();
+    let pk: BlsPk = env.update(signer, "sign_request_public_key", (signer, NO_KEY)).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    EncryptedVetKey::deserialize(&sig.signature)
+        .unwrap()
+        .decrypt_and_verify(&tsk, &dpk, &encode_input(signer, req))
+        .expect("signature does not verify")
+        .signature_bytes()
+        .to_vec()
+}
+
+#[test]
+fn sign_request_round_trip() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let req = request(&env, b"n-1");
+    let signature = sign(&env, alice, &req);
+
+    let ok: bool = env
+        .update(alice, "verify_sign_request", (alice, &req, &signature, NO_KEY))
+        .unwrap();
+    assert!(ok);
+
+    let mut tampered = req.clone();
+    tampered.payload = b"deploy tee-8".to_vec();
+    let ok: bool = env
+        .update(alice, "verify_sign_request", (alice, &tampered, &signature, NO_KEY))
+        .unwrap();
+    assert!(!ok);
+
+    let ok: bool = env
+        .update(alice, "verify_sign_request", (user(2), &req, &signature, NO_KEY))
+        .unwrap();
+    assert!(!ok);
+}
+
+#[test]
+fn sign_request_rejects_replay_and_expiry() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let req = request(&env, b"n-1");
+    sign(&env, alice, &req);
+
+    let res: Result<SignReply, _> =
+        env.update(alice, "sign_request", (&req, transport_key(1).public_key(), NO_KEY));
+    assert_rejected(res, "nonce already used");
+
+    let mut expired = request(&env, b"n-2");
+    expired.expires_at = env.now() - 1;
+    let res: Result<SignReply, _> =
+        env.update(alice, "sign_request", (&expired, transport_key(1).public_key(), NO_KEY));
+    assert_rejected(res, "expired");
+}
+
+#[test]
+fn sign_request_is_refused_without_cycles() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let req = request(&env, b"n-1");
+
+    let args = (&req, transport_key(1).public_key(), NO_KEY);
+    let err = env.update::<SignReply>(alice, "sign_request", args).unwrap().unwrap_err();
+    let MeteringError::InsufficientCycles { op, required, available } = err;
+    assert_eq!(op, MeteredOp::SignRequest);
+    assert!(required > 0);
+    assert_eq!(available, 0);
+
+    // A refused request does not consume its nonce.
+    sign(&env, alice, &req);
+}
+
+#[test]
+fn verify_signature_rejects_malformed_signature() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let ok: bool = env
+        .update(user(1), "verify_signature", (user(1), b"x".to_vec(), vec![0u8; 47], NO_KEY))
+        .unwrap();
+    assert!(!ok);
+}
+
+#[test]
+fn canister_signer_requires_allowlist() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let msg = b"manifest digest".to_vec();
+
+    let res: Result<SignReply, _> = env.update(alice, "sign_as_canister", (&msg, NO_KEY));
+    assert_rejected(res, "may not request canister signatures");
+
+    let res: Result<(), _> = env.update(alice, "add_canister_signer", (alice,));
+    assert_rejected(res, "caller is not a controller");
+    env.update::<()>(env.controller, "add_canister_signer", (alice,)).unwrap();
+    env.fund(alice);
+
+    let sig = env.update::<SignReply>(alice, "sign_as_canister", (&msg, NO_KEY)).unwrap().unwrap();
+    let pk: BlsPk = env.update(alice, "canister_bls_public_key", (NO_KEY,)).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    assert!(verify_bls_signature(&dpk, &msg, &sig.signature));
+    assert!(!verify_bls_signature(&dpk, b"other", &sig.signature));
+}
+
+#[test]
+fn upgrade_preserves_allowlist_and_legacy_flag() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    env.update::<()>(env.controller, "add_canister_signer", (alice,)).unwrap();
+    env.update::<()>(env.controller, "set_legacy_signing", (false,)).unwrap();
+    let used = request(&env, b"n-1");
+    sign(&env, alice, &used);
+
+    env.upgrade();
+
+    let signers: Vec<Principal> =
+        env.query(env.controller, "list_canister_signers", ()).unwrap();
+    assert_eq!(signers, vec![alice]);
+    let legacy: bool = env.query(alice, "legacy_signing_enabled", ()).unwrap();
+    assert!(!legacy);
+    let res: Result<SignReply, _> =
+        env.update(alice, "sign_caller", (b"x".to_vec(), transport_key(1).public_key(), NO_KEY));
+    assert_rejected(res, "legacy `sign_caller` is disabled");
+
+    // Consumed nonces survive the upgrade too.
+    let res: Result<SignReply, _> =
+        env.update(alice, "sign_request", (&used, transport_key(1).public_key(), NO_KEY));
+    assert_rejected(res, "nonce already used");
+}
+
+#[test]
+fn timelock_key_is_published_after_its_slot() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let now_secs = env.now() / 1_000_000_000;
+    let ts = (now_secs / 60 + 2) * 60;
+
+    let res: Result<TimelockReply, _> = env.update(alice, "register_timelock", (ts,));
+    assert_rejected(res, "may not register timelock slots");
+    let identity =
+        env.update::<TimelockReply>(env.controller, "register_timelock", (ts,)).unwrap().unwrap();
+    assert_eq!(identity, format!("time|{ts}").into_bytes());
+    let pk: BlsPk = env.update(alice, "timelock_public_key", ()).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    let seed = IbeSeed::from_bytes(&[7u8; 32]).unwrap();
+    let ct = IbeCiphertext::encrypt(&dpk, &IbeIdentity::from_bytes(&identity), b"bid", &seed);
+
+    let key: Option<Vec<u8>> = env.query(alice, "get_timelock_key", (ts,)).unwrap();
+    assert_eq!(key, None);
+
+    env.advance(Duration::from_secs(4 * 60));
+
+    let key: Option<Vec<u8>> = env.query(alice, "get_timelock_key", (ts,)).unwrap();
+    let vetkey = VetKey::deserialize(&key.expect("timelock key not published")).unwrap();
+    assert_eq!(ct.decrypt(&vetkey).unwrap(), b"bid".to_vec());
+
+    let res: Result<TimelockReply, _> = env.update(env.controller, "register_timelock", (ts + 1,));
+    assert_rejected(res, "multiple of 60");
+}
+
+fn beacon(env: &Env, caller: Principal, purpose: &str) -> BeaconEntry {
+    env.update::<BeaconReply>(caller, "beacon", (purpose,)).unwrap().unwrap()
+}
+
+fn get_beacon(env: &Env, epoch: u64, purpose: &str) -> Option<BeaconEntry> {
+    env.query(user(1), "get_beacon", (epoch, purpose)).unwrap()
+}
+
+#[test]
+fn beacon_is_verifiable() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let (epoch, epoch_secs): (u64, u64) = env.query(user(1), "beacon_epoch", ()).unwrap();
+    assert_eq!(epoch_secs, 60 * 60);
+
+    let entry = beacon(&env, env.controller, "akash-provider");
+    assert_eq!(entry.epoch, epoch);
+    assert_eq!(entry.purpose, "akash-provider");
+    assert_eq!(entry.input, [epoch.to_be_bytes().as_slice(), b"akash-provider"].concat());
+    let pk: BlsPk = env.update(user(1), "beacon_public_key", ()).unwrap();
+    let dpk = DerivedPublicKey::deserialize(&pk.pk).unwrap();
+    assert!(verify_bls_signature(&dpk, &entry.input, &entry.signature));
+    assert_eq!(entry.randomness, Sha256::digest(&entry.signature).to_vec());
+
+    // The epoch's value is recorded, and each purpose is its own stream.
+    let again = beacon(&env, env.controller, "akash-provider");
+    assert_eq!(again.signature, entry.signature);
+    assert_eq!(get_beacon(&env, epoch, "akash-provider").unwrap().signature, entry.signature);
+    let other = beacon(&env, env.controller, "recovery-witness");
+    assert!(verify_bls_signature(&dpk, &other.input, &other.signature));
+    assert_ne!(other.randomness, entry.randomness);
+}
+
+#[test]
+fn beacon_derives_only_the_current_epoch() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let first = beacon(&env, env.controller, "akash-provider");
+
+    // Nobody can look ahead: the next epoch has no value yet.
+    assert!(get_beacon(&env, first.epoch + 1, "akash-provider").is_none());
+
+    env.advance(Duration::from_secs(60 * 60));
+
+    let next = beacon(&env, env.controller, "akash-provider");
+    assert_eq!(next.epoch, first.epoch + 1);
+    assert_ne!(next.randomness, first.randomness);
+    assert_eq!(get_beacon(&env, first.epoch, "akash-provider").unwrap().signature, first.signature);
+
+    // A past epoch that was never derived cannot be derived later.
+    let late = beacon(&env, env.controller, "recovery-witness");
+    assert_eq!(late.epoch, next.epoch);
+    assert!(get_beacon(&env, first.epoch, "recovery-witness").is_none());
+}
+
+#[test]
+fn beacon_requires_an_authorized_caller() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+
+    let res: Result<BeaconReply, _> = env.update(alice, "beacon", ("akash-provider",));
+    assert_rejected(res, "caller may not advance the beacon");
+    let res: Result<BeaconReply, _> = env.update(env.controller, "beacon", ("",));
+    assert_rejected(res, "must be 1..=64 bytes");
+
+    // Allowlisted canister signers may advance it and pay for new entries.
+    env.update::<()>(env.controller, "add_canister_signer", (alice,)).unwrap();
+    let res = env.update::<BeaconReply>(alice, "beacon", ("akash-provider",)).unwrap();
+    assert!(matches!(
+        res.unwrap_err(),
+        MeteringError::InsufficientCycles { op: MeteredOp::Beacon, available: 0, .. }
+    ));
+    env.fund(alice);
+    beacon(&env, alice, "akash-provider");
+
+    env.update::<bool>(env.controller, "remove_canister_signer", (alice,)).unwrap();
+    let res: Result<BeaconReply, _> = env.update(alice, "beacon", ("akash-provider",));
+    assert_rejected(res, "caller may not advance the beacon");
+}
+
+#[test]
+fn cycle_grants_are_audited() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let alice = user(1);
+    let res: Result<u128, _> = env.update(alice, "grant_cycles", (alice, 1_000u128));
+    assert_rejected(res, "caller is not a controller");
+    env.update::<u128>(env.controller, "grant_cycles", (alice, 1_000u128)).unwrap();
+
+    let res: Result<Vec<AuditEvent>, _> = env.query(alice, "get_audit_log", (0u64, 10u64));
+    assert_rejected(res, "caller is not a controller");
+    let log: Vec<AuditEvent> = env.query(env.controller, "get_audit_log", (0u64, 10u64)).unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].actor, env.controller);
+    assert_eq!(log[0].kind, AuditKind::MeteringChanged);
+    assert_eq!(log[0].subject, alice.to_text());
+    assert_eq!(log[0].detail, "granted 1000 cycles");
+}
+
+#[test]
+fn key_registry_changes_are_audited() {
+    let env = Env::new(Canister::VetkeysDemo);
+    let config = KeyConfig { name: "test_key_1".into(), curve: KeyCurve::Bls12_381_G2 };
+    env.update::<()>(env.controller, "add_key", ("test", &config)).unwrap();
+    env.update::<()>(env.controller, "add_key", ("spare", &config)).unwrap();
+    let scope = KeyScope::Purpose("beacon".into());
+    env.update::<()>(env.controller, "assign_key", (scope, "test")).unwrap();
+    assert!(env.update::<bool>(env.controller, "remove_key", ("spare",)).unwrap());
+
+    let log: Vec<AuditEvent> = env.query(env.controller, "get_audit_log", (0u64, 10u64)).unwrap();
+    let entries: Vec<_> =
+        log.iter().map(|e| (&e.kind, e.subject.as_str(), e.detail.as_str())).collect();
+    assert_eq!(
+        entries,
+        [
+            (&AuditKind::KeyRegistryChanged, "test", "added test_key_1 (Bls12_381_G2)"),
+            (&AuditKind::KeyRegistryChanged, "spare", "added test_key_1 (Bls12_381_G2)"),
+            (&AuditKind::KeyRegistryChanged, "Purpose(\"beacon\")", "assigned test"),
+            (&AuditKind::KeyRegistryChanged, "spare", "removed"),
+        ]
+    );
+    assert!(log.iter().all(|e| e.actor == env.controller));
+}
@@ -10,7 +10,7 @@
  *
  * Canister API (Candid):
  *  - bls_public_key() -> BlsPk
- *  - derive_data_key(record_id: blob, transport_pk_g1_48B: blob) -> EncryptedKey
+ *  - derive_data_key(record_id: blob, transport_pk_g1_48B: blob) -> variant { Ok : EncryptedKey; Err : MeteringError }
  *  - put_record(record_id: blob, envelope: blob)
  *  - get_record(record_id: blob) -> opt blob   (JS shape: [] | [Uint8Array])
  *  - list_record_ids() -> vec blob
@@ -107,8 +107,9 @@ async function main() {
 
   // 1) Derive a record-scoped data key, encrypted to our BLS12-381 G1 public key (48 bytes)
   const { sk: transportSk, pk: transportPk } = generateTransportG1();
-  const enc = await can.derive_data_key(Array.from(recordId), Array.from(transportPk));
-  const encrypted_key = new Uint8Array(enc.encrypted_key);
+  const res = await can.derive_data_key(Array.from(recordId), Array.from(transportPk));
+  if ('Err' in res) throw new Error(`derive_data_key refused: ${Object.keys(res.Err)[0]}`);
+  const encrypted_key = new Uint8Array(res.Ok.encrypted_key);
 
   // 2) Decrypt VetKD transport envelope client-side (TODO: real impl)
   const dataKey = decryptVetKDEncryptedKey(encrypted_key, transportSk);
@@ -101,8 +101,9 @@ IgIY7lH0W0p0b3lUo3o1tFq8aHq2C2o5nYx1lq8k4x2k=
 
   // 2) Derive VetKD encrypted data key for this record_id, using G1 (48B) transport pk
   const { sk: transportSk, pk: transportPk } = generateTransportG1();
-  const enc = await can.derive_data_key(Array.from(recordId), Array.from(transportPk));
-  const encrypted_key = new Uint8Array(enc.encrypted_key);
+  const res = await can.derive_data_key(Array.from(recordId), Array.from(transportPk));
+  if ('Err' in res) throw new Error(`derive_data_key refused: ${Object.keys(res.Err)[0]}`);
+  const encrypted_key = new Uint8Array(res.Ok.encrypted_key);
 
   // 3) Client-side decrypt (TODO real impl) -> 32B data key
   const dataKey = decryptVetKDEncryptedKey(encrypted_key, transportSk);
@@ -97,7 +97,8 @@ async function main() {
   // 1) Derive VetKD key (transport = BLS12-381 G1, 48 bytes)
   const { sk: transportSk, pk: transportPk } = generateTransportG1();
   const encKeyRes = await can.derive_data_key(Array.from(recordId), Array.from(transportPk));
-  const encrypted_key = new Uint8Array(encKeyRes.encrypted_key);
+  if ('Err' in encKeyRes) throw new Error(`derive_data_key refused: ${Object.keys(encKeyRes.Err)[0]}`);
+  const encrypted_key = new Uint8Array(encKeyRes.Ok.encrypted_key);
   console.log('[DBG] derive_data_key: encrypted_key len =', encrypted_key.length);
 
   // 2) Decrypt VetKD key (mock)
@@ -0,0 +1,78 @@
+//! Append-only audit log of security-relevant state changes.
+//!
+//! Events are keyed by a monotonically increasing sequence number so the log
+//! can be paged from any offset and survives upgrades in stable memory.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::{layout, memory, Memory};
+
+/// Upper bound on events returned by a single `get_audit_log` page.
+const MAX_PAGE: u64 = 500;
+
+#[derive(Clone, CandidType, Deserialize)]
+pub enum AuditKind {
+    NodeRegistered,
+    NodeAttested,
+    NodeStatusChanged,
+    RecoveryOpened,
+    RecoveryApproved,
+    RecoveryStatusChanged,
+    RecoveryKeyDerived,
+    KeyRegistryChanged,
+    MeteringChanged,
+    NodeConfigChanged,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct AuditEvent {
+    pub seq: u64,
+    pub timestamp: u64,
+    /// Principal that triggered the change (the canister itself for timer jobs).
+    pub actor: Principal,
+    pub kind: AuditKind,
+    pub subject: String,
+    pub detail: String,
+}
+
+candid_storable!(AuditEvent);
+
+thread_local! {
+    static LOG: RefCell<StableBTreeMap<u64, AuditEvent, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::AUDIT_LOG)));
+}
+
+/// Appends an event attributed to `actor`.
+pub(crate) fn record(actor: Principal, kind: AuditKind, subject: &str, detail: String) {
+    LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        let seq = log.last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
+        log.insert(
+            seq,
+            AuditEvent {
+                seq,
+                timestamp: ic_cdk::api::time(),
+                actor,
+                kind,
+                subject: subject.to_string(),
+                detail,
+            },
+        );
+    });
+}
+
+/// Returns up to `limit` events starting at sequence number `start` (controllers only).
+#[query]
+fn get_audit_log(start: u64, limit: u64) -> Vec<AuditEvent> {
+    crate::require_controller();
+    LOG.with(|log| {
+        log.borrow()
+            .range(start..)
+            .take(limit.min(MAX_PAGE) as usize)
+            .map(|(_, e)| e)
+            .collect()
+    })
+}
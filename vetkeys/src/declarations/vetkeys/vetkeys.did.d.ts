@@ -5,10 +5,22 @@ import type { IDL } from '@dfinity/candid';
 export type Blob = Uint8Array | number[];
 export interface BlsPk { 'pk' : Blob }
 export interface EncryptedKey { 'encrypted_key' : Blob }
+export type EncryptedKeyResult = { 'Ok' : EncryptedKey } |
+  { 'Err' : MeteringError };
+export type MeteredOp = { 'DeriveDataKey' : null } |
+  { 'DeriveRecoveryKey' : null } |
+  { 'DeriveIbeKey' : null };
+export type MeteringError = {
+    'InsufficientCycles' : {
+      'op' : MeteredOp,
+      'required' : bigint,
+      'available' : bigint,
+    }
+  };
 export interface _SERVICE {
   'bls_public_key' : ActorMethod<[], BlsPk>,
   'delete_record' : ActorMethod<[Blob], boolean>,
-  'derive_data_key' : ActorMethod<[Blob, Blob], EncryptedKey>,
+  'derive_data_key' : ActorMethod<[Blob, Blob], EncryptedKeyResult>,
   'get_record' : ActorMethod<[Blob], [] | [Blob]>,
   'list_record_ids' : ActorMethod<[], Array<Blob>>,
   'put_record' : ActorMethod<[Blob, Blob], undefined>,
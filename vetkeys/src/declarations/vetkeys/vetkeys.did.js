@@ -2,10 +2,26 @@ export const idlFactory = ({ IDL }) => {
   const Blob = IDL.Vec(IDL.Nat8);
   const BlsPk = IDL.Record({ 'pk' : Blob });
   const EncryptedKey = IDL.Record({ 'encrypted_key' : Blob });
+  const MeteredOp = IDL.Variant({
+    'DeriveDataKey' : IDL.Null,
+    'DeriveRecoveryKey' : IDL.Null,
+    'DeriveIbeKey' : IDL.Null,
+  });
+  const MeteringError = IDL.Variant({
+    'InsufficientCycles' : IDL.Record({
+      'op' : MeteredOp,
+      'required' : IDL.Nat,
+      'available' : IDL.Nat,
+    }),
+  });
+  const EncryptedKeyResult = IDL.Variant({
+    'Ok' : EncryptedKey,
+    'Err' : MeteringError,
+  });
   return IDL.Service({
     'bls_public_key' : IDL.Func([], [BlsPk], []),
     'delete_record' : IDL.Func([Blob], [IDL.Bool], []),
-    'derive_data_key' : IDL.Func([Blob, Blob], [EncryptedKey], []),
+    'derive_data_key' : IDL.Func([Blob, Blob], [EncryptedKeyResult], []),
     'get_record' : IDL.Func([Blob], [IDL.Opt(Blob)], []),
     'list_record_ids' : IDL.Func([], [IDL.Vec(Blob)], []),
     'put_record' : IDL.Func([Blob, Blob], [], []),
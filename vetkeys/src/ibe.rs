@@ -0,0 +1,158 @@
+//! Identity-based encryption (IBE) to registered TEE nodes.
+//!
+//! Anyone can seal a payload to a node with `ic_vetkeys::IbeCiphertext`
+//! using the canister-wide `ibe_public_key()` and the node's identity
+//! `node|<node_id>`. Only the node itself can obtain the matching decryption
+//! key through `derive_ibe_key`.
+//!
+//! - VetKD `context = len(IBE_DS) || IBE_DS` (not bound to any caller)
+//! - VetKD `input = "node|" || node_id` (the IBE identity)
+
+use candid::Principal;
+use ic_cdk::management_canister::{vetkd_public_key, VetKDPublicKeyArgs};
+use ic_cdk_macros::*;
+
+use crate::keys;
+use crate::metering::MeteredOp;
+use crate::nodes::{self, NodeStatus};
+use crate::BlsPk;
+
+const IBE_DS: &[u8] = b"dooor.vetkeys.ibe.v1";
+const NODE_IDENTITY_PREFIX: &[u8] = b"node|";
+
+fn ibe_context() -> Vec<u8> {
+    std::iter::once(IBE_DS.len() as u8)
+        .chain(IBE_DS.iter().copied())
+        .collect()
+}
+
+/// IBE identity bytes for `node_id`: `"node|" || node_id`.
+pub fn node_identity(node_id: &str) -> Vec<u8> {
+    let mut v = Vec::with_capacity(NODE_IDENTITY_PREFIX.len() + node_id.len());
+    v.extend_from_slice(NODE_IDENTITY_PREFIX);
+    v.extend_from_slice(node_id.as_bytes());
+    v
+}
+
+/// Identity of `caller`, trapping unless it is an `Active` registered node.
+fn caller_identity(caller: Principal) -> Vec<u8> {
+    let node = nodes::node_of(caller)
+        .unwrap_or_else(|| ic_cdk::trap("caller is not a registered node"));
+    if node.status != NodeStatus::Active {
+        ic_cdk::trap(&format!("node is {:?}; submit fresh attestation evidence", node.status));
+    }
+    node_identity(&node.node_id)
+}
+
+// ── IBE API ───────────────────────────────────────────────────────────────
+/// Returns the derived public key that senders encrypt to (96-byte G2).
+#[update]
+async fn ibe_public_key(key: Option<String>) -> BlsPk {
+    let args = VetKDPublicKeyArgs {
+        canister_id: None,
+        context: ibe_context(),
+        key_id: keys::resolve(key, None, "ibe"),
+    };
+    let res = vetkd_public_key(&args)
+        .await
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("VetKD public_key error: {e:?}")));
+    BlsPk { pk: res.public_key }
+}
+
+/// Derives the caller node's IBE decryption key, encrypted to `transport_pk`.
+/// Replies with `Result<EncryptedKey, MeteringError>`.
+#[update(manual_reply = true)]
+async fn derive_ibe_key(transport_pk: Vec<u8>, key: Option<String>) {
+    crate::check_transport_pk(&transport_pk);
+    let identity = caller_identity(ic_cdk::api::caller());
+    let key_id = keys::resolve_for_derivation(key, None, "ibe");
+    let op = MeteredOp::DeriveIbeKey;
+    crate::reply_with_encrypted_key(op, key_id, ibe_context(), identity, transport_pk).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_bls12_381::{
+        hash_to_curve::{ExpandMsgXmd, HashToCurve},
+        G1Affine, G1Projective, G2Affine, Scalar,
+    };
+    use ic_vetkeys::{DerivedPublicKey, IbeCiphertext, IbeIdentity, IbeSeed, VetKey};
+
+    /// Stand-in for a VetKD subnet key, derived locally from a fixed secret.
+    struct SimulatedKey {
+        secret: Scalar,
+        public: G2Affine,
+    }
+
+    impl SimulatedKey {
+        fn new(seed: u8) -> Self {
+            let secret = Scalar::from_bytes_wide(&[seed; 64]);
+            let public = G2Affine::from(G2Affine::generator() * secret);
+            SimulatedKey { secret, public }
+        }
+
+        fn derived_public_key(&self) -> DerivedPublicKey {
+            DerivedPublicKey::deserialize(&self.public.to_compressed()).unwrap()
+        }
+
+        /// What `vetkd_derive_key` yields after transport decryption:
+        /// `secret * H(pk || input)` with the augmented BLS hash.
+        fn vetkey(&self, input: &[u8]) -> VetKey {
+            const DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_";
+            let mut msg = self.public.to_compressed().to_vec();
+            msg.extend_from_slice(input);
+            let h = <G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(
+                &msg, DST,
+            );
+            VetKey::deserialize(&G1Affine::from(h * self.secret).to_compressed()).unwrap()
+        }
+    }
+
+    fn seal(key: &SimulatedKey, node_id: &str, msg: &[u8]) -> Vec<u8> {
+        let identity = IbeIdentity::from_bytes(&node_identity(node_id));
+        let seed = IbeSeed::from_bytes(&[42u8; 32]).unwrap();
+        IbeCiphertext::encrypt(&key.derived_public_key(), &identity, msg, &seed).serialize()
+    }
+
+    #[test]
+    fn node_identity_format() {
+        assert_eq!(node_identity("tee-7"), b"node|tee-7".to_vec());
+        assert_eq!(node_identity(""), b"node|".to_vec());
+    }
+
+    #[test]
+    fn ibe_context_is_length_prefixed() {
+        let ctx = ibe_context();
+        assert_eq!(ctx[0] as usize, IBE_DS.len());
+        assert_eq!(&ctx[1..], IBE_DS);
+    }
+
+    #[test]
+    fn recipient_key_decrypts() {
+        let key = SimulatedKey::new(1);
+        let sealed = seal(&key, "tee-7", b"akash mnemonic");
+
+        let ct = IbeCiphertext::deserialize(&sealed).unwrap();
+        let vetkey = key.vetkey(&node_identity("tee-7"));
+        assert_eq!(ct.decrypt(&vetkey).unwrap(), b"akash mnemonic".to_vec());
+    }
+
+    #[test]
+    fn other_node_key_does_not_decrypt() {
+        let key = SimulatedKey::new(1);
+        let sealed = seal(&key, "tee-7", b"secret");
+
+        let ct = IbeCiphertext::deserialize(&sealed).unwrap();
+        assert!(ct.decrypt(&key.vetkey(&node_identity("tee-8"))).is_err());
+    }
+
+    #[test]
+    fn other_master_key_does_not_decrypt() {
+        let sealed = seal(&SimulatedKey::new(1), "tee-7", b"secret");
+
+        let ct = IbeCiphertext::deserialize(&sealed).unwrap();
+        let wrong = SimulatedKey::new(2).vetkey(&node_identity("tee-7"));
+        assert!(ct.decrypt(&wrong).is_err());
+    }
+}
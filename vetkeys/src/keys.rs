@@ -0,0 +1,114 @@
+//! Key registry endpoints.
+//!
+//! The registry itself (resolution order, validation, migration rules) lives
+//! in `dooor_vetkd_common::keys`; this module wires it to stable memory and
+//! audits every change.
+
+use candid::Principal;
+use dooor_vetkd_common::keys::{KeyAssignment, KeyConfig, KeyEntry, KeyRegistry, KeyScope};
+use ic_cdk::management_canister::VetKDKeyId;
+use ic_cdk_macros::*;
+use std::cell::RefCell;
+
+use crate::audit::{self, AuditKind};
+use crate::{layout, memory, require_controller, Memory};
+
+/// Purposes that can be assigned a key. Recovery derives the owner's data
+/// keys, so it resolves with `db`.
+pub const PURPOSES: &[&str] = &["db", "ibe"];
+
+thread_local! {
+    static REGISTRY: RefCell<KeyRegistry<Memory>> = RefCell::new(KeyRegistry::init(
+        PURPOSES,
+        memory(layout::KEY_REGISTRY),
+        memory(layout::KEY_ASSIGNMENTS),
+        memory(layout::KEY_DERIVED),
+    ));
+}
+
+/// Resolves the key id for a public key or a verification, trapping on an
+/// unknown selector.
+///
+/// `tenant` is the principal whose material is derived (caller, owner or
+/// signer), `None` for canister-wide contexts.
+pub(crate) fn resolve(
+    selector: Option<String>,
+    tenant: Option<Principal>,
+    purpose: &str,
+) -> VetKDKeyId {
+    REGISTRY
+        .with(|r| r.borrow().resolve(selector, tenant, purpose))
+        .unwrap_or_else(|e| ic_cdk::trap(&e.to_string()))
+}
+
+/// Resolves the key id for a derivation like [`resolve`], and records the
+/// scopes an assignment would switch so that `assign_key` refuses them.
+/// Call it before every `vetkd_derive_key`.
+pub(crate) fn resolve_for_derivation(
+    selector: Option<String>,
+    tenant: Option<Principal>,
+    purpose: &str,
+) -> VetKDKeyId {
+    REGISTRY
+        .with(|r| r.borrow_mut().resolve_for_derivation(selector, tenant, purpose))
+        .unwrap_or_else(|e| ic_cdk::trap(&e.to_string()))
+}
+
+// ── Registry API ──────────────────────────────────────────────────────────
+/// Registers `config` under `alias` (controllers only). Aliases are permanent.
+#[update]
+fn add_key(alias: String, config: KeyConfig) {
+    require_controller();
+    let detail = format!("added {} ({:?})", config.name, config.curve);
+    REGISTRY
+        .with(|r| r.borrow_mut().add(alias.clone(), config))
+        .unwrap_or_else(|e| ic_cdk::trap(&e.to_string()));
+    audit::record(ic_cdk::api::caller(), AuditKind::KeyRegistryChanged, &alias, detail);
+}
+
+/// Removes an alias that is not assigned to anything (controllers only).
+#[update]
+fn remove_key(alias: String) -> bool {
+    require_controller();
+    let removed = REGISTRY
+        .with(|r| r.borrow_mut().remove(&alias))
+        .unwrap_or_else(|e| ic_cdk::trap(&e.to_string()));
+    if removed {
+        let caller = ic_cdk::api::caller();
+        audit::record(caller, AuditKind::KeyRegistryChanged, &alias, "removed".into());
+    }
+    removed
+}
+
+/// Assigns `alias` to `scope` (controllers only). Assignments are permanent.
+/// A scope that has already derived keys is refused, since the assignment
+/// would switch them, unless `force` is set; forced assignments are audited
+/// as such.
+#[update]
+fn assign_key(scope: KeyScope, alias: String, force: Option<bool>) {
+    require_controller();
+    let subject = format!("{scope:?}");
+    let forced = REGISTRY
+        .with(|r| {
+            let mut r = r.borrow_mut();
+            let forced = force == Some(true) && r.has_derived(&scope);
+            r.assign(scope, alias.clone(), force == Some(true)).map(|()| forced)
+        })
+        .unwrap_or_else(|e| ic_cdk::trap(&e.to_string()));
+    let detail = match forced {
+        true => format!("assigned {alias} (forced over existing derivations)"),
+        false => format!("assigned {alias}"),
+    };
+    audit::record(ic_cdk::api::caller(), AuditKind::KeyRegistryChanged, &subject, detail);
+}
+
+/// Lists all aliases, `default` first.
+#[query]
+fn list_keys() -> Vec<KeyEntry> {
+    REGISTRY.with(|r| r.borrow().list())
+}
+
+#[query]
+fn list_key_assignments() -> Vec<KeyAssignment> {
+    REGISTRY.with(|r| r.borrow().assignments())
+}
@@ -0,0 +1,51 @@
+//! Stable memory layout of the vetkeys canister.
+//!
+//! Ids are permanent: never renumber or rename a region. A region that is no
+//! longer used moves to `retired` so its id is never handed out again.
+
+use dooor_memory_layout::{Layout, Region, RegionStats};
+use ic_cdk_macros::*;
+
+use crate::{require_controller, MM};
+
+pub const DB: u8 = 0;
+pub const NODES: u8 = 1;
+pub const NODE_CONFIG: u8 = 2;
+pub const AUDIT_LOG: u8 = 3;
+pub const RECOVERY_REQUESTS: u8 = 4;
+pub const RECOVERY_CONFIG: u8 = 5;
+pub const KEY_REGISTRY: u8 = 6;
+pub const KEY_ASSIGNMENTS: u8 = 7;
+pub const CYCLE_BALANCES: u8 = 8;
+pub const METERING_CONFIG: u8 = 9;
+pub const KEY_DERIVED: u8 = 10;
+
+pub const LAYOUT: Layout = Layout {
+    regions: &[
+        Region::new(DB, "db.records", "lib"),
+        Region::new(NODES, "nodes.registry", "nodes"),
+        Region::new(NODE_CONFIG, "nodes.config", "nodes"),
+        Region::new(AUDIT_LOG, "audit.log", "audit"),
+        Region::new(RECOVERY_REQUESTS, "recovery.requests", "recovery"),
+        Region::new(RECOVERY_CONFIG, "recovery.config", "recovery"),
+        Region::new(KEY_REGISTRY, "keys.registry", "keys"),
+        Region::new(KEY_ASSIGNMENTS, "keys.assignments", "keys"),
+        Region::new(CYCLE_BALANCES, "metering.balances", "metering"),
+        Region::new(METERING_CONFIG, "metering.config", "metering"),
+        Region::new(KEY_DERIVED, "keys.derived", "keys"),
+    ],
+    retired: &[],
+};
+
+/// Checks the layout against the manifest in stable memory; traps on mismatch.
+pub(crate) fn validate() {
+    MM.with(|m| LAYOUT.validate(&m.borrow()))
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("stable memory layout: {e}")));
+}
+
+/// Per-region stable memory usage (controllers only).
+#[query]
+fn memory_stats() -> Vec<RegionStats> {
+    require_controller();
+    MM.with(|m| LAYOUT.stats(&m.borrow()))
+}
@@ -11,20 +11,41 @@
 //! - VetKD public key retrieval bound to caller context
 //! - VetKD data-key derivation for a given record_id
 //! - Auth-scoped put/get/list/delete of encrypted records per caller
+//! - Periodic re-attestation of registered TEE nodes with automatic quarantine
+//! - M-of-N approved recovery of a failed node's records by a replacement node
+//! - Identity-based encryption to registered nodes (identity `node|<node_id>`)
+//! - Registry of VetKD key ids assignable to tenants or purposes
+//! - Prepaid per-principal cycle balances charged for each derivation
 //!
 //! ## Security properties
 //! - VetKD `context = len(DS) || DS || caller_principal` binds material to the caller
 //! - VetKD `input = "db|v1|" || record_id` binds material to the logical record
+//! - Data keys are only released to their owner, or to the replacement node of
+//!   an approved recovery request for the owner
 //! - Access control: callers can only operate on their own records
+//! - Registered nodes that miss their attestation deadline are quarantined and
+//!   can no longer derive data keys or write records until they re-attest
 
 use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::management_canister::{
     vetkd_derive_key, vetkd_public_key,
-    VetKDDeriveKeyArgs, VetKDKeyId, VetKDPublicKeyArgs, VetKDCurve,
+    VetKDDeriveKeyArgs, VetKDKeyId, VetKDPublicKeyArgs,
 };
 use ic_cdk_macros::*;
 use std::cell::RefCell;
 
+/// `candid_storable!` implements `Storable` for Candid-serializable types.
+#[macro_use]
+extern crate dooor_vetkd_common;
+
+mod audit;
+mod ibe;
+mod keys;
+mod layout;
+mod metering;
+mod nodes;
+mod recovery;
+
 // ── getrandom: custom stub (avoids WebCrypto in wasm) ──────────────────────
 use getrandom::register_custom_getrandom;
 fn no_rand(_: &mut [u8]) -> Result<(), getrandom::Error> {
@@ -34,7 +55,6 @@ register_custom_getrandom!(no_rand);
 
 // ── Constants ─────────────────────────────────────────────────────────────
 const DS: &[u8] = b"dooor.vetkeys.db.v1";
-const KEY_NAME: &str = "key_1";
 
 // ── Stable Structures ─────────────────────────────────────────────────────
 use ic_stable_structures::{
@@ -46,6 +66,16 @@ use ic_stable_structures::{
 
 type PKey = [u8; 29];
 
+// Memory ids are declared in `layout`.
+pub(crate) type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+pub(crate) fn memory(id: u8) -> Memory {
+    if layout::LAYOUT.region(id).is_none() {
+        ic_cdk::trap(&format!("memory id {id} is not declared in the layout"));
+    }
+    MM.with(|m| m.borrow().get(MemoryId::new(id)))
+}
+
 #[derive(Clone, CandidType, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 struct DbKey {
     user: PKey,
@@ -83,24 +113,26 @@ impl Storable for Envelope {
 }
 
 thread_local! {
-    static MM: RefCell<MemoryManager<DefaultMemoryImpl>> =
+    pub(crate) static MM: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 
-    static DB: RefCell<StableBTreeMap<
-        DbKey, Envelope, VirtualMemory<DefaultMemoryImpl>>> =
-        RefCell::new(StableBTreeMap::init(
-            MM.with(|m| m.borrow().get(MemoryId::new(0)))
-    ));
+    static DB: RefCell<StableBTreeMap<DbKey, Envelope, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::DB)));
 }
 
 // ── Helpers ───────────────────────────────────────────────────────────────
-fn context(p: Principal) -> Vec<u8> {
-    std::iter::once(DS.len() as u8)
-        .chain(DS.iter().copied())
+fn ds_context(ds: &[u8], p: Principal) -> Vec<u8> {
+    std::iter::once(ds.len() as u8)
+        .chain(ds.iter().copied())
         .chain(p.as_slice().iter().copied())
         .collect()
 }
 
+/// VetKD context of `p`'s data keys.
+pub(crate) fn context(p: Principal) -> Vec<u8> {
+    ds_context(DS, p)
+}
+
 fn pk(principal: Principal) -> PKey {
     let src = principal.as_slice();
     let mut out = [0u8; 29];
@@ -108,7 +140,13 @@ fn pk(principal: Principal) -> PKey {
     out
 }
 
-fn data_key_input(record_id: &[u8]) -> Vec<u8> {
+pub(crate) fn require_controller() {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        ic_cdk::trap("caller is not a controller");
+    }
+}
+
+pub(crate) fn data_key_input(record_id: &[u8]) -> Vec<u8> {
     const PREFIX: &[u8] = b"db|v1|";
     let mut v = Vec::with_capacity(PREFIX.len() + record_id.len());
     v.extend_from_slice(PREFIX);
@@ -116,6 +154,61 @@ fn data_key_input(record_id: &[u8]) -> Vec<u8> {
     v
 }
 
+pub(crate) fn check_transport_pk(transport_pk: &[u8]) {
+    if transport_pk.len() != 48 {
+        ic_cdk::trap("transport_public_key must be 48 bytes (BLS12-381 G1 compressed)");
+    }
+}
+
+/// Charges the caller for `op` (see `metering`), derives the key and replies
+/// with `Result<EncryptedKey, MeteringError>`. Endpoints that call this use
+/// `manual_reply`, so that a failed derivation can refund the charge and
+/// reject without trapping. Returns whether the key was derived.
+pub(crate) async fn reply_with_encrypted_key(
+    op: metering::MeteredOp,
+    key_id: VetKDKeyId,
+    context: Vec<u8>,
+    input: Vec<u8>,
+    transport_pk: Vec<u8>,
+) -> bool {
+    let caller = ic_cdk::api::caller();
+    let Some(charged) = metering::charge_or_reply::<EncryptedKey>(caller, op, &key_id) else {
+        return false;
+    };
+    let args = VetKDDeriveKeyArgs {
+        input,
+        context,
+        transport_public_key: transport_pk,
+        key_id,
+    };
+    let res = vetkd_derive_key(&args)
+        .await
+        .map(|res| EncryptedKey { encrypted_key: res.encrypted_key })
+        .map_err(|e| format!("VetKD derive error: {e:?}"));
+    let derived = res.is_ok();
+    metering::reply_or_refund(caller, charged, res);
+    derived
+}
+
+pub(crate) fn record_ids_of(owner: Principal) -> Vec<Vec<u8>> {
+    let owner = pk(owner);
+    DB.with(|db| {
+        let b = db.borrow();
+        let mut out = Vec::new();
+        for (k, _) in b.iter() {
+            if k.user == owner {
+                out.push(k.record_id.clone());
+            }
+        }
+        out
+    })
+}
+
+pub(crate) fn record_of(owner: Principal, record_id: Vec<u8>) -> Option<Vec<u8>> {
+    let key = DbKey { user: pk(owner), record_id };
+    DB.with(|db| db.borrow().get(&key).map(|e| e.0.clone()))
+}
+
 // ── DTOs ──────────────────────────────────────────────────────────────────
 #[derive(CandidType, Deserialize)]
 pub struct BlsPk {
@@ -129,19 +222,26 @@ pub struct EncryptedKey {
 
 // ── Lifecycle ─────────────────────────────────────────────────────────────
 #[init]
-fn init() {}
+fn init() {
+    layout::validate();
+    metering::enable_on_install();
+    nodes::start_health_timer();
+}
 
 #[post_upgrade]
-fn post_upgrade() {}
+fn post_upgrade() {
+    layout::validate();
+    nodes::start_health_timer();
+}
 
 // ── VetKD API ─────────────────────────────────────────────────────────────
 #[update]
-async fn bls_public_key() -> BlsPk {
+async fn bls_public_key(key: Option<String>) -> BlsPk {
     let caller = ic_cdk::api::caller();
     let args = VetKDPublicKeyArgs {
         canister_id: None,
         context: context(caller),
-        key_id: VetKDKeyId { name: KEY_NAME.into(), curve: VetKDCurve::Bls12_381_G2 },
+        key_id: keys::resolve(key, Some(caller), "db"),
     };
     let res = vetkd_public_key(&args)
         .await
@@ -149,27 +249,27 @@ async fn bls_public_key() -> BlsPk {
     BlsPk { pk: res.public_key }
 }
 
-#[update]
-async fn derive_data_key(record_id: Vec<u8>, transport_pk: Vec<u8>) -> EncryptedKey {
-    if transport_pk.len() != 48 {
-        ic_cdk::trap("transport_public_key must be 48 bytes (BLS12-381 G1 compressed)");
-    }
+/// Replies with `Result<EncryptedKey, MeteringError>`.
+#[update(manual_reply = true)]
+async fn derive_data_key(record_id: Vec<u8>, transport_pk: Vec<u8>, key: Option<String>) {
+    check_transport_pk(&transport_pk);
     let caller = ic_cdk::api::caller();
-    let args = VetKDDeriveKeyArgs {
-        input: data_key_input(&record_id),
-        context: context(caller),
-        transport_public_key: transport_pk,
-        key_id: VetKDKeyId { name: KEY_NAME.into(), curve: VetKDCurve::Bls12_381_G2 },
-    };
-    let res = vetkd_derive_key(&args)
-        .await
-        .unwrap_or_else(|e| ic_cdk::trap(&format!("VetKD derive error: {e:?}")));
-    EncryptedKey { encrypted_key: res.encrypted_key }
+    nodes::ensure_not_quarantined(caller);
+    let key_id = keys::resolve_for_derivation(key, Some(caller), "db");
+    reply_with_encrypted_key(
+        metering::MeteredOp::DeriveDataKey,
+        key_id,
+        context(caller),
+        data_key_input(&record_id),
+        transport_pk,
+    )
+    .await;
 }
 
 // ── DB API ─────────────────────────────────────────────────────────────────
 #[update]
 fn put_record(record_id: Vec<u8>, envelope: Vec<u8>) {
+    nodes::ensure_not_quarantined(ic_cdk::api::caller());
     let key = DbKey { user: pk(ic_cdk::api::caller()), record_id };
     DB.with(|db| {
         db.borrow_mut().insert(key, Envelope(envelope));
@@ -178,27 +278,17 @@ fn put_record(record_id: Vec<u8>, envelope: Vec<u8>) {
 
 #[query]
 fn get_record(record_id: Vec<u8>) -> Option<Vec<u8>> {
-    let key = DbKey { user: pk(ic_cdk::api::caller()), record_id };
-    DB.with(|db| db.borrow().get(&key).map(|e| e.0.clone()))
+    record_of(ic_cdk::api::caller(), record_id)
 }
 
 #[query]
 fn list_record_ids() -> Vec<Vec<u8>> {
-    let me = pk(ic_cdk::api::caller());
-    DB.with(|db| {
-        let b = db.borrow();
-        let mut out = Vec::new();
-        for (k, _) in b.iter() {
-            if k.user == me {
-                out.push(k.record_id.clone());
-            }
-        }
-        out
-    })
+    record_ids_of(ic_cdk::api::caller())
 }
 
 #[update]
 fn delete_record(record_id: Vec<u8>) -> bool {
+    nodes::ensure_not_quarantined(ic_cdk::api::caller());
     let key = DbKey { user: pk(ic_cdk::api::caller()), record_id };
     DB.with(|db| db.borrow_mut().remove(&key).is_some())
 }
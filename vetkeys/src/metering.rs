@@ -0,0 +1,177 @@
+//! Prepaid cycles for VetKD derivations.
+//!
+//! Every `vetkd_derive_key` call is paid from the canister's cycles. While
+//! metering is enabled, each principal pays for its own derivations from a
+//! cycles balance held by this canister:
+//! - `top_up` accepts the cycles attached to the call (from a wallet or any
+//!   other canister) and credits them to the caller or a beneficiary
+//! - controllers can `grant_cycles` to principals that cannot attach cycles,
+//!   such as TEE nodes calling over ingress
+//! - each derivation is charged the management canister's current price for
+//!   the key plus `margin_percent`, before the call is made. The charge is
+//!   refunded if the derivation fails
+//!
+//! Controllers are never charged. Metering is enabled when the canister is
+//! installed. A canister upgraded from a version without metering starts with
+//! it disabled, so that nodes keep working across that upgrade; fund their
+//! balances, then enable it with `set_metering_config`.
+//!
+//! Balances, config and pricing are kept by `dooor_vetkd_common::metering`;
+//! this module defines the metered operations and endpoints.
+
+use candid::{CandidType, Deserialize, Principal};
+use dooor_vetkd_common::metering::{self as common, Meter, MeteringConfig};
+use ic_cdk::management_canister::VetKDKeyId;
+use ic_cdk_macros::*;
+use std::cell::RefCell;
+
+use crate::audit::{self, AuditKind};
+use crate::{keys, layout, memory, require_controller, Memory};
+
+/// An endpoint that derives a key and is charged for it.
+#[derive(Clone, Copy, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub enum MeteredOp {
+    DeriveDataKey,
+    DeriveRecoveryKey,
+    DeriveIbeKey,
+}
+
+impl MeteredOp {
+    /// Key-registry purpose the endpoint resolves its key id with.
+    fn purpose(self) -> &'static str {
+        match self {
+            MeteredOp::DeriveDataKey | MeteredOp::DeriveRecoveryKey => "db",
+            MeteredOp::DeriveIbeKey => "ibe",
+        }
+    }
+}
+
+pub type CostEstimate = common::CostEstimate<MeteredOp>;
+pub type MeteringError = common::MeteringError<MeteredOp>;
+
+thread_local! {
+    static METER: RefCell<Meter<Memory>> = RefCell::new(Meter::init(
+        memory(layout::CYCLE_BALANCES),
+        memory(layout::METERING_CONFIG),
+    ));
+}
+
+/// Enables metering on a fresh install. Upgrades keep the stored config, which
+/// stays at the disabled default on canisters that predate metering.
+pub(crate) fn enable_on_install() {
+    METER.with(|m| {
+        let mut m = m.borrow_mut();
+        let config = MeteringConfig { enabled: true, ..m.config() };
+        m.set_config(config).expect("stored metering config is valid");
+    });
+}
+
+/// Debits `payer` for one `op` derivation with `key_id`. Returns the amount
+/// debited, which is 0 for unmetered callers, or the `MeteringError` if the
+/// balance is too low.
+pub(crate) fn charge(
+    payer: Principal,
+    op: MeteredOp,
+    key_id: &VetKDKeyId,
+) -> Result<u128, MeteringError> {
+    METER.with(|m| m.borrow_mut().charge(payer, op, key_id))
+}
+
+/// [`charge`] for a `manual_reply` endpoint that returns
+/// `Result<T, MeteringError>`: replies with the `Err` and returns `None` if
+/// the balance is too low. Charge before changing any state, since a refusal
+/// commits whatever the call did so far.
+pub(crate) fn charge_or_reply<T: CandidType>(
+    payer: Principal,
+    op: MeteredOp,
+    key_id: &VetKDKeyId,
+) -> Option<u128> {
+    match charge(payer, op, key_id) {
+        Ok(charged) => Some(charged),
+        Err(e) => {
+            reply(Err::<T, _>(e));
+            None
+        }
+    }
+}
+
+fn reply<T: CandidType>(result: Result<T, MeteringError>) {
+    ic_cdk::api::msg_reply(candid::encode_one(result).expect("failed to encode reply"));
+}
+
+/// Finishes a `manual_reply` endpoint that was charged `charged` cycles:
+/// replies with `Ok` and the derived value, or credits the charge back to
+/// `payer` and rejects. The charge was committed at the derivation's await, so
+/// a trap here would roll back the refund but keep the charge.
+pub(crate) fn reply_or_refund<T: CandidType>(
+    payer: Principal,
+    charged: u128,
+    result: Result<T, String>,
+) {
+    match result {
+        Ok(value) => reply(Ok(value)),
+        Err(e) => {
+            if charged > 0 {
+                METER.with(|m| m.borrow_mut().credit(payer, charged));
+            }
+            ic_cdk::api::msg_reject(e);
+        }
+    }
+}
+
+// ── Metering API ──────────────────────────────────────────────────────────
+/// Accepts all attached cycles and credits them to `beneficiary` (default:
+/// the caller). Returns the new balance.
+#[update]
+fn top_up(beneficiary: Option<Principal>) -> u128 {
+    let accepted = ic_cdk::api::msg_cycles_accept(ic_cdk::api::msg_cycles_available());
+    if accepted == 0 {
+        ic_cdk::trap("no cycles attached");
+    }
+    let beneficiary = beneficiary.unwrap_or_else(ic_cdk::api::caller);
+    METER.with(|m| m.borrow_mut().credit(beneficiary, accepted))
+}
+
+/// Credits `amount` cycles to `principal` without payment (controllers only).
+#[update]
+fn grant_cycles(principal: Principal, amount: u128) -> u128 {
+    require_controller();
+    let balance = METER.with(|m| m.borrow_mut().credit(principal, amount));
+    audit::record(
+        ic_cdk::api::caller(),
+        AuditKind::MeteringChanged,
+        &principal.to_text(),
+        format!("granted {amount} cycles"),
+    );
+    balance
+}
+
+/// Cycles balance of the caller.
+#[query]
+fn cycles_balance() -> u128 {
+    METER.with(|m| m.borrow().balance_of(ic_cdk::api::caller()))
+}
+
+/// What one `op` call would cost the caller now, with the key it would use
+/// (`key` selects an alias, as on the endpoint itself).
+#[query]
+fn get_cost_estimate(op: MeteredOp, key: Option<String>) -> CostEstimate {
+    let caller = ic_cdk::api::caller();
+    let tenant = (op != MeteredOp::DeriveIbeKey).then_some(caller);
+    let key_id = keys::resolve(key, tenant, op.purpose());
+    METER.with(|m| m.borrow().estimate(caller, op, key_id))
+}
+
+#[query]
+fn get_metering_config() -> MeteringConfig {
+    METER.with(|m| m.borrow().config())
+}
+
+/// Replaces the metering configuration (controllers only).
+#[update]
+fn set_metering_config(new: MeteringConfig) {
+    require_controller();
+    let detail = format!("enabled={} margin_percent={}", new.enabled, new.margin_percent);
+    METER.with(|m| m.borrow_mut().set_config(new)).unwrap_or_else(|e| ic_cdk::trap(&e.to_string()));
+    audit::record(ic_cdk::api::caller(), AuditKind::MeteringChanged, "config", detail);
+}
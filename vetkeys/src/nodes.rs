@@ -0,0 +1,261 @@
+//! TEE node registry and attestation health.
+//!
+//! Controllers register node principals; each node must then submit fresh
+//! attestation evidence at least once per `attestation_interval_secs`. A
+//! periodic timer moves overdue `Active` nodes to `Quarantined`, which blocks
+//! `derive_data_key` and record writes for that principal until it re-attests.
+//! Callers that are not registered nodes are unaffected.
+//!
+//! The evidence is not verified: it is stored as-is, so a submission only
+//! proves that the node's principal is still live. It is a heartbeat, not
+//! remote attestation; quote verification happens off-chain.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk_macros::*;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use std::{cell::RefCell, time::Duration};
+
+use crate::audit::{self, AuditKind};
+use crate::{layout, memory, require_controller, Memory};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// Default deadline between two attestations of the same node.
+const DEFAULT_ATTESTATION_INTERVAL_SECS: u64 = 24 * 60 * 60;
+/// How often the health sweep runs.
+const HEALTH_CHECK_PERIOD: Duration = Duration::from_secs(5 * 60);
+/// Largest evidence blob accepted by `submit_attestation`.
+const MAX_EVIDENCE_BYTES: usize = 16 * 1024;
+
+#[derive(Clone, Copy, CandidType, Deserialize, PartialEq, Eq, Debug)]
+pub enum NodeStatus {
+    /// Registered but no evidence submitted yet.
+    Pending,
+    Active,
+    /// Missed its attestation deadline; restored by fresh evidence.
+    Quarantined,
+    /// Removed by a controller; cannot re-attest.
+    Deactivated,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct NodeRecord {
+    pub node_id: String,
+    pub principal: Principal,
+    pub status: NodeStatus,
+    pub registered_at: u64,
+    pub last_attested_at: Option<u64>,
+    pub last_evidence: Vec<u8>,
+}
+
+candid_storable!(NodeRecord);
+
+#[derive(Clone, CandidType, Deserialize)]
+struct NodeConfig {
+    attestation_interval_secs: u64,
+}
+
+candid_storable!(NodeConfig);
+
+#[derive(CandidType, Deserialize)]
+pub struct NodeHealth {
+    pub node_id: String,
+    pub principal: Principal,
+    pub status: NodeStatus,
+    pub last_attested_at: Option<u64>,
+    /// Time by which the node must re-attest; `None` while pending or deactivated.
+    pub deadline: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct NodeHealthReport {
+    pub now: u64,
+    pub attestation_interval_secs: u64,
+    pub nodes: Vec<NodeHealth>,
+}
+
+thread_local! {
+    static NODES: RefCell<StableBTreeMap<Vec<u8>, NodeRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::NODES)));
+
+    static CONFIG: RefCell<StableCell<NodeConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            memory(layout::NODE_CONFIG),
+            NodeConfig { attestation_interval_secs: DEFAULT_ATTESTATION_INTERVAL_SECS },
+        )
+        .expect("failed to init node config cell"),
+    );
+}
+
+fn interval_secs() -> u64 {
+    CONFIG.with(|c| c.borrow().get().attestation_interval_secs)
+}
+
+fn deadline(node: &NodeRecord, interval_secs: u64) -> Option<u64> {
+    match node.status {
+        NodeStatus::Active | NodeStatus::Quarantined => node
+            .last_attested_at
+            .map(|t| t.saturating_add(interval_secs.saturating_mul(NANOS_PER_SEC))),
+        NodeStatus::Pending | NodeStatus::Deactivated => None,
+    }
+}
+
+fn set_status(key: &[u8], mut node: NodeRecord, status: NodeStatus, actor: Principal, why: &str) {
+    let from = node.status;
+    node.status = status;
+    let subject = node.node_id.clone();
+    NODES.with(|n| n.borrow_mut().insert(key.to_vec(), node));
+    audit::record(
+        actor,
+        AuditKind::NodeStatusChanged,
+        &subject,
+        format!("{from:?} -> {status:?}: {why}"),
+    );
+}
+
+/// Status of `principal` if it is a registered node.
+pub(crate) fn status_of(principal: Principal) -> Option<NodeStatus> {
+    node_of(principal).map(|r| r.status)
+}
+
+/// The full record of `principal` if it is a registered node.
+pub(crate) fn node_of(principal: Principal) -> Option<NodeRecord> {
+    NODES.with(|n| n.borrow().get(&principal.as_slice().to_vec()))
+}
+
+/// Traps if `caller` is a registered node that is not currently `Active`, or
+/// is past its deadline but not yet swept.
+pub(crate) fn ensure_not_quarantined(caller: Principal) {
+    let Some(node) = node_of(caller) else { return };
+    let overdue = deadline(&node, interval_secs()).is_some_and(|d| d < ic_cdk::api::time());
+    match node.status {
+        NodeStatus::Active if !overdue => {}
+        NodeStatus::Active => ic_cdk::trap("node is overdue; submit fresh attestation evidence"),
+        s => ic_cdk::trap(&format!("node is {s:?}; submit fresh attestation evidence")),
+    }
+}
+
+/// Moves every `Active` node past its deadline to `Quarantined`.
+fn sweep() {
+    let now = ic_cdk::api::time();
+    let interval = interval_secs();
+    let overdue: Vec<(Vec<u8>, NodeRecord)> = NODES.with(|n| {
+        n.borrow()
+            .iter()
+            .filter(|(_, r)| r.status == NodeStatus::Active)
+            .filter(|(_, r)| deadline(r, interval).is_some_and(|d| d < now))
+            .collect()
+    });
+    for (key, node) in overdue {
+        set_status(&key, node, NodeStatus::Quarantined, ic_cdk::api::id(), "attestation overdue");
+    }
+}
+
+pub(crate) fn start_health_timer() {
+    ic_cdk_timers::set_timer_interval(HEALTH_CHECK_PERIOD, sweep);
+}
+
+// ── Node API ──────────────────────────────────────────────────────────────
+/// Registers `principal` as TEE node `node_id` (controllers only).
+/// The node stays `Pending` until its first `submit_attestation`.
+#[update]
+fn register_node(node_id: String, principal: Principal) {
+    require_controller();
+    if node_id.is_empty() {
+        ic_cdk::trap("node_id must not be empty");
+    }
+    let key = principal.as_slice().to_vec();
+    if NODES.with(|n| n.borrow().contains_key(&key)) {
+        ic_cdk::trap("principal is already registered as a node");
+    }
+    // node ids name IBE identities (`node|<node_id>`), so they must be unique
+    if NODES.with(|n| n.borrow().iter().any(|(_, r)| r.node_id == node_id)) {
+        ic_cdk::trap("node_id is already taken");
+    }
+    let record = NodeRecord {
+        node_id: node_id.clone(),
+        principal,
+        status: NodeStatus::Pending,
+        registered_at: ic_cdk::api::time(),
+        last_attested_at: None,
+        last_evidence: Vec::new(),
+    };
+    NODES.with(|n| n.borrow_mut().insert(key, record));
+    audit::record(ic_cdk::api::caller(), AuditKind::NodeRegistered, &node_id, principal.to_text());
+}
+
+/// Submits fresh attestation evidence for the calling node, restoring it to `Active`.
+///
+/// The evidence is stored unverified (1..=16 KiB), so this is a liveness
+/// heartbeat from the node's principal, not proof that it runs in a TEE.
+#[update]
+fn submit_attestation(evidence: Vec<u8>) {
+    if evidence.is_empty() || evidence.len() > MAX_EVIDENCE_BYTES {
+        ic_cdk::trap(&format!("evidence must be 1..={MAX_EVIDENCE_BYTES} bytes"));
+    }
+    let caller = ic_cdk::api::caller();
+    let key = caller.as_slice().to_vec();
+    let mut node = NODES
+        .with(|n| n.borrow().get(&key))
+        .unwrap_or_else(|| ic_cdk::trap("caller is not a registered node"));
+    if node.status == NodeStatus::Deactivated {
+        ic_cdk::trap("node is deactivated");
+    }
+    node.last_attested_at = Some(ic_cdk::api::time());
+    node.last_evidence = evidence;
+    audit::record(caller, AuditKind::NodeAttested, &node.node_id, String::new());
+    if node.status == NodeStatus::Active {
+        NODES.with(|n| n.borrow_mut().insert(key, node));
+    } else {
+        set_status(&key, node, NodeStatus::Active, caller, "fresh evidence");
+    }
+}
+
+/// Permanently deactivates a node (controllers only).
+#[update]
+fn deactivate_node(principal: Principal) {
+    require_controller();
+    let key = principal.as_slice().to_vec();
+    let node = NODES
+        .with(|n| n.borrow().get(&key))
+        .unwrap_or_else(|| ic_cdk::trap("unknown node"));
+    let caller = ic_cdk::api::caller();
+    set_status(&key, node, NodeStatus::Deactivated, caller, "deactivated by controller");
+}
+
+/// Sets the maximum age of a node's latest attestation (controllers only).
+#[update]
+fn set_attestation_interval(secs: u64) {
+    require_controller();
+    if secs == 0 {
+        ic_cdk::trap("attestation interval must be positive");
+    }
+    let old = interval_secs();
+    CONFIG.with(|c| {
+        c.borrow_mut()
+            .set(NodeConfig { attestation_interval_secs: secs })
+            .expect("failed to persist node config");
+    });
+    let caller = ic_cdk::api::caller();
+    let detail = format!("{old}s -> {secs}s");
+    audit::record(caller, AuditKind::NodeConfigChanged, "attestation_interval", detail);
+}
+
+/// Per-node attestation status and deadlines (controllers only).
+#[query]
+fn get_node_health() -> NodeHealthReport {
+    require_controller();
+    let interval = interval_secs();
+    let nodes = NODES.with(|n| {
+        n.borrow()
+            .iter()
+            .map(|(_, r)| NodeHealth {
+                deadline: deadline(&r, interval),
+                node_id: r.node_id,
+                principal: r.principal,
+                status: r.status,
+                last_attested_at: r.last_attested_at,
+            })
+            .collect()
+    });
+    NodeHealthReport { now: ic_cdk::api::time(), attestation_interval_secs: interval, nodes }
+}
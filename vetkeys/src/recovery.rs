@@ -0,0 +1,310 @@
+//! Threshold-approved recovery of a failed node's records.
+//!
+//! A replacement node opens a recovery request for a failed (non-`Active`)
+//! node. The request must collect `threshold` approvals from `Active` nodes or
+//! controllers before `approval_window_secs` elapses. Once approved, the
+//! replacement may, for `access_window_secs`, read the failed node's stored
+//! envelopes and derive the data keys they were sealed with. Nobody else ever
+//! receives a node's data keys.
+//!
+//! Requests past their approval deadline read as `Expired` everywhere, but the
+//! status is only persisted (and audited) by update calls.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk_macros::*;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use std::cell::RefCell;
+
+use crate::audit::{self, AuditKind};
+use crate::keys;
+use crate::metering::MeteredOp;
+use crate::nodes::{self, NodeStatus};
+use crate::{layout, memory, require_controller, Memory};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+#[derive(Clone, Copy, CandidType, Deserialize, PartialEq, Eq, Debug)]
+pub enum RecoveryStatus {
+    Open,
+    Approved,
+    Expired,
+    Cancelled,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct RecoveryRequest {
+    pub id: u64,
+    /// Principal of the failed node whose namespace is being recovered.
+    pub failed: Principal,
+    pub replacement: Principal,
+    pub status: RecoveryStatus,
+    pub threshold: u32,
+    pub approvals: Vec<Principal>,
+    pub opened_at: u64,
+    /// Deadline for collecting approvals.
+    pub approval_deadline: u64,
+    /// Set once approved: the replacement may recover until this time.
+    pub access_until: Option<u64>,
+}
+
+candid_storable!(RecoveryRequest);
+
+#[derive(Clone, CandidType, Deserialize)]
+pub struct RecoveryConfig {
+    /// Approvals required (M of the N eligible nodes and controllers).
+    pub threshold: u32,
+    pub approval_window_secs: u64,
+    pub access_window_secs: u64,
+}
+
+candid_storable!(RecoveryConfig);
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        RecoveryConfig {
+            threshold: 2,
+            approval_window_secs: 24 * 60 * 60,
+            access_window_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+thread_local! {
+    static REQUESTS: RefCell<StableBTreeMap<u64, RecoveryRequest, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::RECOVERY_REQUESTS)));
+
+    static CONFIG: RefCell<StableCell<RecoveryConfig, Memory>> = RefCell::new(
+        StableCell::init(memory(layout::RECOVERY_CONFIG), RecoveryConfig::default())
+            .expect("failed to init recovery config cell"),
+    );
+}
+
+fn config() -> RecoveryConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+fn save(req: RecoveryRequest) {
+    REQUESTS.with(|r| r.borrow_mut().insert(req.id, req));
+}
+
+fn set_status(mut req: RecoveryRequest, status: RecoveryStatus, actor: Principal) {
+    let from = req.status;
+    req.status = status;
+    let subject = req.failed.to_text();
+    let detail = format!("request {}: {from:?} -> {status:?}", req.id);
+    save(req);
+    audit::record(actor, AuditKind::RecoveryStatusChanged, &subject, detail);
+}
+
+fn is_expired(req: &RecoveryRequest) -> bool {
+    req.status == RecoveryStatus::Open && ic_cdk::api::time() > req.approval_deadline
+}
+
+/// The request as it currently stands, `Expired` if its approval deadline has
+/// passed. Does not write, so queries can use it.
+fn view(req: RecoveryRequest) -> RecoveryRequest {
+    if is_expired(&req) {
+        return RecoveryRequest { status: RecoveryStatus::Expired, ..req };
+    }
+    req
+}
+
+fn get(id: u64) -> RecoveryRequest {
+    REQUESTS
+        .with(|r| r.borrow().get(&id))
+        .unwrap_or_else(|| ic_cdk::trap("unknown recovery request"))
+}
+
+/// Loads a request for an update call, persisting its expiry first if its
+/// approval deadline has passed.
+fn load(id: u64) -> RecoveryRequest {
+    let req = get(id);
+    if is_expired(&req) {
+        set_status(req.clone(), RecoveryStatus::Expired, ic_cdk::api::id());
+    }
+    view(req)
+}
+
+fn is_eligible_approver(p: Principal) -> bool {
+    ic_cdk::api::is_controller(&p) || nodes::status_of(p) == Some(NodeStatus::Active)
+}
+
+/// Returns the request if `caller` currently holds recovery access through it.
+/// Read-only; an approved request never expires, so `view` is enough.
+fn granted(id: u64, caller: Principal) -> RecoveryRequest {
+    let req = view(get(id));
+    if req.replacement != caller {
+        ic_cdk::trap("caller is not the replacement node of this request");
+    }
+    nodes::ensure_not_quarantined(caller);
+    match (req.status, req.access_until) {
+        (RecoveryStatus::Approved, Some(until)) if ic_cdk::api::time() <= until => req,
+        (RecoveryStatus::Approved, _) => ic_cdk::trap("recovery access window has closed"),
+        (s, _) => ic_cdk::trap(&format!("recovery request is {s:?}")),
+    }
+}
+
+// ── Recovery API ──────────────────────────────────────────────────────────
+/// Opens a recovery request for `failed`; the caller becomes the replacement.
+#[update]
+fn open_recovery(failed: Principal) -> u64 {
+    let caller = ic_cdk::api::caller();
+    if nodes::status_of(caller) != Some(NodeStatus::Active) {
+        ic_cdk::trap("only active nodes can open a recovery request");
+    }
+    match nodes::status_of(failed) {
+        None => ic_cdk::trap("failed principal is not a registered node"),
+        Some(NodeStatus::Active) => ic_cdk::trap("node is active; nothing to recover"),
+        Some(_) => {}
+    }
+    if failed == caller {
+        ic_cdk::trap("a node cannot recover itself");
+    }
+    let cfg = config();
+    let now = ic_cdk::api::time();
+    let window = cfg.approval_window_secs.saturating_mul(NANOS_PER_SEC);
+    let id = REQUESTS.with(|r| r.borrow().last_key_value().map(|(k, _)| k + 1).unwrap_or(0));
+    save(RecoveryRequest {
+        id,
+        failed,
+        replacement: caller,
+        status: RecoveryStatus::Open,
+        threshold: cfg.threshold,
+        approvals: Vec::new(),
+        opened_at: now,
+        approval_deadline: now.saturating_add(window),
+        access_until: None,
+    });
+    audit::record(caller, AuditKind::RecoveryOpened, &failed.to_text(), format!("request {id}"));
+    id
+}
+
+/// Approves an open request (active nodes and controllers, once each).
+#[update]
+fn approve_recovery(id: u64) -> RecoveryStatus {
+    let caller = ic_cdk::api::caller();
+    if !is_eligible_approver(caller) {
+        ic_cdk::trap("only active nodes or controllers can approve recoveries");
+    }
+    let mut req = load(id);
+    if req.status != RecoveryStatus::Open {
+        ic_cdk::trap(&format!("recovery request is {:?}", req.status));
+    }
+    if caller == req.replacement || caller == req.failed {
+        ic_cdk::trap("parties to a recovery cannot approve it");
+    }
+    if req.approvals.contains(&caller) {
+        ic_cdk::trap("already approved");
+    }
+    req.approvals.push(caller);
+    audit::record(
+        caller,
+        AuditKind::RecoveryApproved,
+        &req.failed.to_text(),
+        format!("request {id}: {}/{}", req.approvals.len(), req.threshold),
+    );
+    if req.approvals.len() as u32 >= req.threshold {
+        let access = config().access_window_secs.saturating_mul(NANOS_PER_SEC);
+        req.access_until = Some(ic_cdk::api::time().saturating_add(access));
+        set_status(req, RecoveryStatus::Approved, caller);
+        RecoveryStatus::Approved
+    } else {
+        save(req);
+        RecoveryStatus::Open
+    }
+}
+
+/// Cancels a request (its replacement or a controller).
+#[update]
+fn cancel_recovery(id: u64) {
+    let caller = ic_cdk::api::caller();
+    let req = load(id);
+    if caller != req.replacement && !ic_cdk::api::is_controller(&caller) {
+        ic_cdk::trap("only the replacement node or a controller can cancel");
+    }
+    if matches!(req.status, RecoveryStatus::Expired | RecoveryStatus::Cancelled) {
+        ic_cdk::trap(&format!("recovery request is {:?}", req.status));
+    }
+    set_status(req, RecoveryStatus::Cancelled, caller);
+}
+
+/// Derives `owner`'s data key for a record, encrypted to `transport_pk`: the
+/// key `owner` got from `derive_data_key` (same context, input and key
+/// resolution), so it opens the envelopes `owner` stored.
+///
+/// `owner` may always derive its own keys; anyone else must pass the id of an
+/// approved request for `owner`, and each key it derives this way is audited.
+/// Replies with `Result<EncryptedKey, MeteringError>`.
+#[update(manual_reply = true)]
+async fn derive_recovery_key(
+    owner: Principal,
+    request_id: Option<u64>,
+    record_id: Vec<u8>,
+    transport_pk: Vec<u8>,
+    key: Option<String>,
+) {
+    crate::check_transport_pk(&transport_pk);
+    let caller = ic_cdk::api::caller();
+    let mut audited = None;
+    if caller == owner {
+        nodes::ensure_not_quarantined(caller);
+    } else {
+        let id = request_id.unwrap_or_else(|| ic_cdk::trap("request_id required"));
+        let req = granted(id, caller);
+        if req.failed != owner {
+            ic_cdk::trap("recovery request does not cover this owner");
+        }
+        audited = Some(id);
+    }
+    let derived = crate::reply_with_encrypted_key(
+        MeteredOp::DeriveRecoveryKey,
+        keys::resolve_for_derivation(key, Some(owner), "db"),
+        crate::context(owner),
+        crate::data_key_input(&record_id),
+        transport_pk,
+    )
+    .await;
+    if let (true, Some(id)) = (derived, audited) {
+        let detail = format!("request {id}");
+        audit::record(caller, AuditKind::RecoveryKeyDerived, &owner.to_text(), detail);
+    }
+}
+
+/// Record ids stored by the failed node of an approved request.
+#[query]
+fn list_recovered_record_ids(request_id: u64) -> Vec<Vec<u8>> {
+    let req = granted(request_id, ic_cdk::api::caller());
+    crate::record_ids_of(req.failed)
+}
+
+/// An envelope stored by the failed node of an approved request.
+#[query]
+fn get_recovered_record(request_id: u64, record_id: Vec<u8>) -> Option<Vec<u8>> {
+    let req = granted(request_id, ic_cdk::api::caller());
+    crate::record_of(req.failed, record_id)
+}
+
+#[query]
+fn get_recovery_request(id: u64) -> Option<RecoveryRequest> {
+    REQUESTS.with(|r| r.borrow().get(&id)).map(view)
+}
+
+#[query]
+fn list_recovery_requests() -> Vec<RecoveryRequest> {
+    REQUESTS.with(|r| r.borrow().iter().map(|(_, v)| view(v)).collect())
+}
+
+#[query]
+fn get_recovery_config() -> RecoveryConfig {
+    config()
+}
+
+/// Updates quorum size and windows (controllers only). Applies to new requests.
+#[update]
+fn set_recovery_config(cfg: RecoveryConfig) {
+    require_controller();
+    if cfg.threshold == 0 {
+        ic_cdk::trap("threshold must be at least 1");
+    }
+    CONFIG.with(|c| c.borrow_mut().set(cfg).expect("failed to persist recovery config"));
+}
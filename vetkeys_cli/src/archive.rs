@@ -0,0 +1,67 @@
+//! JSON archive written by `export` and read by `import`.
+//!
+//! By default records are exported as their stored envelopes, which stay
+//! encrypted and only open for the same owner and record id. With
+//! `--plaintext` the records are decrypted locally, so the archive can be
+//! imported under another identity. Handle such archives as secrets.
+
+use serde::{Deserialize, Serialize};
+
+pub const ARCHIVE_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Archive {
+    pub version: u8,
+    /// Principal (text) that owned the records when exported.
+    pub owner: String,
+    pub canister_id: String,
+    pub records: Vec<ArchivedRecord>,
+}
+
+/// One record; exactly one of `envelope_hex` and `data_hex` is set.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ArchivedRecord {
+    pub record_id_hex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envelope_hex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_hex: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let archive = Archive {
+            version: ARCHIVE_VERSION,
+            owner: "2vxsx-fae".into(),
+            canister_id: "rrkah-fqaaa-aaaaa-aaaaq-cai".into(),
+            records: vec![
+                ArchivedRecord {
+                    record_id_hex: "6131".into(),
+                    envelope_hex: Some("01ff".into()),
+                    data_hex: None,
+                },
+                ArchivedRecord {
+                    record_id_hex: "6132".into(),
+                    envelope_hex: None,
+                    data_hex: Some("00".into()),
+                },
+            ],
+        };
+        let json = serde_json::to_string(&archive).unwrap();
+        assert!(!json.contains("null"), "unset fields are omitted: {json}");
+        assert_eq!(serde_json::from_str::<Archive>(&json).unwrap(), archive);
+    }
+
+    #[test]
+    fn missing_optional_fields_default_to_none() {
+        let json =
+            r#"{"version":1,"owner":"o","canister_id":"c","records":[{"record_id_hex":"61"}]}"#;
+        let archive: Archive = serde_json::from_str(json).unwrap();
+        assert_eq!(archive.records[0].envelope_hex, None);
+        assert_eq!(archive.records[0].data_hex, None);
+    }
+}
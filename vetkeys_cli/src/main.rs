@@ -0,0 +1,351 @@
+//! `vetkeys`: operate the Dooor vetkeys private DB from a TEE or an admin
+//! workstation.
+//!
+//! Every value is encrypted and decrypted locally through VetKD (see
+//! `vetkeys-client`); the canister only ever sees envelopes. Record ids are
+//! UTF-8 strings, or hex when prefixed with `0x`.
+
+use anyhow::{bail, Context};
+use candid::Principal;
+use clap::{Parser, Subcommand};
+use ic_agent::identity::{AnonymousIdentity, BasicIdentity, Secp256k1Identity};
+use ic_agent::{Agent, Identity};
+use serde_json::json;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use vetkeys_client::{envelope, Client, EnvelopeKey};
+
+mod archive;
+
+use archive::{Archive, ArchivedRecord, ARCHIVE_VERSION};
+
+#[derive(Parser)]
+#[command(name = "vetkeys", version, about = "Encrypted records in the vetkeys canister")]
+struct Cli {
+    /// Replica URL.
+    #[arg(long, env = "VETKEYS_URL", default_value = "http://127.0.0.1:4943")]
+    url: String,
+
+    /// Canister id of the vetkeys canister.
+    #[arg(long, env = "VETKEYS_CANISTER_ID")]
+    canister: Principal,
+
+    /// Identity PEM file (secp256k1 or Ed25519); anonymous if omitted.
+    #[arg(long, env = "VETKEYS_IDENTITY")]
+    identity: Option<PathBuf>,
+
+    /// Key alias from the canister's key registry.
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Fetch the root key (never on mainnet). Implied for local URLs.
+    #[arg(long)]
+    fetch_root_key: bool,
+
+    /// Print machine-readable JSON instead of human-readable text.
+    #[arg(long)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encrypt a file (or stdin) and store it.
+    Put {
+        record_id: String,
+        /// Input file; stdin if omitted.
+        #[arg(long, short)]
+        file: Option<PathBuf>,
+    },
+    /// Fetch and decrypt a record.
+    Get {
+        record_id: String,
+        /// Output file; stdout if omitted.
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// List the caller's record ids.
+    List,
+    /// Delete a record.
+    Delete { record_id: String },
+    /// Derive and print a record's VetKD data key (sensitive).
+    DeriveKey { record_id: String },
+    /// Export all records to a JSON archive.
+    Export {
+        #[arg(long, short)]
+        out: PathBuf,
+        /// Decrypt records into the archive instead of keeping envelopes.
+        #[arg(long)]
+        plaintext: bool,
+    },
+    /// Import records from a JSON archive.
+    Import {
+        file: PathBuf,
+        /// Replace records that already exist.
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let agent = Agent::builder()
+        .with_url(&cli.url)
+        .with_boxed_identity(load_identity(cli.identity.as_deref())?)
+        .build()
+        .context("building agent")?;
+    if cli.fetch_root_key || is_local_url(&cli.url) {
+        agent.fetch_root_key().await.context("fetching root key")?;
+    }
+    let mut client = Client::new(agent, cli.canister)?;
+    if let Some(key) = &cli.key {
+        client = client.with_key(key.clone());
+    }
+    let out = Output { json: cli.json };
+
+    match cli.command {
+        Command::Put { record_id, file } => {
+            let rid = parse_record_id(&record_id)?;
+            let data = read_input(file.as_deref())?;
+            client.put(&rid, &data).await?;
+            out.emit(
+                json!({ "record_id": record_id, "bytes": data.len() }),
+                format!("stored {} bytes at {record_id}", data.len()),
+            );
+        }
+        Command::Get { record_id, out: path } => {
+            let rid = parse_record_id(&record_id)?;
+            let Some(data) = client.get(&rid).await? else {
+                bail!("record {record_id} not found");
+            };
+            match (path, out.json) {
+                (Some(path), _) => {
+                    std::fs::write(&path, &data)
+                        .with_context(|| format!("writing {}", path.display()))?;
+                    out.emit(
+                        json!({ "record_id": record_id, "bytes": data.len(), "out": path }),
+                        format!("wrote {} bytes to {}", data.len(), path.display()),
+                    );
+                }
+                (None, true) => out.emit(
+                    json!({ "record_id": record_id, "data_hex": hex::encode(&data) }),
+                    String::new(),
+                ),
+                (None, false) => std::io::stdout().write_all(&data)?,
+            }
+        }
+        Command::List => {
+            let ids = client.list().await?;
+            let shown: Vec<String> = ids.iter().map(|id| show_record_id(id)).collect();
+            out.emit(json!({ "record_ids": shown }), shown.join("\n"));
+        }
+        Command::Delete { record_id } => {
+            let deleted = client.delete(&parse_record_id(&record_id)?).await?;
+            let human = match deleted {
+                true => format!("deleted {record_id}"),
+                false => format!("{record_id} not found"),
+            };
+            out.emit(json!({ "record_id": record_id, "deleted": deleted }), human);
+        }
+        Command::DeriveKey { record_id } => {
+            let key = client.data_key(&parse_record_id(&record_id)?).await?;
+            let key_hex = hex::encode(key.signature_bytes());
+            out.emit(json!({ "record_id": record_id, "data_key_hex": key_hex }), key_hex);
+        }
+        Command::Export { out: path, plaintext } => {
+            let archive = export(&client, plaintext).await?;
+            let count = archive.records.len();
+            std::fs::write(&path, serde_json::to_vec_pretty(&archive)?)
+                .with_context(|| format!("writing {}", path.display()))?;
+            out.emit(
+                json!({ "records": count, "out": path, "plaintext": plaintext }),
+                format!("exported {count} records to {}", path.display()),
+            );
+        }
+        Command::Import { file, overwrite } => {
+            let archive: Archive = serde_json::from_slice(
+                &std::fs::read(&file).with_context(|| format!("reading {}", file.display()))?,
+            )?;
+            let (imported, skipped) = import(&client, archive, overwrite).await?;
+            out.emit(
+                json!({ "imported": imported, "skipped": skipped }),
+                format!("imported {imported} records, skipped {skipped} existing"),
+            );
+        }
+    }
+    Ok(())
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn emit(&self, value: serde_json::Value, human: String) {
+        if self.json {
+            println!("{value}");
+        } else if !human.is_empty() {
+            println!("{human}");
+        }
+    }
+}
+
+fn load_identity(path: Option<&Path>) -> anyhow::Result<Box<dyn Identity>> {
+    let Some(path) = path else {
+        return Ok(Box::new(AnonymousIdentity));
+    };
+    let pem = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    if let Ok(id) = Secp256k1Identity::from_pem(pem.as_slice()) {
+        return Ok(Box::new(id));
+    }
+    let id = BasicIdentity::from_pem(pem.as_slice())
+        .with_context(|| format!("{} is not a secp256k1 or Ed25519 PEM", path.display()))?;
+    Ok(Box::new(id))
+}
+
+/// Whether `url` points at a replica on this machine, whose root key must be
+/// fetched.
+fn is_local_url(url: &str) -> bool {
+    let authority = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = authority.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+fn parse_record_id(s: &str) -> anyhow::Result<Vec<u8>> {
+    match s.strip_prefix("0x") {
+        Some(h) => hex::decode(h).context("invalid hex record id"),
+        None => Ok(s.as_bytes().to_vec()),
+    }
+}
+
+fn show_record_id(id: &[u8]) -> String {
+    match std::str::from_utf8(id) {
+        Ok(s) if !s.starts_with("0x") && !s.chars().any(char::is_control) => s.to_string(),
+        _ => format!("0x{}", hex::encode(id)),
+    }
+}
+
+fn read_input(file: Option<&Path>) -> anyhow::Result<Vec<u8>> {
+    match file {
+        Some(path) => std::fs::read(path).with_context(|| format!("reading {}", path.display())),
+        None => {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+async fn export(client: &Client, plaintext: bool) -> anyhow::Result<Archive> {
+    let mut records = Vec::new();
+    for rid in client.list().await? {
+        let Some(sealed) = client.canister().get_record(&rid).await? else {
+            continue;
+        };
+        let entry = if plaintext {
+            let key = EnvelopeKey::from_vetkey(&client.data_key(&rid).await?);
+            let data = envelope::open(&key, client.owner(), &rid, &sealed)
+                .with_context(|| format!("opening {}", show_record_id(&rid)))?;
+            ArchivedRecord {
+                record_id_hex: hex::encode(&rid),
+                envelope_hex: None,
+                data_hex: Some(hex::encode(data)),
+            }
+        } else {
+            ArchivedRecord {
+                record_id_hex: hex::encode(&rid),
+                envelope_hex: Some(hex::encode(sealed)),
+                data_hex: None,
+            }
+        };
+        records.push(entry);
+    }
+    Ok(Archive {
+        version: ARCHIVE_VERSION,
+        owner: client.owner().to_text(),
+        canister_id: client.canister().canister_id().to_text(),
+        records,
+    })
+}
+
+/// Returns `(imported, skipped)`.
+async fn import(
+    client: &Client,
+    archive: Archive,
+    overwrite: bool,
+) -> anyhow::Result<(usize, usize)> {
+    if archive.version != ARCHIVE_VERSION {
+        bail!("unsupported archive version {}", archive.version);
+    }
+    let existing = client.list().await?;
+    let (mut imported, mut skipped) = (0, 0);
+    for entry in archive.records {
+        let rid = hex::decode(&entry.record_id_hex).context("invalid record_id_hex")?;
+        if !overwrite && existing.contains(&rid) {
+            skipped += 1;
+            continue;
+        }
+        match (entry.envelope_hex, entry.data_hex) {
+            (Some(sealed), None) => {
+                let sealed = hex::decode(sealed).context("invalid envelope_hex")?;
+                // Envelopes are bound to their owner: check before storing.
+                let key = EnvelopeKey::from_vetkey(&client.data_key(&rid).await?);
+                envelope::open(&key, client.owner(), &rid, &sealed).with_context(|| {
+                    format!(
+                        "envelope for {} does not open for {}; export it with --plaintext",
+                        show_record_id(&rid),
+                        client.owner()
+                    )
+                })?;
+                client.canister().put_record(&rid, &sealed).await?;
+            }
+            (None, Some(data)) => {
+                client.put(&rid, &hex::decode(data).context("invalid data_hex")?).await?;
+            }
+            _ => bail!(
+                "record {} must have exactly one of envelope_hex and data_hex",
+                entry.record_id_hex
+            ),
+        }
+        imported += 1;
+    }
+    Ok((imported, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_ids_round_trip() {
+        for id in [b"cfg:db".as_slice(), b"0xab", b"a\nb", &[0xff, 0], b""] {
+            assert_eq!(parse_record_id(&show_record_id(id)).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn record_ids_show_as_text_or_hex() {
+        assert_eq!(show_record_id(b"cfg:db"), "cfg:db");
+        assert_eq!(show_record_id(b"0xab"), "0x30786162");
+        assert_eq!(show_record_id(&[0xff, 0]), "0xff00");
+        assert_eq!(parse_record_id("0xff00").unwrap(), vec![0xff, 0]);
+        assert!(parse_record_id("0xzz").is_err());
+    }
+
+    #[test]
+    fn local_urls_fetch_the_root_key() {
+        for url in ["http://127.0.0.1:4943", "http://localhost:8000/", "http://[::1]:4943"] {
+            assert!(is_local_url(url), "{url}");
+        }
+        for url in ["https://icp-api.io", "https://ic0.app", "http://localhost.example.com"] {
+            assert!(!is_local_url(url), "{url}");
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Typed wrapper over every method in `vetkeys.did`.
+//!
+//! Methods map one to one onto the canister interface and return the decoded
+//! Candid values; canister traps surface as [`Error::Agent`] and refused
+//! metered derivations as [`Error::Metering`]. Use
+//! [`crate::Client`] for the encrypted record workflow.
+
+use candid::{decode_one, encode_args, utils::ArgumentEncoder, CandidType, Principal};
+use ic_agent::Agent;
+use serde::de::DeserializeOwned;
+
+use crate::types::*;
+use crate::{Error, Result};
+
+/// Low-level handle on one `vetkeys` canister.
+#[derive(Clone)]
+pub struct VetkeysAgent {
+    agent: Agent,
+    canister_id: Principal,
+}
+
+impl VetkeysAgent {
+    /// Wraps `agent` (already configured with identity and, for local
+    /// replicas, the fetched root key) for the canister `canister_id`.
+    pub fn new(agent: Agent, canister_id: Principal) -> Self {
+        VetkeysAgent { agent, canister_id }
+    }
+
+    pub fn agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    pub fn canister_id(&self) -> Principal {
+        self.canister_id
+    }
+
+    /// Principal of the agent's identity, i.e. the record owner.
+    pub fn principal(&self) -> Result<Principal> {
+        self.agent.get_principal().map_err(Error::Identity)
+    }
+
+    async fn update<R: CandidType + DeserializeOwned>(
+        &self,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R> {
+        let bytes = self
+            .agent
+            .update(&self.canister_id, method)
+            .with_arg(encode_args(args)?)
+            .call_and_wait()
+            .await?;
+        Ok(decode_one(&bytes)?)
+    }
+
+    /// [`Self::update`] for a metered endpoint, which replies with
+    /// `Result<R, MeteringError>`.
+    async fn metered<R: CandidType + DeserializeOwned>(
+        &self,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R> {
+        self.update::<std::result::Result<R, MeteringError>>(method, args)
+            .await?
+            .map_err(Error::Metering)
+    }
+
+    async fn query<R: CandidType + DeserializeOwned>(
+        &self,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<R> {
+        let bytes = self
+            .agent
+            .query(&self.canister_id, method)
+            .with_arg(encode_args(args)?)
+            .call()
+            .await?;
+        Ok(decode_one(&bytes)?)
+    }
+
+    // ── VetKD / DB ─────────────────────────────────────────────────────────
+    pub async fn bls_public_key(&self, key: Option<&str>) -> Result<BlsPk> {
+        self.update("bls_public_key", (key,)).await
+    }
+
+    pub async fn derive_data_key(
+        &self,
+        record_id: &[u8],
+        transport_pk: &[u8],
+        key: Option<&str>,
+    ) -> Result<EncryptedKey> {
+        self.metered("derive_data_key", (record_id.to_vec(), transport_pk.to_vec(), key)).await
+    }
+
+    pub async fn put_record(&self, record_id: &[u8], envelope: &[u8]) -> Result<()> {
+        self.update("put_record", (record_id.to_vec(), envelope.to_vec())).await
+    }
+
+    pub async fn get_record(&self, record_id: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.query("get_record", (record_id.to_vec(),)).await
+    }
+
+    pub async fn list_record_ids(&self) -> Result<Vec<Vec<u8>>> {
+        self.query("list_record_ids", ()).await
+    }
+
+    pub async fn delete_record(&self, record_id: &[u8]) -> Result<bool> {
+        self.update("delete_record", (record_id.to_vec(),)).await
+    }
+
+    // ── Nodes and audit ───────────────────────────────────────────────────
+    pub async fn register_node(&self, node_id: &str, principal: Principal) -> Result<()> {
+        self.update("register_node", (node_id, principal)).await
+    }
+
+    pub async fn submit_attestation(&self, evidence: &[u8]) -> Result<()> {
+        self.update("submit_attestation", (evidence.to_vec(),)).await
+    }
+
+    pub async fn deactivate_node(&self, principal: Principal) -> Result<()> {
+        self.update("deactivate_node", (principal,)).await
+    }
+
+    pub async fn set_attestation_interval(&self, secs: u64) -> Result<()> {
+        self.update("set_attestation_interval", (secs,)).await
+    }
+
+    pub async fn get_node_health(&self) -> Result<NodeHealthReport> {
+        self.query("get_node_health", ()).await
+    }
+
+    pub async fn get_audit_log(&self, start: u64, limit: u64) -> Result<Vec<AuditEvent>> {
+        self.query("get_audit_log", (start, limit)).await
+    }
+
+    // ── Recovery ──────────────────────────────────────────────────────────
+    pub async fn open_recovery(&self, failed: Principal) -> Result<u64> {
+        self.update("open_recovery", (failed,)).await
+    }
+
+    pub async fn approve_recovery(&self, id: u64) -> Result<RecoveryStatus> {
+        self.update("approve_recovery", (id,)).await
+    }
+
+    pub async fn cancel_recovery(&self, id: u64) -> Result<()> {
+        self.update("cancel_recovery", (id,)).await
+    }
+
+    pub async fn derive_recovery_key(
+        &self,
+        owner: Principal,
+        request_id: Option<u64>,
+        record_id: &[u8],
+        transport_pk: &[u8],
+        key: Option<&str>,
+    ) -> Result<EncryptedKey> {
+        let args = (owner, request_id, record_id.to_vec(), transport_pk.to_vec(), key);
+        self.metered("derive_recovery_key", args).await
+    }
+
+    pub async fn list_recovered_record_ids(&self, request_id: u64) -> Result<Vec<Vec<u8>>> {
+        self.query("list_recovered_record_ids", (request_id,)).await
+    }
+
+    pub async fn get_recovered_record(
+        &self,
+        request_id: u64,
+        record_id: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        self.query("get_recovered_record", (request_id, record_id.to_vec())).await
+    }
+
+    pub async fn get_recovery_request(&self, id: u64) -> Result<Option<RecoveryRequest>> {
+        self.query("get_recovery_request", (id,)).await
+    }
+
+    pub async fn list_recovery_requests(&self) -> Result<Vec<RecoveryRequest>> {
+        self.query("list_recovery_requests", ()).await
+    }
+
+    pub async fn get_recovery_config(&self) -> Result<RecoveryConfig> {
+        self.query("get_recovery_config", ()).await
+    }
+
+    pub async fn set_recovery_config(&self, config: &RecoveryConfig) -> Result<()> {
+        self.update("set_recovery_config", (config,)).await
+    }
+
+    // ── IBE ───────────────────────────────────────────────────────────────
+    pub async fn ibe_public_key(&self, key: Option<&str>) -> Result<BlsPk> {
+        self.update("ibe_public_key", (key,)).await
+    }
+
+    pub async fn derive_ibe_key(
+        &self,
+        transport_pk: &[u8],
+        key: Option<&str>,
+    ) -> Result<EncryptedKey> {
+        self.metered("derive_ibe_key", (transport_pk.to_vec(), key)).await
+    }
+
+    // ── Key registry ──────────────────────────────────────────────────────
+    pub async fn add_key(&self, alias: &str, config: &KeyConfig) -> Result<()> {
+        self.update("add_key", (alias, config)).await
+    }
+
+    pub async fn remove_key(&self, alias: &str) -> Result<bool> {
+        self.update("remove_key", (alias,)).await
+    }
+
+    /// Assigns `alias` to `scope`. Without `force`, the canister refuses a
+    /// scope that has already derived keys.
+    pub async fn assign_key(&self, scope: &KeyScope, alias: &str, force: bool) -> Result<()> {
+        self.update("assign_key", (scope, alias, Some(force))).await
+    }
+
+    pub async fn list_keys(&self) -> Result<Vec<KeyEntry>> {
+        self.query("list_keys", ()).await
+    }
+
+    pub async fn list_key_assignments(&self) -> Result<Vec<KeyAssignment>> {
+        self.query("list_key_assignments", ()).await
+    }
+
+    // ── Metering ──────────────────────────────────────────────────────────
+    /// Credits the cycles attached to the call. Ingress calls cannot attach
+    /// cycles, so over an agent this only succeeds through a proxy canister.
+    pub async fn top_up(&self, beneficiary: Option<Principal>) -> Result<u128> {
+        self.update("top_up", (beneficiary,)).await
+    }
+
+    pub async fn grant_cycles(&self, principal: Principal, amount: u128) -> Result<u128> {
+        self.update("grant_cycles", (principal, amount)).await
+    }
+
+    pub async fn cycles_balance(&self) -> Result<u128> {
+        self.query("cycles_balance", ()).await
+    }
+
+    pub async fn get_cost_estimate(
+        &self,
+        op: MeteredOp,
+        key: Option<&str>,
+    ) -> Result<CostEstimate> {
+        self.query("get_cost_estimate", (op, key)).await
+    }
+
+    pub async fn get_metering_config(&self) -> Result<MeteringConfig> {
+        self.query("get_metering_config", ()).await
+    }
+
+    pub async fn set_metering_config(&self, config: &MeteringConfig) -> Result<()> {
+        self.update("set_metering_config", (config,)).await
+    }
+
+    // ── Stable memory ─────────────────────────────────────────────────────
+    pub async fn memory_stats(&self) -> Result<Vec<RegionStats>> {
+        self.query("memory_stats", ()).await
+    }
+}
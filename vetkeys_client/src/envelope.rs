@@ -0,0 +1,166 @@
+//! Versioned AES-256-GCM envelope stored by `put_record`.
+//!
+//! ```text
+//! envelope = version (1 byte) || nonce (12 bytes) || ciphertext || tag (16 bytes)
+//! ad       = "dooor.vetkeys.envelope" || version || lp(owner) || lp(record_id)
+//! ```
+//!
+//! `lp(x) = u32_be(len(x)) || x`. The associated data binds the envelope to its
+//! owner and record id, so an envelope copied to another record or another
+//! owner's namespace fails to open. The AES key is derived from the record's
+//! VetKD data key with a domain-separated KDF and never leaves the client.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use candid::Principal;
+use ic_vetkeys::VetKey;
+use rand::RngCore;
+
+/// Current envelope version.
+pub const ENVELOPE_V1: u8 = 1;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const AD_TAG: &[u8] = b"dooor.vetkeys.envelope";
+const KEY_DOMAIN: &str = "dooor.vetkeys.envelope.v1.aes-256-gcm";
+
+/// Why an envelope could not be opened.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("envelope is truncated")]
+    Truncated,
+    #[error("unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("envelope does not authenticate for this owner, record and key")]
+    Authentication,
+}
+
+/// AES-256 key for one record's envelopes.
+pub struct EnvelopeKey([u8; 32]);
+
+impl EnvelopeKey {
+    /// Derives the envelope key from a decrypted VetKD data key.
+    pub fn from_vetkey(vetkey: &VetKey) -> Self {
+        let bytes = vetkey.derive_symmetric_key(KEY_DOMAIN, 32);
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&bytes);
+        EnvelopeKey(key)
+    }
+
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        EnvelopeKey(key)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+fn push_lp(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Associated data for an envelope of `version` owned by `owner` at `record_id`.
+pub fn associated_data(version: u8, owner: Principal, record_id: &[u8]) -> Vec<u8> {
+    let mut ad = AD_TAG.to_vec();
+    ad.push(version);
+    push_lp(&mut ad, owner.as_slice());
+    push_lp(&mut ad, record_id);
+    ad
+}
+
+/// Encrypts `plaintext` into a v1 envelope with a random nonce.
+pub fn seal(key: &EnvelopeKey, owner: Principal, record_id: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    seal_with_nonce(key, owner, record_id, plaintext, nonce)
+}
+
+fn seal_with_nonce(
+    key: &EnvelopeKey,
+    owner: Principal,
+    record_id: &[u8],
+    plaintext: &[u8],
+    nonce: [u8; NONCE_LEN],
+) -> Vec<u8> {
+    let ad = associated_data(ENVELOPE_V1, owner, record_id);
+    let ciphertext = key
+        .cipher()
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &ad })
+        .expect("AES-GCM encryption cannot fail for in-memory buffers");
+    let mut out = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    out.push(ENVELOPE_V1);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    out
+}
+
+/// Decrypts an envelope sealed for `owner` at `record_id`.
+pub fn open(
+    key: &EnvelopeKey,
+    owner: Principal,
+    record_id: &[u8],
+    envelope: &[u8],
+) -> Result<Vec<u8>, EnvelopeError> {
+    let (&version, rest) = envelope.split_first().ok_or(EnvelopeError::Truncated)?;
+    if version != ENVELOPE_V1 {
+        return Err(EnvelopeError::UnsupportedVersion(version));
+    }
+    if rest.len() < NONCE_LEN + TAG_LEN {
+        return Err(EnvelopeError::Truncated);
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let ad = associated_data(version, owner, record_id);
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &ad })
+        .map_err(|_| EnvelopeError::Authentication)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(n: u8) -> Principal {
+        Principal::self_authenticating([n])
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = EnvelopeKey::from_bytes([1; 32]);
+        let env = seal(&key, owner(1), b"cfg:db", b"secret");
+        assert_eq!(env[0], ENVELOPE_V1);
+        assert_eq!(env.len(), 1 + NONCE_LEN + 6 + TAG_LEN);
+        assert_eq!(open(&key, owner(1), b"cfg:db", &env).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn fresh_nonce_per_seal() {
+        let key = EnvelopeKey::from_bytes([1; 32]);
+        assert_ne!(seal(&key, owner(1), b"r", b"m"), seal(&key, owner(1), b"r", b"m"));
+    }
+
+    #[test]
+    fn bound_to_owner_record_and_key() {
+        let key = EnvelopeKey::from_bytes([1; 32]);
+        let env = seal_with_nonce(&key, owner(1), b"r1", b"m", [9; NONCE_LEN]);
+        let auth = Err(EnvelopeError::Authentication);
+        assert_eq!(open(&key, owner(2), b"r1", &env), auth);
+        assert_eq!(open(&key, owner(1), b"r2", &env), auth);
+        assert_eq!(open(&EnvelopeKey::from_bytes([2; 32]), owner(1), b"r1", &env), auth);
+
+        let mut tampered = env.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&key, owner(1), b"r1", &tampered), auth);
+    }
+
+    #[test]
+    fn rejects_unknown_version_and_truncation() {
+        let key = EnvelopeKey::from_bytes([1; 32]);
+        let mut env = seal(&key, owner(1), b"r", b"m");
+        assert_eq!(open(&key, owner(1), b"r", &env[..10]), Err(EnvelopeError::Truncated));
+        assert_eq!(open(&key, owner(1), b"r", &[]), Err(EnvelopeError::Truncated));
+        env[0] = 2;
+        assert_eq!(open(&key, owner(1), b"r", &env), Err(EnvelopeError::UnsupportedVersion(2)));
+    }
+}
@@ -0,0 +1,115 @@
+//! Rust client for the Dooor vetkeys private DB canister.
+//!
+//! - [`VetkeysAgent`]: typed wrapper over every method in `vetkeys.did`
+//! - [`TransportKey`]: transport key generation and derived-key decryption
+//! - [`envelope`]: versioned AES-256-GCM envelope bound to owner and record id
+//! - [`Client`]: the full workflow (derive → decrypt → seal/open → store)
+//!
+//! ```no_run
+//! # async fn run(agent: ic_agent::Agent, id: candid::Principal) -> vetkeys_client::Result<()> {
+//! let client = vetkeys_client::Client::new(agent, id)?;
+//! let rid = vetkeys_client::record_id("cfg", "db-password");
+//! client.put(&rid, b"hunter2").await?;
+//! assert_eq!(client.get(&rid).await?.as_deref(), Some(&b"hunter2"[..]));
+//! # Ok(()) }
+//! ```
+
+use candid::Principal;
+use ic_agent::{Agent, AgentError};
+use ic_vetkeys::VetKey;
+
+mod agent;
+pub mod envelope;
+mod transport;
+pub mod types;
+
+pub use agent::VetkeysAgent;
+pub use envelope::{EnvelopeError, EnvelopeKey};
+pub use transport::{data_key_input, record_id, TransportKey};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("agent error: {0}")]
+    Agent(Box<AgentError>),
+    #[error("candid error: {0}")]
+    Candid(#[from] candid::Error),
+    #[error("identity error: {0}")]
+    Identity(String),
+    #[error("derivation refused: {0:?}")]
+    Metering(types::MeteringError),
+    #[error("crypto error: {0}")]
+    Crypto(String),
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+}
+
+impl From<AgentError> for Error {
+    fn from(e: AgentError) -> Self {
+        Error::Agent(Box::new(e))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Encrypted record store for the agent's own principal.
+#[derive(Clone)]
+pub struct Client {
+    canister: VetkeysAgent,
+    owner: Principal,
+    key: Option<String>,
+}
+
+impl Client {
+    pub fn new(agent: Agent, canister_id: Principal) -> Result<Self> {
+        let canister = VetkeysAgent::new(agent, canister_id);
+        let owner = canister.principal()?;
+        Ok(Client { canister, owner, key: None })
+    }
+
+    /// Uses the key alias `key` from the canister's key registry.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    pub fn canister(&self) -> &VetkeysAgent {
+        &self.canister
+    }
+
+    pub fn owner(&self) -> Principal {
+        self.owner
+    }
+
+    /// Derives and verifies `record_id`'s VetKD data key.
+    pub async fn data_key(&self, record_id: &[u8]) -> Result<VetKey> {
+        let tk = TransportKey::random();
+        let key = self.key.as_deref();
+        let encrypted = self.canister.derive_data_key(record_id, &tk.public_key(), key).await?;
+        let derived_pk = self.canister.bls_public_key(key).await?;
+        tk.decrypt(&encrypted, &derived_pk, &data_key_input(record_id))
+    }
+
+    /// Seals `plaintext` and stores it at `record_id`, replacing any previous value.
+    pub async fn put(&self, record_id: &[u8], plaintext: &[u8]) -> Result<()> {
+        let key = EnvelopeKey::from_vetkey(&self.data_key(record_id).await?);
+        let sealed = envelope::seal(&key, self.owner, record_id, plaintext);
+        self.canister.put_record(record_id, &sealed).await
+    }
+
+    /// Fetches and opens the record at `record_id`.
+    pub async fn get(&self, record_id: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(sealed) = self.canister.get_record(record_id).await? else {
+            return Ok(None);
+        };
+        let key = EnvelopeKey::from_vetkey(&self.data_key(record_id).await?);
+        Ok(Some(envelope::open(&key, self.owner, record_id, &sealed)?))
+    }
+
+    pub async fn list(&self) -> Result<Vec<Vec<u8>>> {
+        self.canister.list_record_ids().await
+    }
+
+    pub async fn delete(&self, record_id: &[u8]) -> Result<bool> {
+        self.canister.delete_record(record_id).await
+    }
+}
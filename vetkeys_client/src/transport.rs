@@ -0,0 +1,62 @@
+//! Transport keys and decryption of VetKD-derived keys.
+//!
+//! Every derivation uses a fresh BLS12-381 G1 transport key (48-byte public
+//! key, as the canister requires). The encrypted key returned by the canister
+//! is decrypted and verified against the derived public key before use.
+
+use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey, TransportSecretKey, VetKey};
+use rand::RngCore;
+
+use crate::types::{BlsPk, EncryptedKey};
+use crate::{Error, Result};
+
+/// Prefix of the VetKD input for data keys: `"db|v1|" || record_id`.
+const DATA_KEY_PREFIX: &[u8] = b"db|v1|";
+
+/// VetKD input the canister derives `record_id`'s data key for.
+pub fn data_key_input(record_id: &[u8]) -> Vec<u8> {
+    [DATA_KEY_PREFIX, record_id].concat()
+}
+
+/// Conventional record id `<namespace>:<name>` (UTF-8), as used by the demos.
+pub fn record_id(namespace: &str, name: &str) -> Vec<u8> {
+    assert!(!namespace.contains(':'), "namespace must not contain ':'");
+    format!("{namespace}:{name}").into_bytes()
+}
+
+/// Ephemeral transport secret key.
+pub struct TransportKey(TransportSecretKey);
+
+impl TransportKey {
+    /// Generates a key from 32 bytes of OS randomness.
+    pub fn random() -> Self {
+        let mut seed = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        Self::from_seed(seed)
+    }
+
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        TransportKey(
+            TransportSecretKey::from_seed(seed.to_vec()).expect("a 32-byte seed is always valid"),
+        )
+    }
+
+    /// 48-byte compressed G1 public key to pass to `derive_*_key`.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.0.public_key()
+    }
+
+    /// Decrypts `encrypted` and verifies it for `input` under `derived_pk`.
+    pub fn decrypt(
+        &self,
+        encrypted: &EncryptedKey,
+        derived_pk: &BlsPk,
+        input: &[u8],
+    ) -> Result<VetKey> {
+        let dpk = DerivedPublicKey::deserialize(&derived_pk.pk)
+            .map_err(|e| Error::Crypto(format!("derived public key: {e:?}")))?;
+        EncryptedVetKey::deserialize(&encrypted.encrypted_key)
+            .and_then(|ek| ek.decrypt_and_verify(&self.0, &dpk, input))
+            .map_err(|e| Error::Crypto(format!("encrypted key: {e:?}")))
+    }
+}
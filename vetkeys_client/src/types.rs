@@ -0,0 +1,158 @@
+//! Candid types of `vetkeys.did`.
+
+use candid::{CandidType, Deserialize, Principal};
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct BlsPk {
+    pub pk: Vec<u8>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct EncryptedKey {
+    pub encrypted_key: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum NodeStatus {
+    Pending,
+    Active,
+    Quarantined,
+    Deactivated,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct NodeHealth {
+    pub node_id: String,
+    pub principal: Principal,
+    pub status: NodeStatus,
+    pub last_attested_at: Option<u64>,
+    pub deadline: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct NodeHealthReport {
+    pub now: u64,
+    pub attestation_interval_secs: u64,
+    pub nodes: Vec<NodeHealth>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum AuditKind {
+    NodeRegistered,
+    NodeAttested,
+    NodeStatusChanged,
+    RecoveryOpened,
+    RecoveryApproved,
+    RecoveryStatusChanged,
+    RecoveryKeyDerived,
+    KeyRegistryChanged,
+    MeteringChanged,
+    NodeConfigChanged,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct AuditEvent {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub actor: Principal,
+    pub kind: AuditKind,
+    pub subject: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum RecoveryStatus {
+    Open,
+    Approved,
+    Expired,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RecoveryRequest {
+    pub id: u64,
+    pub failed: Principal,
+    pub replacement: Principal,
+    pub status: RecoveryStatus,
+    pub threshold: u32,
+    pub approvals: Vec<Principal>,
+    pub opened_at: u64,
+    pub approval_deadline: u64,
+    pub access_until: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RecoveryConfig {
+    pub threshold: u32,
+    pub approval_window_secs: u64,
+    pub access_window_secs: u64,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum KeyCurve {
+    Bls12_381_G2,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct KeyConfig {
+    pub name: String,
+    pub curve: KeyCurve,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum KeyScope {
+    Tenant(Principal),
+    Purpose(String),
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct KeyEntry {
+    pub alias: String,
+    pub config: KeyConfig,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct KeyAssignment {
+    pub scope: KeyScope,
+    pub alias: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum MeteredOp {
+    DeriveDataKey,
+    DeriveRecoveryKey,
+    DeriveIbeKey,
+}
+
+/// Why a metered derivation was refused; the `Err` of its reply.
+#[derive(Clone, Debug, PartialEq, Eq, CandidType, Deserialize)]
+pub enum MeteringError {
+    InsufficientCycles { op: MeteredOp, required: u128, available: u128 },
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct MeteringConfig {
+    pub enabled: bool,
+    pub margin_percent: u32,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CostEstimate {
+    pub op: MeteredOp,
+    pub key_name: String,
+    pub vetkd_cycles: u128,
+    pub margin_percent: u32,
+    pub cycles: u128,
+    pub balance: u128,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RegionStats {
+    pub id: u8,
+    pub name: String,
+    pub owner: String,
+    pub retired: bool,
+    pub pages: u64,
+    pub bytes: u64,
+}
@@ -0,0 +1,486 @@
+//! Aggregated multi-node BLS attestation certificates.
+//!
+//! A controller opens a certificate round for a message (e.g. a deployment
+//! decision or recovery approval) and names the committee of node principals
+//! that may co-sign it. Each member derives its share with
+//! `certificate_share_key`, decrypts it locally and submits the plain
+//! signature. The canister verifies every share against the member's derived
+//! public key; once `threshold` shares are in, it sums them into one
+//! BLS12-381 signature and stores the certificate with a signer bitmap.
+//!
+//! - VetKD `context = [len(AGG_DS)] || AGG_DS || member_principal_bytes`
+//! - VetKD `input = CERT_TAG || u64_be(id) || message`
+//!
+//! Because VetKD signatures hash the signer's public key into the message
+//! (augmented BLS), the aggregate verifies as
+//! `e(sig, g2) == Π e(H(pk_i || input), pk_i)` over the signers in the bitmap,
+//! with no rogue-key concerns.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared,
+};
+use ic_cdk::management_canister::{vetkd_derive_key, VetKDDeriveKeyArgs};
+use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use ic_vetkeys::verify_bls_signature;
+use std::cell::RefCell;
+
+use crate::metering::{self, MeteredOp};
+use crate::{derived_public_key, keys, layout, memory, require_controller, BlsPk, BlsSig, Memory};
+
+/// Domain separation tag for certificate share contexts.
+const AGG_DS: &[u8] = b"bls_demo.aggregate";
+
+/// Prefix of the signed certificate input.
+const CERT_TAG: &[u8] = b"dooor-cert-v1";
+
+/// DST of the augmented BLS hash used by VetKD signatures.
+const AUG_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_";
+
+/// Largest committee a round may name (bitmap stays small).
+const MAX_COMMITTEE: usize = 256;
+
+/// A finalized certificate, verifiable by anyone.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct AttestationCertificate {
+    pub id: u64,
+    pub message: Vec<u8>,
+    /// Exact bytes every member signed: CERT_TAG || u64_be(id) || message
+    pub signed_input: Vec<u8>,
+    pub committee: Vec<Principal>,
+    /// Bit `i` (LSB-first within each byte) is set if `committee[i]` signed.
+    pub signer_bitmap: Vec<u8>,
+    /// 96-byte G2 public keys of the signers, in committee order.
+    pub signer_public_keys: Vec<Vec<u8>>,
+    /// 48-byte aggregated BLS12-381 G1 signature.
+    pub aggregate_signature: Vec<u8>,
+    pub finalized_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct Round {
+    id: u64,
+    message: Vec<u8>,
+    committee: Vec<Principal>,
+    threshold: u32,
+    /// (committee index, 96-byte public key, 48-byte signature)
+    shares: Vec<(u32, Vec<u8>, Vec<u8>)>,
+    certificate: Option<AttestationCertificate>,
+}
+
+candid_storable!(Round);
+
+/// Public view of a round.
+#[derive(CandidType, Deserialize)]
+pub struct CertificateRound {
+    pub id: u64,
+    pub message: Vec<u8>,
+    pub committee: Vec<Principal>,
+    pub threshold: u32,
+    pub signed: Vec<Principal>,
+    pub finalized: bool,
+}
+
+thread_local! {
+    static ROUNDS: RefCell<StableBTreeMap<u64, Round, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::ROUNDS)));
+}
+
+/// Constructs a member's share context: [len(AGG_DS)] || AGG_DS || member_bytes
+fn agg_context(member: Principal) -> Vec<u8> {
+    std::iter::once(AGG_DS.len() as u8)
+        .chain(AGG_DS.iter().copied())
+        .chain(member.as_slice().iter().copied())
+        .collect()
+}
+
+fn cert_input(id: u64, message: &[u8]) -> Vec<u8> {
+    let mut input = CERT_TAG.to_vec();
+    input.extend_from_slice(&id.to_be_bytes());
+    input.extend_from_slice(message);
+    input
+}
+
+fn load(id: u64) -> Round {
+    ROUNDS
+        .with(|r| r.borrow().get(&id))
+        .unwrap_or_else(|| ic_cdk::trap("unknown certificate round"))
+}
+
+fn member_index(round: &Round, member: Principal) -> u32 {
+    round
+        .committee
+        .iter()
+        .position(|p| *p == member)
+        .unwrap_or_else(|| ic_cdk::trap("caller is not in the committee")) as u32
+}
+
+fn parse_g1(bytes: &[u8]) -> Option<G1Affine> {
+    let arr: [u8; 48] = bytes.try_into().ok()?;
+    Option::from(G1Affine::from_compressed(&arr))
+}
+
+fn parse_g2(bytes: &[u8]) -> Option<G2Affine> {
+    let arr: [u8; 96] = bytes.try_into().ok()?;
+    Option::from(G2Affine::from_compressed(&arr))
+}
+
+fn augmented_hash(pk: &G2Affine, input: &[u8]) -> G1Affine {
+    let mut msg = pk.to_compressed().to_vec();
+    msg.extend_from_slice(input);
+    G1Affine::from(<G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(
+        &msg, AUG_DST,
+    ))
+}
+
+/// Checks `e(sig, g2) == Π e(H(pk_i || input), pk_i)`.
+fn verify_aggregate(public_keys: &[Vec<u8>], input: &[u8], signature: &[u8]) -> bool {
+    let Some(sig) = parse_g1(signature) else { return false };
+    let Some(pks) = public_keys.iter().map(|pk| parse_g2(pk)).collect::<Option<Vec<_>>>() else {
+        return false;
+    };
+    if pks.is_empty() {
+        return false;
+    }
+
+    let hashes: Vec<G1Affine> = pks.iter().map(|pk| augmented_hash(pk, input)).collect();
+    let neg_g2 = G2Prepared::from(-G2Affine::generator());
+    let prepared: Vec<G2Prepared> = pks.iter().map(|pk| G2Prepared::from(*pk)).collect();
+
+    let mut terms: Vec<(&G1Affine, &G2Prepared)> = vec![(&sig, &neg_g2)];
+    terms.extend(hashes.iter().zip(prepared.iter()));
+
+    bool::from(multi_miller_loop(&terms).final_exponentiation().is_identity())
+}
+
+/// Sums the shares into a certificate finalized at `now`.
+fn finalize(round: &Round, now: u64) -> AttestationCertificate {
+    let mut shares = round.shares.clone();
+    shares.sort_by_key(|(index, _, _)| *index);
+
+    let mut aggregate = G1Projective::identity();
+    let mut bitmap = vec![0u8; round.committee.len().div_ceil(8)];
+    for (index, _, sig) in &shares {
+        let point = parse_g1(sig).unwrap_or_else(|| ic_cdk::trap("stored share is malformed"));
+        aggregate += point;
+        bitmap[*index as usize / 8] |= 1 << (*index % 8);
+    }
+
+    AttestationCertificate {
+        id: round.id,
+        message: round.message.clone(),
+        signed_input: cert_input(round.id, &round.message),
+        committee: round.committee.clone(),
+        signer_bitmap: bitmap,
+        signer_public_keys: shares.into_iter().map(|(_, pk, _)| pk).collect(),
+        aggregate_signature: G1Affine::from(aggregate).to_compressed().to_vec(),
+        finalized_at: now,
+    }
+}
+
+/// Opens a certificate round (controllers only).
+///
+/// ### Parameters
+/// - `message`: Bytes the committee co-signs (e.g. a deployment decision)
+/// - `committee`: Node principals allowed to sign; their order defines the bitmap
+/// - `threshold`: Shares needed before the certificate is aggregated
+///
+/// ### Returns
+/// - The round id
+#[update]
+fn open_certificate(message: Vec<u8>, committee: Vec<Principal>, threshold: u32) -> u64 {
+    require_controller();
+    if committee.is_empty() || committee.len() > MAX_COMMITTEE {
+        ic_cdk::trap("committee must have 1..=256 members");
+    }
+    let mut unique = committee.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != committee.len() {
+        ic_cdk::trap("committee contains duplicates");
+    }
+    if threshold == 0 || threshold as usize > committee.len() {
+        ic_cdk::trap("threshold must be between 1 and the committee size");
+    }
+
+    ROUNDS.with(|r| {
+        let mut rounds = r.borrow_mut();
+        let id = rounds.last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
+        rounds.insert(
+            id,
+            Round { id, message, committee, threshold, shares: vec![], certificate: None },
+        );
+        id
+    })
+}
+
+/// Derives the caller's share for round `id`, encrypted to the transport key.
+///
+/// Decrypt it with the matching transport secret key (input = `signed_input`,
+/// public key = `certificate_member_public_key(id, caller)`) and pass the
+/// 48-byte signature to `submit_certificate_share`. Replies with
+/// `Err(MeteringError)` without deriving if the caller's cycle balance is too low.
+#[update(manual_reply = true)]
+async fn certificate_share_key(id: u64, transport_public_key: Vec<u8>) {
+    if ![32, 48].contains(&transport_public_key.len()) {
+        ic_cdk::trap("`transport_public_key` must be 32 (X25519) or 48 (BLS-G1) bytes");
+    }
+    let caller = ic_cdk::api::caller();
+    let round = load(id);
+    member_index(&round, caller);
+    let key_id = keys::resolve_for_derivation(None, Some(caller), "aggregate");
+    let op = MeteredOp::CertificateShare;
+    let Some(charged) = metering::charge_or_reply::<BlsSig>(caller, op, &key_id) else {
+        return;
+    };
+
+    let args = VetKDDeriveKeyArgs {
+        input: cert_input(id, &round.message),
+        context: agg_context(caller),
+        transport_public_key,
+        key_id,
+    };
+
+    let res = vetkd_derive_key(&args).await
+        .map(|res| BlsSig { signature: res.encrypted_key })
+        .map_err(|e| format!("VetKD derive key error: {:?}", e));
+
+    metering::reply_or_refund(caller, charged, res);
+}
+
+/// Submits the caller's decrypted share for round `id`.
+///
+/// ### Returns
+/// - `true` once the round has reached its threshold and the certificate is stored
+#[update]
+async fn submit_certificate_share(id: u64, signature: Vec<u8>) -> bool {
+    let caller = ic_cdk::api::caller();
+    let round = load(id);
+    let index = member_index(&round, caller);
+
+    let key_id = keys::resolve(None, Some(caller), "aggregate");
+    let dpk = derived_public_key(&key_id, &agg_context(caller)).await;
+    if !verify_bls_signature(&dpk, &cert_input(id, &round.message), &signature) {
+        ic_cdk::trap("invalid share signature");
+    }
+
+    // Re-read: other shares may have landed while we awaited.
+    let mut round = load(id);
+    if round.certificate.is_some() {
+        ic_cdk::trap("certificate already finalized");
+    }
+    if round.shares.iter().any(|(i, _, _)| *i == index) {
+        ic_cdk::trap("share already submitted");
+    }
+    round.shares.push((index, dpk.serialize().to_vec(), signature));
+    if round.shares.len() >= round.threshold as usize {
+        round.certificate = Some(finalize(&round, ic_cdk::api::time()));
+    }
+    let finalized = round.certificate.is_some();
+    ROUNDS.with(|r| r.borrow_mut().insert(id, round));
+    finalized
+}
+
+/// Returns the finalized certificate for round `id`, if any.
+#[query]
+fn get_certificate(id: u64) -> Option<AttestationCertificate> {
+    ROUNDS.with(|r| r.borrow().get(&id)).and_then(|round| round.certificate)
+}
+
+/// Returns the state of round `id`.
+#[query]
+fn get_certificate_round(id: u64) -> Option<CertificateRound> {
+    ROUNDS.with(|r| r.borrow().get(&id)).map(|round| CertificateRound {
+        id: round.id,
+        signed: round.shares.iter().map(|(i, _, _)| round.committee[*i as usize]).collect(),
+        finalized: round.certificate.is_some(),
+        message: round.message,
+        committee: round.committee,
+        threshold: round.threshold,
+    })
+}
+
+/// Returns the 96-byte G2 public key `member` signs round shares with.
+#[update]
+async fn certificate_member_public_key(member: Principal) -> BlsPk {
+    let key_id = keys::resolve(None, Some(member), "aggregate");
+    BlsPk { pk: derived_public_key(&key_id, &agg_context(member)).await.serialize().to_vec() }
+}
+
+/// Re-checks a certificate's aggregate signature against its signer keys.
+///
+/// This is the pure pairing check; callers that received the certificate from a
+/// third party should also confirm `signer_public_keys` with
+/// `certificate_member_public_key` for the members set in the bitmap.
+#[query]
+fn verify_certificate(certificate: AttestationCertificate) -> bool {
+    let signers: u32 = certificate.signer_bitmap.iter().map(|b| b.count_ones()).sum();
+    if certificate.signed_input != cert_input(certificate.id, &certificate.message)
+        || signers as usize != certificate.signer_public_keys.len()
+    {
+        return false;
+    }
+    verify_aggregate(
+        &certificate.signer_public_keys,
+        &certificate.signed_input,
+        &certificate.aggregate_signature,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_bls12_381::Scalar;
+    use ic_vetkeys::DerivedPublicKey;
+
+    /// Stand-in for a member's VetKD-derived key, from a fixed secret.
+    struct Member {
+        secret: Scalar,
+        public: G2Affine,
+    }
+
+    impl Member {
+        fn new(seed: u8) -> Self {
+            let secret = Scalar::from_bytes_wide(&[seed; 64]);
+            let public = G2Affine::from(G2Affine::generator() * secret);
+            Member { secret, public }
+        }
+
+        fn public_key(&self) -> Vec<u8> {
+            self.public.to_compressed().to_vec()
+        }
+
+        /// The decrypted share `certificate_share_key` yields for `input`.
+        fn sign(&self, input: &[u8]) -> G1Projective {
+            augmented_hash(&self.public, input) * self.secret
+        }
+    }
+
+    fn principal(seed: u8) -> Principal {
+        Principal::from_slice(&[seed; 29])
+    }
+
+    fn aggregate(shares: &[G1Projective]) -> Vec<u8> {
+        let sum = shares.iter().fold(G1Projective::identity(), |acc, s| acc + s);
+        G1Affine::from(sum).to_compressed().to_vec()
+    }
+
+    /// Round 9 with one committee seat per member, holding the shares of `signers`.
+    fn round(members: &[Member], signers: &[u32], threshold: u32) -> Round {
+        let input = cert_input(9, b"deploy tee-7");
+        Round {
+            id: 9,
+            message: b"deploy tee-7".to_vec(),
+            committee: (1..=members.len() as u8).map(principal).collect(),
+            threshold,
+            shares: signers
+                .iter()
+                .map(|&i| {
+                    let m = &members[i as usize];
+                    (i, m.public_key(), aggregate(&[m.sign(&input)]))
+                })
+                .collect(),
+            certificate: None,
+        }
+    }
+
+    fn members() -> Vec<Member> {
+        (1..=3).map(Member::new).collect()
+    }
+
+    #[test]
+    fn shares_are_vetkd_signatures() {
+        let member = Member::new(1);
+        let input = cert_input(0, b"msg");
+        let dpk = DerivedPublicKey::deserialize(&member.public_key()).unwrap();
+        assert!(verify_bls_signature(&dpk, &input, &aggregate(&[member.sign(&input)])));
+    }
+
+    #[test]
+    fn verify_aggregate_accepts_a_known_good_aggregate() {
+        let members = members();
+        let input = cert_input(0, b"msg");
+        let pks: Vec<_> = members.iter().map(Member::public_key).collect();
+        let shares: Vec<_> = members.iter().map(|m| m.sign(&input)).collect();
+        assert!(verify_aggregate(&pks, &input, &aggregate(&shares)));
+        assert!(!verify_aggregate(&pks, &cert_input(1, b"msg"), &aggregate(&shares)));
+    }
+
+    #[test]
+    fn verify_aggregate_rejects_a_missing_signer() {
+        let members = members();
+        let input = cert_input(0, b"msg");
+        let pks: Vec<_> = members.iter().map(Member::public_key).collect();
+        let two = aggregate(&[members[0].sign(&input), members[1].sign(&input)]);
+        assert!(!verify_aggregate(&pks, &input, &two));
+        assert!(verify_aggregate(&pks[..2], &input, &two));
+    }
+
+    #[test]
+    fn verify_aggregate_rejects_a_duplicate_share() {
+        let members = members();
+        let input = cert_input(0, b"msg");
+        let pks: Vec<_> = members[..2].iter().map(Member::public_key).collect();
+        let (a, b) = (members[0].sign(&input), members[1].sign(&input));
+        assert!(!verify_aggregate(&pks, &input, &aggregate(&[a, a, b])));
+        assert!(!verify_aggregate(&pks, &input, &aggregate(&[a, a])));
+    }
+
+    #[test]
+    fn verify_aggregate_rejects_malformed_input() {
+        let member = Member::new(1);
+        let input = cert_input(0, b"msg");
+        let sig = aggregate(&[member.sign(&input)]);
+        assert!(!verify_aggregate(&[], &input, &sig));
+        assert!(!verify_aggregate(&[member.public_key()], &input, &sig[..47]));
+        assert!(!verify_aggregate(&[vec![0u8; 96]], &input, &sig));
+    }
+
+    #[test]
+    fn finalize_aggregates_the_shares_at_the_threshold() {
+        let members = members();
+        let round = round(&members, &[2, 0], 2);
+        let cert = finalize(&round, 42);
+
+        assert_eq!(cert.id, 9);
+        assert_eq!(cert.signed_input, cert_input(9, b"deploy tee-7"));
+        assert_eq!(cert.signer_bitmap, vec![0b101]);
+        assert_eq!(cert.signer_public_keys, vec![members[0].public_key(), members[2].public_key()]);
+        assert_eq!(cert.finalized_at, 42);
+        assert!(verify_certificate(cert));
+    }
+
+    #[test]
+    fn finalize_sizes_the_bitmap_to_the_committee() {
+        let members: Vec<_> = (1..=9).map(Member::new).collect();
+        let cert = finalize(&round(&members, &[8], 1), 0);
+        assert_eq!(cert.signer_bitmap, vec![0, 1]);
+        assert!(verify_certificate(cert));
+    }
+
+    #[test]
+    fn verify_certificate_rejects_a_tampered_bitmap() {
+        let cert = finalize(&round(&members(), &[0, 1], 2), 0);
+
+        let mut extra = cert.clone();
+        extra.signer_bitmap = vec![0b111];
+        assert!(!verify_certificate(extra));
+
+        let mut missing = cert.clone();
+        missing.signer_bitmap = vec![0b001];
+        assert!(!verify_certificate(missing));
+
+        let mut message = cert;
+        message.message = b"deploy tee-8".to_vec();
+        assert!(!verify_certificate(message));
+    }
+
+    #[test]
+    fn a_duplicated_share_does_not_verify() {
+        let mut round = round(&members(), &[0, 1], 2);
+        round.shares.push(round.shares[0].clone());
+        assert!(!verify_certificate(finalize(&round, 0)));
+    }
+}
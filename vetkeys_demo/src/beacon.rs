@@ -0,0 +1,134 @@
+//! Verifiable random beacon derived from VetKD.
+//!
+//! For each epoch and purpose (e.g. "akash-provider", "recovery-witness") the
+//! canister derives a BLS signature over `u64_be(epoch) || purpose`. VetKD
+//! derivation is deterministic, so neither the canister nor the caller can
+//! bias the value, and only the current or past epochs can be derived, so
+//! nobody can look ahead. The random value is `SHA-256(signature)`; anyone can
+//! check it against `beacon_public_key()` with `verify_bls_signature`.
+//!
+//! - VetKD `context = [len(BEACON_DS)] || BEACON_DS`
+//! - VetKD `input = u64_be(epoch) || purpose`
+
+use candid::{CandidType, Deserialize};
+use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::canister_signer::{is_authorized, sign_with_context};
+use crate::metering::{self, MeteredOp};
+use crate::{derived_public_key, keys, layout, memory, BlsPk, Memory};
+
+/// Domain separation tag for the beacon context.
+const BEACON_DS: &[u8] = b"bls_demo.beacon";
+
+/// Length of one beacon epoch.
+pub const EPOCH_SECS: u64 = 60 * 60;
+
+/// Longest accepted purpose label.
+const MAX_PURPOSE_LEN: usize = 64;
+
+/// One beacon output.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BeaconEntry {
+    pub epoch: u64,
+    pub purpose: String,
+    /// Signed bytes: u64_be(epoch) || purpose
+    pub input: Vec<u8>,
+    /// 48-byte BLS12-381 G1 signature over `input`
+    pub signature: Vec<u8>,
+    /// SHA-256(signature): the random value
+    pub randomness: Vec<u8>,
+    pub created_at: u64,
+}
+
+candid_storable!(BeaconEntry);
+
+thread_local! {
+    /// u64_be(epoch) || purpose -> entry
+    static BEACON: RefCell<StableBTreeMap<Vec<u8>, BeaconEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::BEACON)));
+}
+
+/// Constructs the beacon context: [len(BEACON_DS)] || BEACON_DS
+fn beacon_context() -> Vec<u8> {
+    std::iter::once(BEACON_DS.len() as u8)
+        .chain(BEACON_DS.iter().copied())
+        .collect()
+}
+
+fn beacon_input(epoch: u64, purpose: &str) -> Vec<u8> {
+    let mut input = epoch.to_be_bytes().to_vec();
+    input.extend_from_slice(purpose.as_bytes());
+    input
+}
+
+fn current_epoch() -> u64 {
+    ic_cdk::api::time() / 1_000_000_000 / EPOCH_SECS
+}
+
+/// Returns the beacon value for the current epoch and `purpose`, deriving it on
+/// first use (controllers and allowlisted canister signers only).
+///
+/// ### Parameters
+/// - `purpose`: Label separating independent random streams (1..=64 bytes)
+///
+/// ### Returns
+/// - The beacon entry for `(current_epoch, purpose)`
+/// - `Err(MeteringError)` if a new entry is due and the caller's cycle balance is too low
+#[update(manual_reply = true)]
+async fn beacon(purpose: String) {
+    let caller = ic_cdk::api::caller();
+    if !is_authorized(caller) {
+        ic_cdk::trap("caller may not advance the beacon");
+    }
+    if purpose.is_empty() || purpose.len() > MAX_PURPOSE_LEN {
+        ic_cdk::trap("`purpose` must be 1..=64 bytes");
+    }
+
+    let epoch = current_epoch();
+    let input = beacon_input(epoch, &purpose);
+    if let Some(entry) = BEACON.with(|b| b.borrow().get(&input)) {
+        // Nothing was charged for an existing entry.
+        return metering::reply_or_refund(caller, 0, Ok(entry));
+    }
+
+    let key_id = keys::resolve_for_derivation(None, None, "beacon");
+    let op = MeteredOp::Beacon;
+    let Some(charged) = metering::charge_or_reply::<BeaconEntry>(caller, op, &key_id) else {
+        return;
+    };
+    let res = sign_with_context(key_id, beacon_context(), input.clone()).await.map(|signature| {
+        let entry = BeaconEntry {
+            epoch,
+            purpose,
+            randomness: Sha256::digest(&signature).to_vec(),
+            input: input.clone(),
+            signature,
+            created_at: ic_cdk::api::time(),
+        };
+        BEACON.with(|b| b.borrow_mut().insert(input, entry.clone()));
+        entry
+    });
+    metering::reply_or_refund(caller, charged, res);
+}
+
+/// Returns the recorded beacon entry for `(epoch, purpose)`, if any.
+#[query]
+fn get_beacon(epoch: u64, purpose: String) -> Option<BeaconEntry> {
+    BEACON.with(|b| b.borrow().get(&beacon_input(epoch, &purpose)))
+}
+
+/// Returns the current epoch number and epoch length in seconds.
+#[query]
+fn beacon_epoch() -> (u64, u64) {
+    (current_epoch(), EPOCH_SECS)
+}
+
+/// Returns the 96-byte G2 public key that verifies beacon signatures.
+#[update]
+async fn beacon_public_key() -> BlsPk {
+    let key_id = keys::resolve(None, None, "beacon");
+    BlsPk { pk: derived_public_key(&key_id, &beacon_context()).await.serialize().to_vec() }
+}
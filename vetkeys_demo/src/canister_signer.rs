@@ -0,0 +1,139 @@
+//! Canister-held BLS signing (threshold-BLS signing oracle).
+//!
+//! `sign_caller` hands callers an *encrypted* key, which canisters cannot use.
+//! Here the canister derives the key itself for a canister-chosen context,
+//! using a fixed internal transport key, and returns the plain BLS signature.
+//! This is safe because a VetKD key derived for `input = message` *is* the BLS
+//! signature over `message`, which is meant to be public anyway.
+//!
+//! - VetKD `context = [len(CANISTER_DS)] || CANISTER_DS` (not bound to any caller)
+//! - VetKD `input = message`
+//!
+//! Only controllers and allowlisted principals may request signatures, since
+//! anything signed here is attested by this canister.
+
+use candid::Principal;
+use ic_cdk::management_canister::{vetkd_derive_key, VetKDDeriveKeyArgs, VetKDKeyId};
+use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use ic_vetkeys::{EncryptedVetKey, TransportSecretKey};
+use std::cell::RefCell;
+
+use crate::metering::{self, MeteredOp};
+use crate::{
+    derived_public_key, keys, layout, memory, require_controller, try_derived_public_key, BlsPk,
+    BlsSig, Memory,
+};
+
+/// Domain separation tag for the canister signing context.
+const CANISTER_DS: &[u8] = b"bls_demo.canister";
+
+/// Seed of the internal transport key. Public on purpose: the decrypted key is a
+/// signature that is returned to the caller in the clear.
+const INTERNAL_TRANSPORT_SEED: [u8; 32] = *b"bls_demo.internal.transport.v1.\0";
+
+thread_local! {
+    /// Principals (besides controllers) allowed to call `sign_as_canister`.
+    static SIGNERS: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::SIGNERS)));
+}
+
+/// Constructs the canister signing context: [len(CANISTER_DS)] || CANISTER_DS
+fn canister_context() -> Vec<u8> {
+    std::iter::once(CANISTER_DS.len() as u8)
+        .chain(CANISTER_DS.iter().copied())
+        .collect()
+}
+
+/// Derives the VetKD key for (`context`, `input`) under `key_id` inside the canister
+/// and returns its 48-byte BLS signature bytes, verified against the derived public key.
+///
+/// Errors after the derivation call are returned rather than trapped, so that
+/// callers can refund the charge for it.
+pub(crate) async fn sign_with_context(
+    key_id: VetKDKeyId,
+    context: Vec<u8>,
+    input: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let tsk = TransportSecretKey::from_seed(INTERNAL_TRANSPORT_SEED.to_vec())
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("transport key error: {e}")));
+
+    let args = VetKDDeriveKeyArgs {
+        input: input.clone(),
+        context: context.clone(),
+        transport_public_key: tsk.public_key(),
+        key_id: key_id.clone(),
+    };
+
+    let res = vetkd_derive_key(&args).await
+        .map_err(|e| format!("VetKD derive key error: {:?}", e))?;
+
+    let dpk = try_derived_public_key(&key_id, &context).await?;
+    let vetkey = EncryptedVetKey::deserialize(&res.encrypted_key)
+        .and_then(|ek| ek.decrypt_and_verify(&tsk, &dpk, &input))
+        .map_err(|e| format!("VetKD decryption error: {e}"))?;
+
+    Ok(vetkey.signature_bytes().to_vec())
+}
+
+/// Whether `principal` may request canister-held signatures (controllers and allowlist).
+pub(crate) fn is_authorized(principal: Principal) -> bool {
+    ic_cdk::api::is_controller(&principal)
+        || SIGNERS.with(|s| s.borrow().contains_key(&principal.as_slice().to_vec()))
+}
+
+/// Returns a plain BLS12-381 signature over `message` made with the canister key.
+///
+/// Verify it with `ic_vetkeys::verify_bls_signature` against `canister_bls_public_key()`.
+///
+/// ### Parameters
+/// - `message`: Message bytes to be signed (Vec<u8>)
+/// - `key`: Optional key alias from the key registry
+///
+/// ### Returns
+/// - `signature`: 48-byte BLS12-381 G1 signature (not encrypted)
+/// - `Err(MeteringError)` without deriving if the caller's cycle balance is too low
+#[update(manual_reply = true)]
+async fn sign_as_canister(message: Vec<u8>, key: Option<String>) {
+    let caller = ic_cdk::api::caller();
+    if !is_authorized(caller) {
+        ic_cdk::trap("caller may not request canister signatures");
+    }
+
+    let key_id = keys::resolve_for_derivation(key, None, "canister");
+    let op = MeteredOp::SignAsCanister;
+    let Some(charged) = metering::charge_or_reply::<BlsSig>(caller, op, &key_id) else {
+        return;
+    };
+    let res = sign_with_context(key_id, canister_context(), message).await;
+    let res = res.map(|signature| BlsSig { signature });
+    metering::reply_or_refund(caller, charged, res);
+}
+
+/// Returns the 96-byte BLS12-381 G2 public key that verifies `sign_as_canister` signatures.
+#[update]
+async fn canister_bls_public_key(key: Option<String>) -> BlsPk {
+    let key_id = keys::resolve(key, None, "canister");
+    BlsPk { pk: derived_public_key(&key_id, &canister_context()).await.serialize().to_vec() }
+}
+
+/// Allows `principal` to call `sign_as_canister` (controllers only).
+#[update]
+fn add_canister_signer(principal: Principal) {
+    require_controller();
+    SIGNERS.with(|s| s.borrow_mut().insert(principal.as_slice().to_vec(), ()));
+}
+
+/// Revokes `principal`'s access to `sign_as_canister` (controllers only).
+#[update]
+fn remove_canister_signer(principal: Principal) -> bool {
+    require_controller();
+    SIGNERS.with(|s| s.borrow_mut().remove(&principal.as_slice().to_vec()).is_some())
+}
+
+/// Lists the allowlisted signers (controllers only).
+#[query]
+fn list_canister_signers() -> Vec<Principal> {
+    require_controller();
+    SIGNERS.with(|s| s.borrow().iter().map(|(k, _)| Principal::from_slice(&k)).collect())
+}
@@ -0,0 +1,57 @@
+//! Stable memory layout of the demo canister.
+//!
+//! Ids are permanent: never renumber or rename a region. A region that is no
+//! longer used moves to `retired` so its id is never handed out again.
+
+use dooor_memory_layout::{Layout, Region, RegionStats};
+use ic_cdk_macros::*;
+
+use crate::{require_controller, MM};
+
+pub const SIGNERS: u8 = 0;
+pub const USED_NONCES: u8 = 1;
+pub const NONCE_EXPIRY: u8 = 2;
+pub const LEGACY_SIGNING: u8 = 3;
+pub const ROUNDS: u8 = 4;
+pub const BEACON: u8 = 5;
+pub const TIMELOCK_KEYS: u8 = 6;
+pub const TIMELOCK_PENDING: u8 = 7;
+pub const KEY_REGISTRY: u8 = 8;
+pub const KEY_ASSIGNMENTS: u8 = 9;
+pub const CYCLE_BALANCES: u8 = 10;
+pub const METERING_CONFIG: u8 = 11;
+pub const AUDIT_LOG: u8 = 12;
+pub const KEY_DERIVED: u8 = 13;
+
+pub const LAYOUT: Layout = Layout {
+    regions: &[
+        Region::new(SIGNERS, "canister_signer.allowlist", "canister_signer"),
+        Region::new(USED_NONCES, "sign_request.used_nonces", "sign_request"),
+        Region::new(NONCE_EXPIRY, "sign_request.nonce_expiry", "sign_request"),
+        Region::new(LEGACY_SIGNING, "sign_request.legacy_flag", "sign_request"),
+        Region::new(ROUNDS, "aggregation.rounds", "aggregation"),
+        Region::new(BEACON, "beacon.entries", "beacon"),
+        Region::new(TIMELOCK_KEYS, "timelock.keys", "timelock"),
+        Region::new(TIMELOCK_PENDING, "timelock.pending", "timelock"),
+        Region::new(KEY_REGISTRY, "keys.registry", "keys"),
+        Region::new(KEY_ASSIGNMENTS, "keys.assignments", "keys"),
+        Region::new(CYCLE_BALANCES, "metering.balances", "metering"),
+        Region::new(METERING_CONFIG, "metering.config", "metering"),
+        Region::new(AUDIT_LOG, "audit.log", "audit"),
+        Region::new(KEY_DERIVED, "keys.derived", "keys"),
+    ],
+    retired: &[],
+};
+
+/// Checks the layout against the manifest in stable memory; traps on mismatch.
+pub(crate) fn validate() {
+    MM.with(|m| LAYOUT.validate(&m.borrow()))
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("stable memory layout: {e}")));
+}
+
+/// Per-region stable memory usage (controllers only).
+#[query]
+fn memory_stats() -> Vec<RegionStats> {
+    require_controller();
+    MM.with(|m| LAYOUT.stats(&m.borrow()))
+}
@@ -3,10 +3,19 @@
 //! This canister provides cryptographic operations using the Internet Computer's
 //! VetKD (Verified Key Derivation) API. It allows clients to:
 //! - Retrieve the subnet’s VetKD BLS12-381 G2 public key
-//! - Sign messages in a secure, derivation-based process using caller identity
+//! - Sign typed, domain-separated, replay-protected requests (`sign_request`)
+//! - Sign messages in a secure, derivation-based process using caller identity (legacy)
+//! - Verify such signatures on-chain against the signer's derived public key
+//! - Act as a threshold-BLS signing oracle for other canisters (`canister_signer`)
+//! - Aggregate co-signatures of several nodes into one certificate (`aggregation`)
+//! - Publish verifiable, unbiasable per-epoch randomness (`beacon`)
+//! - Release timelock decryption keys once their time slot has passed (`timelock`)
+//! - Select among several registered VetKD key ids per tenant or purpose (`keys`)
+//! - Charge derivations to prepaid per-principal cycle balances (`metering`)
 //!
 //! The signing process encrypts the derived key to the caller's transport public key,
-//! ensuring confidentiality even from the canister itself.
+//! ensuring confidentiality even from the canister itself. The signing oracle is the
+//! exception: its signatures are public by design, so it decrypts them in-canister.
 //
 // Author: Dooor Team
 // License: MIT
@@ -14,17 +23,110 @@
 use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::management_canister::{
     vetkd_public_key, vetkd_derive_key,
-    VetKDCurve, VetKDKeyId, VetKDPublicKeyArgs, VetKDDeriveKeyArgs,
+    VetKDKeyId, VetKDPublicKeyArgs, VetKDDeriveKeyArgs,
 };
 use ic_cdk_macros::*;
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager, VirtualMemory},
+    DefaultMemoryImpl,
+};
+use ic_vetkeys::{verify_bls_signature, DerivedPublicKey};
+use std::{cell::RefCell, collections::BTreeMap};
+
+/// `candid_storable!` implements `Storable` for Candid-serializable types.
+#[macro_use]
+extern crate dooor_vetkd_common;
+
+mod aggregation;
+mod audit;
+mod beacon;
+mod canister_signer;
+mod keys;
+mod layout;
+mod metering;
+mod sign_request;
+mod timelock;
+
+/// Virtual stable memory handed out by the memory manager.
+/// Ids are declared in `layout`.
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+thread_local! {
+    pub(crate) static MM: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    /// This canister's VetKD public keys (empty context) by key name, fetched once
+    /// per heap lifetime.
+    static CANISTER_PKS: RefCell<BTreeMap<String, Vec<u8>>> = RefCell::new(BTreeMap::new());
+}
+
+/// Returns the virtual memory with the given id; traps unless `layout` declares it.
+fn memory(id: u8) -> Memory {
+    if layout::LAYOUT.region(id).is_none() {
+        ic_cdk::trap(&format!("memory id {id} is not declared in the layout"));
+    }
+    MM.with(|m| m.borrow().get(MemoryId::new(id)))
+}
 
 /// Domain separation tag (DST) for the VetKD derivation context.
 /// This distinguishes keys used by this canister from others using the same subnet VetKD.
 const DS: &[u8] = b"bls_demo";
 
-/// Key identifier name registered with the VetKD API.
-/// Must match the key configured in the subnet.
-const KEY_NAME: &str = "key_1";
+/// Traps unless the caller is a controller of this canister.
+fn require_controller() {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        ic_cdk::trap("caller is not a controller");
+    }
+}
+
+/// Derives the public key for `context` locally from this canister's VetKD key `key_id`.
+///
+/// Only the canister-level key is fetched from the management canister (and cached);
+/// per-context keys are derived with `DerivedPublicKey::derive_sub_key`.
+async fn derived_public_key(key_id: &VetKDKeyId, context: &[u8]) -> DerivedPublicKey {
+    try_derived_public_key(key_id, context).await.unwrap_or_else(|e| ic_cdk::trap(&e))
+}
+
+/// Like `derived_public_key`, but returns errors instead of trapping.
+async fn try_derived_public_key(
+    key_id: &VetKDKeyId,
+    context: &[u8],
+) -> Result<DerivedPublicKey, String> {
+    let cached = CANISTER_PKS.with(|pks| pks.borrow().get(&key_id.name).cloned());
+    let canister_pk = match cached {
+        Some(pk) => pk,
+        None => {
+            let args = VetKDPublicKeyArgs {
+                canister_id: None,
+                context: vec![],
+                key_id: key_id.clone(),
+            };
+            let res = vetkd_public_key(&args).await
+                .map_err(|e| format!("VetKD public key error: {:?}", e))?;
+            CANISTER_PKS.with(|pks| {
+                pks.borrow_mut().insert(key_id.name.clone(), res.public_key.clone())
+            });
+            res.public_key
+        }
+    };
+
+    Ok(DerivedPublicKey::deserialize(&canister_pk)
+        .map_err(|_| "VetKD returned a malformed public key".to_string())?
+        .derive_sub_key(context))
+}
+
+#[init]
+fn init() {
+    layout::validate();
+    metering::enable_on_install();
+    timelock::start_publisher();
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    layout::validate();
+    timelock::start_publisher();
+}
 
 /// Constructs the VetKD derivation context: [len(DS)] || DS || caller_principal_bytes
 fn context(principal: Principal) -> Vec<u8> {
@@ -34,6 +136,13 @@ fn context(principal: Principal) -> Vec<u8> {
         .collect()
 }
 
+/// Constructs the VetKD derivation input that `sign_caller` signs: signer_principal_bytes || payload
+fn sign_input(signer: Principal, payload: &[u8]) -> Vec<u8> {
+    let mut input = signer.as_slice().to_vec();
+    input.extend_from_slice(payload);
+    input
+}
+
 /// Struct representing a BLS12-381 G2 public key.
 #[derive(CandidType, Deserialize)]
 struct BlsPk {
@@ -51,18 +160,19 @@ struct BlsSig {
 /// Returns the BLS12-381 G2 public key of the subnet's VetKD service.
 /// 
 /// This key is used for verifying signatures produced via VetKD derivation.
-/// 
+///
+/// ### Parameters
+/// - `key`: Optional key alias from the key registry
+///
 /// ### Returns
 /// - `pk`: 96-byte BLS12-381 G2 public key
 #[update]
-async fn bls_public_key() -> BlsPk {
+async fn bls_public_key(key: Option<String>) -> BlsPk {
+    let caller = ic_cdk::api::caller();
     let args = VetKDPublicKeyArgs {
         canister_id: None,
-        context: context(ic_cdk::api::caller()),
-        key_id: VetKDKeyId {
-            name: KEY_NAME.into(),
-            curve: VetKDCurve::Bls12_381_G2,
-        },
+        context: context(caller),
+        key_id: keys::resolve(key, Some(caller), "legacy"),
     };
 
     let res = vetkd_public_key(&args).await
@@ -75,38 +185,82 @@ async fn bls_public_key() -> BlsPk {
 /// Derives a signature over the message `<caller || payload>`, 
 /// encrypted to the provided transport public key.
 ///
+/// **Legacy mode.** The input is not length-prefixed and carries no purpose, nonce
+/// or expiry; use `sign_request` instead. Controllers can switch this endpoint off
+/// with `set_legacy_signing(false)`.
+///
 /// The actual signature (derived key) is not revealed to the canister —
 /// it is encrypted by the subnet using the provided transport key (BLS G1 or X25519).
 ///
 /// ### Parameters
 /// - `payload`: Message bytes to be signed (Vec<u8>)
 /// - `transport_public_key`: 48-byte BLS G1 or 32-byte X25519 public key
+/// - `key`: Optional key alias from the key registry
 ///
 /// ### Returns
 /// - `signature`: Encrypted VetKD-derived key (Vec<u8>)
-#[update]
-async fn sign_caller(payload: Vec<u8>, transport_public_key: Vec<u8>) -> BlsSig {
+/// - `Err(MeteringError)` without deriving if the caller's cycle balance is too low
+#[update(manual_reply = true)]
+async fn sign_caller(
+    payload: Vec<u8>,
+    transport_public_key: Vec<u8>,
+    key: Option<String>,
+) {
+    sign_request::ensure_legacy_enabled();
+
     // Validate transport key length
     if ![32, 48].contains(&transport_public_key.len()) {
         ic_cdk::trap("`transport_public_key` must be 32 (X25519) or 48 (BLS-G1) bytes");
     }
 
-    // Input = caller principal || payload
-    let mut input = ic_cdk::api::caller().as_slice().to_vec();
-    input.extend_from_slice(&payload);
-
+    let caller = ic_cdk::api::caller();
+    let key_id = keys::resolve_for_derivation(key, Some(caller), "legacy");
+    let op = metering::MeteredOp::SignCaller;
+    let Some(charged) = metering::charge_or_reply::<BlsSig>(caller, op, &key_id) else {
+        return;
+    };
     let args = VetKDDeriveKeyArgs {
-        input,
-        context: context(ic_cdk::api::caller()),
+        input: sign_input(caller, &payload),
+        context: context(caller),
         transport_public_key,
-        key_id: VetKDKeyId {
-            name: KEY_NAME.into(),
-            curve: VetKDCurve::Bls12_381_G2,
-        },
+        key_id,
     };
 
     let res = vetkd_derive_key(&args).await
-        .unwrap_or_else(|e| ic_cdk::trap(&format!("VetKD derive key error: {:?}", e)));
+        .map(|res| BlsSig { signature: res.encrypted_key })
+        .map_err(|e| format!("VetKD derive key error: {:?}", e));
+
+    metering::reply_or_refund(caller, charged, res);
+}
+
+/// Verifies a BLS signature produced by `sign_caller` for `signer`.
+///
+/// The signer's derived public key is derived from this canister's VetKD key for the
+/// context bound to `signer`, and `signature` is checked over `<signer || payload>`.
+/// Any canister can call this to trust a node-signed message without an
+/// off-chain verifier.
+///
+/// ### Parameters
+/// - `signer`: Principal that called `sign_caller`
+/// - `payload`: Message bytes that were signed (Vec<u8>)
+/// - `signature`: Decrypted 48-byte BLS12-381 G1 signature
+/// - `key`: Optional key alias the signature was made with
+///
+/// ### Returns
+/// - `true` if the signature is valid, `false` otherwise (including malformed input)
+#[update]
+async fn verify_signature(
+    signer: Principal,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+    key: Option<String>,
+) -> bool {
+    if signature.len() != 48 {
+        return false;
+    }
+
+    let key_id = keys::resolve(key, Some(signer), "legacy");
+    let dpk = derived_public_key(&key_id, &context(signer)).await;
 
-    BlsSig { signature: res.encrypted_key }
+    verify_bls_signature(&dpk, &sign_input(signer, &payload), &signature)
 }
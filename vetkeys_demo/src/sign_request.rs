@@ -0,0 +1,247 @@
+//! Domain-separated, replay-protected signing (`sign_request`).
+//!
+//! `sign_caller` signs `caller || payload` with no length prefixes and no notion
+//! of purpose, so it is kept only as a legacy mode. Structured requests are
+//! encoded unambiguously and signed under their own VetKD context:
+//!
+//! - VetKD `context = [len(SIGN_DS)] || SIGN_DS || caller_principal_bytes`
+//! - VetKD `input = SIGN_TAG || lp(signer) || lp(purpose) || lp(nonce) || expires_at_be || lp(payload)`
+//!
+//! where `lp(x) = u32_be(len(x)) || x`. Each `(signer, nonce)` pair is accepted
+//! once; used nonces are kept in stable memory until their request expires.
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::management_canister::{vetkd_derive_key, VetKDDeriveKeyArgs};
+use ic_cdk_macros::*;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use ic_vetkeys::verify_bls_signature;
+use std::cell::RefCell;
+
+use crate::metering::{self, MeteredOp};
+use crate::{derived_public_key, keys, layout, memory, require_controller, BlsPk, BlsSig, Memory};
+
+/// Domain separation tag for the structured signing context.
+const SIGN_DS: &[u8] = b"bls_demo.sign.v2";
+
+/// Version tag at the start of every encoded request.
+const SIGN_TAG: &[u8] = b"dooor-sign-request-v1";
+
+/// Longest accepted validity window; bounds how long nonces must be remembered.
+const MAX_VALIDITY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Longest accepted nonce.
+const MAX_NONCE_LEN: usize = 64;
+
+/// Expired nonces pruned per signing call.
+const PRUNE_BATCH: usize = 32;
+
+/// What a signature is for. Signatures for one purpose never verify for another.
+#[derive(CandidType, Deserialize, Clone)]
+pub enum SignPurpose {
+    Jwt,
+    Manifest,
+    Deployment,
+    Custom(String),
+}
+
+impl SignPurpose {
+    fn tag(&self) -> Vec<u8> {
+        match self {
+            SignPurpose::Jwt => b"jwt".to_vec(),
+            SignPurpose::Manifest => b"manifest".to_vec(),
+            SignPurpose::Deployment => b"deployment".to_vec(),
+            SignPurpose::Custom(name) => [b"custom:".as_slice(), name.as_bytes()].concat(),
+        }
+    }
+}
+
+/// A typed signing request.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct SignRequest {
+    pub purpose: SignPurpose,
+    /// Caller-chosen unique value (1..=64 bytes); reuse is rejected.
+    pub nonce: Vec<u8>,
+    /// Expiry in nanoseconds since the epoch (at most 24h ahead).
+    pub expires_at: u64,
+    pub payload: Vec<u8>,
+}
+
+thread_local! {
+    /// (signer, nonce) -> expires_at
+    static NONCES: RefCell<StableBTreeMap<Vec<u8>, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::USED_NONCES)));
+
+    /// expires_at_be || (signer, nonce) -> (), for pruning in expiry order
+    static NONCE_EXPIRY: RefCell<StableBTreeMap<Vec<u8>, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::NONCE_EXPIRY)));
+
+    /// Whether the legacy `sign_caller` mode is still enabled.
+    static LEGACY_ENABLED: RefCell<StableCell<bool, Memory>> = RefCell::new(
+        StableCell::init(memory(layout::LEGACY_SIGNING), true)
+            .expect("failed to init legacy signing flag"),
+    );
+}
+
+fn push_lp(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Constructs the structured signing context: [len(SIGN_DS)] || SIGN_DS || signer_bytes
+fn sign_context(signer: Principal) -> Vec<u8> {
+    std::iter::once(SIGN_DS.len() as u8)
+        .chain(SIGN_DS.iter().copied())
+        .chain(signer.as_slice().iter().copied())
+        .collect()
+}
+
+/// Length-prefix encodes `req` for `signer` into the VetKD input.
+fn encode_input(signer: Principal, req: &SignRequest) -> Vec<u8> {
+    let mut out = SIGN_TAG.to_vec();
+    push_lp(&mut out, signer.as_slice());
+    push_lp(&mut out, &req.purpose.tag());
+    push_lp(&mut out, &req.nonce);
+    out.extend_from_slice(&req.expires_at.to_be_bytes());
+    push_lp(&mut out, &req.payload);
+    out
+}
+
+fn nonce_key(signer: Principal, nonce: &[u8]) -> Vec<u8> {
+    let mut key = Vec::new();
+    push_lp(&mut key, signer.as_slice());
+    key.extend_from_slice(nonce);
+    key
+}
+
+/// Drops up to `PRUNE_BATCH` nonces whose requests expired before `now`.
+fn prune_nonces(now: u64) {
+    let expired: Vec<Vec<u8>> = NONCE_EXPIRY.with(|e| {
+        e.borrow()
+            .iter()
+            .take(PRUNE_BATCH)
+            .map(|(k, _)| k)
+            .take_while(|k| u64::from_be_bytes(k[..8].try_into().unwrap()) < now)
+            .collect()
+    });
+    for k in expired {
+        NONCE_EXPIRY.with(|e| e.borrow_mut().remove(&k));
+        NONCES.with(|n| n.borrow_mut().remove(&k[8..].to_vec()));
+    }
+}
+
+/// Validates expiry and records the nonce, trapping on replays.
+fn consume_nonce(signer: Principal, req: &SignRequest) {
+    let now = ic_cdk::api::time();
+    if req.expires_at <= now {
+        ic_cdk::trap("sign request has expired");
+    }
+    if req.expires_at - now > MAX_VALIDITY_NS {
+        ic_cdk::trap("`expires_at` must be at most 24h in the future");
+    }
+    if req.nonce.is_empty() || req.nonce.len() > MAX_NONCE_LEN {
+        ic_cdk::trap("`nonce` must be 1..=64 bytes");
+    }
+
+    prune_nonces(now);
+
+    let key = nonce_key(signer, &req.nonce);
+    if NONCES.with(|n| n.borrow().contains_key(&key)) {
+        ic_cdk::trap("nonce already used");
+    }
+    NONCES.with(|n| n.borrow_mut().insert(key.clone(), req.expires_at));
+    let mut expiry_key = req.expires_at.to_be_bytes().to_vec();
+    expiry_key.extend_from_slice(&key);
+    NONCE_EXPIRY.with(|e| e.borrow_mut().insert(expiry_key, ()));
+}
+
+/// Traps unless the legacy `sign_caller` mode is enabled.
+pub(crate) fn ensure_legacy_enabled() {
+    if !LEGACY_ENABLED.with(|l| *l.borrow().get()) {
+        ic_cdk::trap("legacy `sign_caller` is disabled; use `sign_request`");
+    }
+}
+
+/// Derives a signature over the encoded `request`, encrypted to the transport key.
+///
+/// The nonce is consumed before the VetKD call, so a failed derivation still burns it.
+/// A call refused for its cycle balance does not consume it.
+///
+/// ### Parameters
+/// - `request`: Typed request (purpose, nonce, expiry, payload)
+/// - `transport_public_key`: 48-byte BLS G1 or 32-byte X25519 public key
+/// - `key`: Optional key alias from the key registry
+///
+/// ### Returns
+/// - `signature`: Encrypted VetKD-derived key (Vec<u8>)
+/// - `Err(MeteringError)` without deriving if the caller's cycle balance is too low
+#[update(manual_reply = true)]
+async fn sign_request(
+    request: SignRequest,
+    transport_public_key: Vec<u8>,
+    key: Option<String>,
+) {
+    if ![32, 48].contains(&transport_public_key.len()) {
+        ic_cdk::trap("`transport_public_key` must be 32 (X25519) or 48 (BLS-G1) bytes");
+    }
+
+    let caller = ic_cdk::api::caller();
+    let key_id = keys::resolve_for_derivation(key, Some(caller), "sign");
+    let op = MeteredOp::SignRequest;
+    let Some(charged) = metering::charge_or_reply::<BlsSig>(caller, op, &key_id) else {
+        return;
+    };
+    consume_nonce(caller, &request);
+
+    let args = VetKDDeriveKeyArgs {
+        input: encode_input(caller, &request),
+        context: sign_context(caller),
+        transport_public_key,
+        key_id,
+    };
+
+    let res = vetkd_derive_key(&args).await
+        .map(|res| BlsSig { signature: res.encrypted_key })
+        .map_err(|e| format!("VetKD derive key error: {:?}", e));
+
+    metering::reply_or_refund(caller, charged, res);
+}
+
+/// Verifies a decrypted `sign_request` signature made by `signer` over `request`.
+///
+/// Expiry is not checked here: callers decide whether an expired request is still
+/// acceptable to them.
+#[update]
+async fn verify_sign_request(
+    signer: Principal,
+    request: SignRequest,
+    signature: Vec<u8>,
+    key: Option<String>,
+) -> bool {
+    if signature.len() != 48 {
+        return false;
+    }
+
+    let key_id = keys::resolve(key, Some(signer), "sign");
+    let dpk = derived_public_key(&key_id, &sign_context(signer)).await;
+
+    verify_bls_signature(&dpk, &encode_input(signer, &request), &signature)
+}
+
+/// Returns the 96-byte G2 public key for `signer`'s structured signatures.
+#[update]
+async fn sign_request_public_key(signer: Principal, key: Option<String>) -> BlsPk {
+    let key_id = keys::resolve(key, Some(signer), "sign");
+    BlsPk { pk: derived_public_key(&key_id, &sign_context(signer)).await.serialize().to_vec() }
+}
+
+/// Enables or disables the legacy `sign_caller` mode (controllers only).
+#[update]
+fn set_legacy_signing(enabled: bool) {
+    require_controller();
+    LEGACY_ENABLED.with(|l| l.borrow_mut().set(enabled).expect("failed to persist flag"));
+}
+
+#[query]
+fn legacy_signing_enabled() -> bool {
+    LEGACY_ENABLED.with(|l| *l.borrow().get())
+}
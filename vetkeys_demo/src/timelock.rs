@@ -0,0 +1,175 @@
+//! Timelock encryption on top of VetKD IBE.
+//!
+//! Clients IBE-encrypt to the identity `time|<unix_ts>` under
+//! `timelock_public_key()`. Nobody (including the canister) holds the matching
+//! key until a periodic timer derives it after `unix_ts` has passed and
+//! publishes it in stable memory, where `get_timelock_key(ts)` serves it.
+//!
+//! - VetKD `context = [len(TIMELOCK_DS)] || TIMELOCK_DS`
+//! - VetKD `input = "time|" || decimal(unix_ts)`
+//!
+//! Slots are whole minutes. A slot is only published if it was registered with
+//! `register_timelock`, so the canister does not derive a key every minute.
+//! Each registration costs the canister a derivation, so only controllers and
+//! allowlisted canister signers may register slots.
+
+use ic_cdk_macros::*;
+use ic_stable_structures::StableBTreeMap;
+use std::{cell::RefCell, time::Duration};
+
+use crate::canister_signer::{is_authorized, sign_with_context};
+use crate::metering::{self, MeteredOp, MeteringError};
+use crate::{derived_public_key, keys, layout, memory, BlsPk, Memory};
+
+/// Domain separation tag for the timelock context.
+const TIMELOCK_DS: &[u8] = b"bls_demo.timelock";
+
+/// Slot granularity; timestamps must be multiples of this.
+pub const SLOT_SECS: u64 = 60;
+
+/// How far ahead a slot may be registered.
+const MAX_HORIZON_SECS: u64 = 366 * 24 * 60 * 60;
+
+/// Upper bound on registered, not yet published slots.
+const MAX_PENDING: u64 = 4096;
+
+/// Slots published per timer tick.
+const PUBLISH_BATCH: usize = 8;
+
+/// How often the publisher looks for due slots.
+const PUBLISH_PERIOD: Duration = Duration::from_secs(SLOT_SECS);
+
+/// After this long a publish run is assumed to have trapped and the lock is freed.
+const PUBLISH_LOCK_SECS: u64 = 10 * 60;
+
+thread_local! {
+    /// unix_ts -> published 48-byte VetKey
+    static KEYS: RefCell<StableBTreeMap<u64, Vec<u8>, Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::TIMELOCK_KEYS)));
+
+    /// unix_ts -> (), registered slots awaiting publication
+    static PENDING: RefCell<StableBTreeMap<u64, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(memory(layout::TIMELOCK_PENDING)));
+
+    /// Start time of the publish run in flight, so ticks never overlap.
+    static PUBLISHING: RefCell<Option<u64>> = const { RefCell::new(None) };
+}
+
+/// Constructs the timelock context: [len(TIMELOCK_DS)] || TIMELOCK_DS
+fn timelock_context() -> Vec<u8> {
+    std::iter::once(TIMELOCK_DS.len() as u8)
+        .chain(TIMELOCK_DS.iter().copied())
+        .collect()
+}
+
+/// IBE identity of the slot at `ts`: `time|<ts>`
+fn identity(ts: u64) -> Vec<u8> {
+    format!("time|{ts}").into_bytes()
+}
+
+fn now_secs() -> u64 {
+    ic_cdk::api::time() / 1_000_000_000
+}
+
+/// Starts the periodic publisher. Called from `init` and `post_upgrade`.
+pub(crate) fn start_publisher() {
+    ic_cdk_timers::set_timer_interval(PUBLISH_PERIOD, || ic_cdk::futures::spawn(publish_due()));
+}
+
+/// Derives and stores the keys of up to `PUBLISH_BATCH` slots that have passed.
+async fn publish_due() {
+    let now = now_secs();
+    let busy = PUBLISHING.with(|p| {
+        let mut p = p.borrow_mut();
+        if p.is_some_and(|started| now < started + PUBLISH_LOCK_SECS) {
+            return true;
+        }
+        *p = Some(now);
+        false
+    });
+    if busy {
+        return;
+    }
+
+    let due: Vec<u64> = PENDING.with(|p| {
+        p.borrow()
+            .iter()
+            .map(|(ts, _)| ts)
+            .take_while(|ts| *ts <= now)
+            .take(PUBLISH_BATCH)
+            .collect()
+    });
+
+    for ts in due {
+        // A failed derivation traps this task only; the slot stays pending and is
+        // retried once the lock expires.
+        let key_id = keys::resolve_for_derivation(None, None, "timelock");
+        let key = sign_with_context(key_id, timelock_context(), identity(ts))
+            .await
+            .unwrap_or_else(|e| ic_cdk::trap(&e));
+        KEYS.with(|k| k.borrow_mut().insert(ts, key));
+        PENDING.with(|p| p.borrow_mut().remove(&ts));
+    }
+
+    PUBLISHING.with(|p| *p.borrow_mut() = None);
+}
+
+/// Registers the slot `ts` for publication and returns its IBE identity.
+///
+/// Only registered slots are ever published: data encrypted to an unregistered
+/// `time|<ts>` stays sealed. Only controllers and allowlisted canister signers
+/// may register.
+///
+/// ### Parameters
+/// - `ts`: Unix timestamp in seconds, a multiple of 60, at most one year ahead
+///
+/// ### Returns
+/// - The identity bytes to encrypt to (`time|<ts>`)
+/// - `Err(MeteringError)` if the slot is new and the caller's cycle balance is too low
+#[update]
+fn register_timelock(ts: u64) -> Result<Vec<u8>, MeteringError> {
+    let caller = ic_cdk::api::caller();
+    if !is_authorized(caller) {
+        ic_cdk::trap("caller may not register timelock slots");
+    }
+    if ts % SLOT_SECS != 0 {
+        ic_cdk::trap("`ts` must be a multiple of 60 seconds");
+    }
+    if ts > now_secs() + MAX_HORIZON_SECS {
+        ic_cdk::trap("`ts` is too far in the future");
+    }
+
+    let known = KEYS.with(|k| k.borrow().contains_key(&ts))
+        || PENDING.with(|p| p.borrow().contains_key(&ts));
+    if !known {
+        if PENDING.with(|p| p.borrow().len()) >= MAX_PENDING {
+            ic_cdk::trap("too many pending timelock slots");
+        }
+        let key_id = keys::resolve_for_derivation(None, None, "timelock");
+        metering::charge(caller, MeteredOp::Timelock, &key_id)?;
+        PENDING.with(|p| p.borrow_mut().insert(ts, ()));
+    }
+
+    Ok(identity(ts))
+}
+
+/// Returns the published decryption key (a 48-byte VetKey) for slot `ts`.
+///
+/// `None` until the slot has passed and the publisher has run.
+#[query]
+fn get_timelock_key(ts: u64) -> Option<Vec<u8>> {
+    KEYS.with(|k| k.borrow().get(&ts))
+}
+
+/// Lists registered slots that are not yet published, oldest first.
+#[query]
+fn list_pending_timelocks() -> Vec<u64> {
+    PENDING.with(|p| p.borrow().iter().map(|(ts, _)| ts).collect())
+}
+
+/// Returns the 96-byte G2 IBE master public key that clients encrypt under.
+#[update]
+async fn timelock_public_key() -> BlsPk {
+    let key_id = keys::resolve(None, None, "timelock");
+    BlsPk { pk: derived_public_key(&key_id, &timelock_context()).await.serialize().to_vec() }
+}